@@ -0,0 +1,2222 @@
+//! mDNS advertising and browsing, and the cache of discovered devices.
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use if_addrs::get_if_addrs;
+use log::{debug, error, info, warn};
+use searchlight::{
+    broadcast::{BroadcasterBuilder, Service, ServiceBuilder},
+    discovery::{DiscoveryBuilder, DiscoveryEvent, DiscoveryHandle, Responder},
+    net::IpVersion,
+};
+use serde::Serialize;
+use tauri::{Emitter, Manager, State};
+
+use crate::discovery_http::publish_device_event;
+use crate::protocol::PROTOCOL_VERSION;
+use crate::settings::{
+    save_settings, DiscoveryRetryPolicy, SavedService, MAX_DISCOVERY_RETRIES,
+    MAX_DISCOVERY_RETRY_DELAY_MS,
+};
+use crate::{unix_millis, LockExt, MdnsState};
+
+#[derive(Clone)]
+pub(crate) struct ServiceInfo {
+    service_type: String,
+    instance_name: String,
+    port: u16,
+    txt: Vec<String>,
+    ip_version: IpVersion,
+    // Advertise virtual interfaces too; see `select_local_ips`
+    include_virtual: bool,
+    // Record TTL and re-announce interval; None leaves searchlight's defaults. See
+    // `validate_announce_timing`.
+    ttl_secs: Option<u32>,
+    announce_interval_secs: Option<u64>,
+}
+
+// "v4", "v6" or "both" (the default). Forcing one family works around networks where
+// dual-stack mDNS gives duplicate or missing responders.
+fn parse_ip_version(value: Option<&str>) -> Result<IpVersion, String> {
+    match value.map(|v| v.trim().to_lowercase()).as_deref() {
+        None | Some("") | Some("both") => Ok(IpVersion::Both),
+        Some("v4") => Ok(IpVersion::V4),
+        Some("v6") => Ok(IpVersion::V6),
+        Some(other) => Err(format!(
+            "Unknown IP version '{}' (use \"v4\", \"v6\" or \"both\")",
+            other
+        )),
+    }
+}
+
+fn ip_version_name(version: IpVersion) -> &'static str {
+    match version {
+        IpVersion::V4 => "v4",
+        IpVersion::V6 => "v6",
+        IpVersion::Both => "both",
+    }
+}
+
+// Per-session discovery counters, reset whenever discovery starts. searchlight only
+// hands us responses for the browsed service type, so "responses" counts those rather
+// than every multicast packet seen on the wire.
+#[derive(Default)]
+pub(crate) struct DiscoveryStats {
+    responses: AtomicU64,
+    parse_failures: AtomicU64,
+    responders_found: AtomicU64,
+    responders_lost: AtomicU64,
+    suppressed_updates: AtomicU64,
+    coalesced_updates: AtomicU64,
+    filtered_out: AtomicU64,
+    pub(crate) parse_cache_hits: AtomicU64,
+}
+
+impl DiscoveryStats {
+    fn reset(&self) {
+        self.responses.store(0, Ordering::Relaxed);
+        self.parse_failures.store(0, Ordering::Relaxed);
+        self.responders_found.store(0, Ordering::Relaxed);
+        self.responders_lost.store(0, Ordering::Relaxed);
+        self.suppressed_updates.store(0, Ordering::Relaxed);
+        self.coalesced_updates.store(0, Ordering::Relaxed);
+        self.filtered_out.store(0, Ordering::Relaxed);
+        self.parse_cache_hits.store(0, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> serde_json::Value {
+        serde_json::json!({
+            "responses": self.responses.load(Ordering::Relaxed),
+            "parse_failures": self.parse_failures.load(Ordering::Relaxed),
+            "responders_found": self.responders_found.load(Ordering::Relaxed),
+            "responders_lost": self.responders_lost.load(Ordering::Relaxed),
+            "suppressed_updates": self.suppressed_updates.load(Ordering::Relaxed),
+            "coalesced_updates": self.coalesced_updates.load(Ordering::Relaxed),
+            "filtered_out": self.filtered_out.load(Ordering::Relaxed),
+            "parse_cache_hits": self.parse_cache_hits.load(Ordering::Relaxed)
+        })
+    }
+}
+
+// Collect non-loopback IPs so we can advertise the service.
+fn local_ips(include_virtual: bool) -> Vec<IpAddr> {
+    select_local_ips(include_virtual)
+        .0
+        .into_iter()
+        .map(|(_, ip)| ip)
+        .collect()
+}
+
+// (interface name, address)
+type InterfaceIp = (String, IpAddr);
+
+// Non-loopback (interface name, IP) pairs split into (advertised, skipped). Unless
+// `include_virtual` is set, virtual interfaces are skipped - but only while a physical
+// one is available, so a machine that is only reachable over a VPN still advertises.
+fn select_local_ips(include_virtual: bool) -> (Vec<InterfaceIp>, Vec<InterfaceIp>) {
+    let ifaces: Vec<InterfaceIp> = get_if_addrs()
+        .map(|ifaces| {
+            ifaces
+                .into_iter()
+                .filter(|iface| !iface.is_loopback())
+                .map(|iface| {
+                    let ip = iface.ip();
+                    (iface.name, ip)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    if include_virtual {
+        return (ifaces, Vec::new());
+    }
+
+    let (virtual_ifaces, physical): (Vec<_>, Vec<_>) = ifaces
+        .into_iter()
+        .partition(|(name, ip)| is_virtual_interface(name, *ip));
+    if physical.is_empty() {
+        (virtual_ifaces, Vec::new())
+    } else {
+        (physical, virtual_ifaces)
+    }
+}
+
+// Interfaces phones on the LAN usually can't reach: link-local addresses, VPN tunnels
+// (including Tailscale's 100.64.0.0/10), container bridges and VM host-only adapters.
+// Name-based, so it's a best guess.
+fn is_virtual_interface(name: &str, ip: IpAddr) -> bool {
+    let link_local = match ip {
+        IpAddr::V4(v4) => v4.is_link_local(),
+        IpAddr::V6(v6) => (v6.segments()[0] & 0xffc0) == 0xfe80,
+    };
+    let vpn_or_container_range = match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            (a == 100 && (64..128).contains(&b)) || (a == 172 && b == 17)
+        }
+        IpAddr::V6(_) => false,
+    };
+
+    let name = name.to_lowercase();
+    const VIRTUAL_PREFIXES: [&str; 14] = [
+        "docker",
+        "br-",
+        "veth",
+        "virbr",
+        "vmnet",
+        "vboxnet",
+        "utun",
+        "tun",
+        "tap",
+        "wg",
+        "zt",
+        "tailscale",
+        "ipsec",
+        "ppp",
+    ];
+    const VIRTUAL_NAMES: [&str; 5] = ["virtualbox", "vmware", "hyper-v", "vethernet", "vpn"];
+    let virtual_name = VIRTUAL_PREFIXES.iter().any(|p| name.starts_with(p))
+        || VIRTUAL_NAMES.iter().any(|n| name.contains(n));
+
+    link_local || vpn_or_container_range || virtual_name
+}
+
+// Local addresses for display, IPv4 first
+#[tauri::command]
+pub(crate) fn get_local_ips(exclude_virtual: Option<bool>) -> Result<Vec<String>, String> {
+    let exclude_virtual = exclude_virtual.unwrap_or(false);
+    let ifaces = get_if_addrs().map_err(|e| format!("Failed to list network interfaces: {e}"))?;
+    let mut ips: Vec<IpAddr> = ifaces
+        .iter()
+        .filter(|iface| !iface.is_loopback())
+        .filter(|iface| !exclude_virtual || !is_virtual_interface(&iface.name, iface.ip()))
+        .map(|iface| iface.ip())
+        .collect();
+    ips.sort();
+    ips.dedup();
+    Ok(ips.iter().map(|ip| ip.to_string()).collect())
+}
+
+// Build a broadcastable service from stored info, advertising the current local IPs
+fn build_service(info: &ServiceInfo) -> Result<Service, String> {
+    let ips = local_ips(info.include_virtual);
+    if ips.is_empty() {
+        return Err("No non-loopback IPs found for advertisement".into());
+    }
+
+    let mut svc = ServiceBuilder::new(&info.service_type, &info.instance_name, info.port)
+        .map_err(|e| format!("invalid service params: {e}"))?;
+    for ip in ips {
+        svc = svc.add_ip_address(ip);
+    }
+    for rec in &info.txt {
+        svc = svc.add_txt_truncated(rec.clone());
+    }
+    if let Some(ttl) = info.ttl_secs {
+        svc = svc.ttl(ttl);
+    }
+
+    svc.build()
+        .map_err(|e| format!("service build failed: {e}"))
+}
+
+#[derive(Serialize, Clone, Hash)]
+pub(crate) struct FoundDevice {
+    name: String,
+    hostname: String,
+    // Source address of the mDNS response; kept for older clients
+    addr: String,
+    // Addresses from the response's A/AAAA records for the host, then `addr` if it
+    // wasn't among them. Clients should try these in order.
+    addresses: Vec<String>,
+    port: u16,
+    txt: Vec<String>,
+    // `txt` split on the first "=" (entries without one map to ""). A BTreeMap keeps
+    // FoundDevice hashable and serializes to the same JSON object.
+    txt_map: std::collections::BTreeMap<String, String>,
+    // The desktop socket server port from the "socketPort" TXT record
+    socket_port: Option<u16>,
+    // SRV selection hints; None when the response carried no SRV record
+    priority: Option<u16>,
+    weight: Option<u16>,
+    alias: Option<String>,
+    // From the "room" TXT record
+    room: Option<String>,
+    // App version and command protocol number from the "version" and "proto" TXT
+    // records; None for desktops that predate them
+    version: Option<String>,
+    proto: Option<u32>,
+}
+
+// ---- Device cache ----
+// Devices seen by the current discovery session, keyed by instance name. The cache is
+// capped so a network with thousands of services can't grow it without bound; when
+// full, the least recently seen device is dropped (never the selected one).
+pub(crate) struct CachedDevice {
+    pub(crate) device: FoundDevice,
+    // The latest announcement from each address the instance answered from, for
+    // get_discovered_devices; `device` is whichever of them came last
+    by_addr: std::collections::BTreeMap<String, FoundDevice>,
+    // Hash of every emitted field, used to drop re-announcements that change nothing
+    hash: u64,
+    pub(crate) last_seen: std::time::Instant,
+    // When "mdns:update" last went out, and whether a coalesced one is still due
+    last_update_at: Option<std::time::Instant>,
+    update_scheduled: bool,
+}
+
+const DEFAULT_MAX_CACHED_DEVICES: usize = 1_000;
+const MAX_CACHED_DEVICES_LIMIT: usize = 100_000;
+
+fn device_key(device: &FoundDevice) -> String {
+    if device.name.is_empty() {
+        format!("{}:{}", device.addr, device.port)
+    } else {
+        device.name.clone()
+    }
+}
+
+fn device_hash(device: &FoundDevice) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    device.hash(&mut hasher);
+    hasher.finish()
+}
+
+// Returns false when the device was already cached with identical contents
+fn cache_device(app: &tauri::AppHandle, device: &FoundDevice) -> bool {
+    let state: State<MdnsState> = app.state();
+    let hash = device_hash(device);
+    let changed = {
+        let mut devices = state.devices.lock_or_recover();
+        let key = device_key(device);
+        let previous = devices.remove(&key);
+        let changed = previous.as_ref().is_none_or(|cached| cached.hash != hash);
+        let (last_update_at, update_scheduled, mut by_addr) = previous
+            .map(|cached| {
+                (
+                    cached.last_update_at,
+                    cached.update_scheduled,
+                    cached.by_addr,
+                )
+            })
+            .unwrap_or_default();
+        by_addr.insert(device.addr.clone(), device.clone());
+        devices.insert(
+            key,
+            CachedDevice {
+                device: device.clone(),
+                by_addr,
+                hash,
+                last_seen: std::time::Instant::now(),
+                last_update_at,
+                update_scheduled,
+            },
+        );
+        changed
+    };
+    enforce_device_cap(app);
+    changed
+}
+
+// ---- Update debouncing ----
+// Chatty responders can change their records several times within milliseconds. Real
+// changes to one device are coalesced so "mdns:update" goes out at most once per
+// update debounce interval: the first is emitted right away, later ones within the
+// interval schedule a single trailing event carrying the device as it is by then.
+// Found/lost events are never delayed.
+const DEFAULT_UPDATE_DEBOUNCE_MS: u64 = 1_000;
+const MAX_UPDATE_DEBOUNCE_MS: u64 = 60_000;
+
+// Whether an update for `device` may be emitted now; if not, a trailing one is queued
+fn debounce_update(app: &tauri::AppHandle, device: &FoundDevice) -> bool {
+    let state: State<MdnsState> = app.state();
+    let interval = std::time::Duration::from_millis(
+        state
+            .settings
+            .lock_or_recover()
+            .update_debounce_ms
+            .unwrap_or(DEFAULT_UPDATE_DEBOUNCE_MS),
+    );
+
+    let key = device_key(device);
+    let mut devices = state.devices.lock_or_recover();
+    let Some(cached) = devices.get_mut(&key) else {
+        return true;
+    };
+    let now = std::time::Instant::now();
+    let elapsed = cached.last_update_at.map(|at| now.duration_since(at));
+    match elapsed {
+        Some(elapsed) if elapsed < interval => {
+            state
+                .discovery_stats
+                .coalesced_updates
+                .fetch_add(1, Ordering::Relaxed);
+            if !cached.update_scheduled {
+                cached.update_scheduled = true;
+                let app = app.clone();
+                tauri::async_runtime::spawn(async move {
+                    tokio::time::sleep(interval - elapsed).await;
+                    emit_coalesced_update(&app, &key);
+                });
+            }
+            false
+        }
+        _ => {
+            cached.last_update_at = Some(now);
+            true
+        }
+    }
+}
+
+fn emit_coalesced_update(app: &tauri::AppHandle, key: &str) {
+    let state: State<MdnsState> = app.state();
+    let device = {
+        let mut devices = state.devices.lock_or_recover();
+        // Lost (or evicted) meanwhile: there is nothing left to update
+        let Some(cached) = devices.get_mut(key) else {
+            return;
+        };
+        cached.update_scheduled = false;
+        cached.last_update_at = Some(std::time::Instant::now());
+        cached.device.clone()
+    };
+
+    publish_device_event(&state, "mdns:update", &device);
+    let _ = app.emit("mdns:update", device);
+}
+
+#[tauri::command]
+pub(crate) fn set_update_debounce(state: State<MdnsState>, interval_ms: u64) -> Result<(), String> {
+    if interval_ms > MAX_UPDATE_DEBOUNCE_MS {
+        return Err(format!(
+            "Update debounce must be between 0 (off) and {} ms",
+            MAX_UPDATE_DEBOUNCE_MS
+        ));
+    }
+
+    let mut settings = state.settings.lock_or_recover();
+    settings.update_debounce_ms = Some(interval_ms);
+    save_settings(&settings)?;
+
+    info!("Device updates debounced to one per {} ms", interval_ms);
+    Ok(())
+}
+
+// Drops the address `device` answered from; the instance goes once none are left
+fn forget_device(app: &tauri::AppHandle, device: &FoundDevice) {
+    let state: State<MdnsState> = app.state();
+    let mut devices = state.devices.lock_or_recover();
+    let key = device_key(device);
+    let Some(cached) = devices.get_mut(&key) else {
+        return;
+    };
+    cached.by_addr.remove(&device.addr);
+    match cached.by_addr.values().next_back() {
+        Some(remaining) => cached.device = remaining.clone(),
+        None => {
+            devices.remove(&key);
+        }
+    }
+}
+
+fn enforce_device_cap(app: &tauri::AppHandle) {
+    let state: State<MdnsState> = app.state();
+    let cap = state
+        .settings
+        .lock_or_recover()
+        .max_cached_devices
+        .unwrap_or(DEFAULT_MAX_CACHED_DEVICES);
+    let selected = state.selected_device.lock_or_recover().clone();
+
+    let mut evicted = Vec::new();
+    {
+        let mut devices = state.devices.lock_or_recover();
+        while devices.len() > cap {
+            let oldest = devices
+                .iter()
+                .filter(|(key, _)| selected.as_ref() != Some(*key))
+                .min_by_key(|(_, cached)| cached.last_seen)
+                .map(|(key, _)| key.clone());
+            let Some(key) = oldest else {
+                break;
+            };
+            if let Some(cached) = devices.remove(&key) {
+                evicted.push(cached.device);
+            }
+        }
+    }
+
+    for device in evicted {
+        info!(
+            "Evicting {} - device cache is full ({})",
+            device_key(&device),
+            cap
+        );
+        let _ = app.emit(
+            "mdns:evicted",
+            serde_json::json!({ "device": device, "reason": "cap", "max_devices": cap }),
+        );
+    }
+}
+
+const MAX_ALIAS_LEN: usize = 64;
+
+// Aliases are looked up by the stable TXT "id" first, then by the full service name,
+// then by the bare instance label, so either key works with `set_device_alias`.
+fn lookup_alias(aliases: &HashMap<String, String>, name: &str, txt: &[String]) -> Option<String> {
+    let txt_id = txt
+        .iter()
+        .find_map(|rec| rec.strip_prefix("id="))
+        .filter(|id| !id.is_empty());
+    let instance = name.split('.').next().unwrap_or(name);
+
+    txt_id
+        .and_then(|id| aliases.get(id))
+        .or_else(|| aliases.get(name))
+        .or_else(|| aliases.get(instance))
+        .cloned()
+}
+
+// A DNS label, which the instance name is sent as, holds at most 63 bytes
+const MAX_INSTANCE_NAME_LEN: usize = 63;
+
+// Catch the mistakes ServiceBuilder only reports as "invalid service params".
+// Service types look like "_name._tcp.local." (RFC 6763: the name is 1-15 letters,
+// digits or hyphens).
+fn validate_service_params(
+    service_type: &str,
+    instance_name: &str,
+    port: u16,
+) -> Result<(), String> {
+    if port == 0 {
+        return Err("Port must be between 1 and 65535".into());
+    }
+
+    let shape_error = || {
+        format!(
+            "Invalid service type '{}': expected the form \"_name._tcp.local.\" or \"_name._udp.local.\" (note the trailing dot)",
+            service_type
+        )
+    };
+    let rest = service_type
+        .strip_suffix("._tcp.local.")
+        .or_else(|| service_type.strip_suffix("._udp.local."))
+        .ok_or_else(shape_error)?;
+    let name = rest.strip_prefix('_').ok_or_else(shape_error)?;
+    let name_ok = (1..=15).contains(&name.len())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    if !name_ok {
+        return Err(format!(
+            "Invalid service name '{}': use 1-15 letters, digits or hyphens",
+            name
+        ));
+    }
+
+    if instance_name.trim().is_empty() {
+        return Err("Instance name must not be empty".into());
+    }
+    if instance_name.len() > MAX_INSTANCE_NAME_LEN {
+        return Err(format!(
+            "Instance name must be at most {} bytes",
+            MAX_INSTANCE_NAME_LEN
+        ));
+    }
+    Ok(())
+}
+
+// Phones forget a service once its records' TTL runs out without a fresh announcement,
+// which on lossy Wi-Fi shows up as the desktop vanishing from the list. A shorter TTL
+// and announce interval make a missed packet recover sooner at the cost of more
+// multicast traffic; the interval should stay well below the TTL.
+const MIN_SERVICE_TTL_SECS: u32 = 10;
+const MAX_SERVICE_TTL_SECS: u32 = 4_500;
+const MIN_ANNOUNCE_INTERVAL_SECS: u64 = 1;
+const MAX_ANNOUNCE_INTERVAL_SECS: u64 = 3_600;
+
+fn validate_announce_timing(
+    ttl_secs: Option<u32>,
+    announce_interval_secs: Option<u64>,
+) -> Result<(), String> {
+    if let Some(ttl) = ttl_secs {
+        if !(MIN_SERVICE_TTL_SECS..=MAX_SERVICE_TTL_SECS).contains(&ttl) {
+            return Err(format!(
+                "TTL must be between {} and {} seconds",
+                MIN_SERVICE_TTL_SECS, MAX_SERVICE_TTL_SECS
+            ));
+        }
+    }
+    if let Some(interval) = announce_interval_secs {
+        if !(MIN_ANNOUNCE_INTERVAL_SECS..=MAX_ANNOUNCE_INTERVAL_SECS).contains(&interval) {
+            return Err(format!(
+                "Announce interval must be between {} and {} seconds",
+                MIN_ANNOUNCE_INTERVAL_SECS, MAX_ANNOUNCE_INTERVAL_SECS
+            ));
+        }
+    }
+    if let (Some(ttl), Some(interval)) = (ttl_secs, announce_interval_secs) {
+        if interval >= u64::from(ttl) {
+            return Err(format!(
+                "Announce interval ({}s) must be shorter than the TTL ({}s)",
+                interval, ttl
+            ));
+        }
+    }
+    Ok(())
+}
+
+// A broadcaster builder using the service's announce interval, if it has one
+fn broadcaster_for(info: &ServiceInfo) -> BroadcasterBuilder {
+    let builder = BroadcasterBuilder::new();
+    match info.announce_interval_secs {
+        Some(secs) => builder.interval(std::time::Duration::from_secs(secs)),
+        None => builder,
+    }
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn register_service(
+    app: tauri::AppHandle,
+    state: State<MdnsState>,
+    service_type: String,                // e.g. "_bruteconnect._tcp.local."
+    instance_name: String,               // e.g. "BruteConnect-1234"
+    port: u16,                           // e.g. 9000
+    txt: Vec<String>,                    // e.g. ["role=desktop"]
+    ip_version: Option<String>,          // "v4", "v6" or "both" (default)
+    include_virtual: Option<bool>,       // advertise VPN/container addresses too (default false)
+    ttl_secs: Option<u32>,               // record TTL (default: searchlight's)
+    announce_interval_secs: Option<u64>, // re-announce period (default: searchlight's)
+) -> Result<String, String> {
+    // A second call while one is still building its broadcaster would race it to
+    // install the handle and the saved service info
+    let Some(_registering) = InFlight::claim(&state.registering) else {
+        return Err("A service registration is already in progress".into());
+    };
+    let result = advertise_service(
+        &app,
+        &state,
+        service_type,
+        instance_name,
+        port,
+        txt,
+        ip_version,
+        include_virtual.unwrap_or(false),
+        ttl_secs,
+        announce_interval_secs,
+    );
+    record_subsystem_result(&state.broadcaster_error, &result);
+    result
+}
+
+// Claims a "start in progress" flag for as long as it lives, so concurrent starts of the
+// same subsystem can't both get past their checks and build a second handle
+pub(crate) struct InFlight<'a>(&'a AtomicBool);
+
+impl<'a> InFlight<'a> {
+    pub(crate) fn claim(flag: &'a AtomicBool) -> Option<Self> {
+        flag.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .ok()
+            .map(|_| InFlight(flag))
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+// Runs `start` and stores its handle unless `slot` already holds one; returns whether
+// this call started it. The lock is held from the check until the handle is stored, so
+// concurrent starts can't both build a handle and leak one of them.
+pub(crate) fn start_once<T>(
+    slot: &Mutex<Option<T>>,
+    start: impl FnOnce() -> Result<T, String>,
+) -> Result<bool, String> {
+    let mut slot = slot.lock_or_recover();
+    if slot.is_some() {
+        return Ok(false);
+    }
+    *slot = Some(start()?);
+    Ok(true)
+}
+
+// Remember the outcome of a register/discovery start for `get_service_status`
+fn record_subsystem_result<T>(last_error: &Mutex<Option<String>>, result: &Result<T, String>) {
+    *last_error.lock_or_recover() = result.as_ref().err().cloned();
+}
+
+#[allow(clippy::too_many_arguments)]
+fn advertise_service(
+    app: &tauri::AppHandle,
+    state: &MdnsState,
+    service_type: String,
+    instance_name: String,
+    port: u16,
+    txt: Vec<String>,
+    ip_version: Option<String>,
+    include_virtual: bool,
+    ttl_secs: Option<u32>,
+    announce_interval_secs: Option<u64>,
+) -> Result<String, String> {
+    // Check if socket server is running
+    let Some(socket_port) = *state.socket_server_port.lock_or_recover() else {
+        return Err("Socket server must be started before registering mDNS service. Please start the socket server first.".into());
+    };
+    validate_service_params(&service_type, &instance_name, port)?;
+    validate_announce_timing(ttl_secs, announce_interval_secs)?;
+    let ip_version = parse_ip_version(ip_version.as_deref())?;
+    let requested_name = instance_name;
+    let instance_name = unique_instance_name(state, &service_type, &requested_name);
+    if instance_name != requested_name {
+        info!(
+            "Instance name '{}' is taken on the network, using '{}'",
+            requested_name, instance_name
+        );
+        validate_service_params(&service_type, &instance_name, port)?;
+    }
+    info!(
+        "Registering service: {} as {} on port {}",
+        service_type, instance_name, port
+    );
+
+    let (ips, skipped) = select_local_ips(include_virtual);
+    if ips.is_empty() {
+        return Err("No non-loopback IPs found for advertisement".into());
+    }
+    for (name, ip) in &skipped {
+        info!("Skipping {} on virtual interface {}", ip, name);
+    }
+    if !include_virtual
+        && skipped.is_empty()
+        && ips.iter().any(|(n, ip)| is_virtual_interface(n, *ip))
+    {
+        warn!("No physical network interface found - advertising virtual interfaces");
+    }
+
+    // Build the service to broadcast
+    let mut svc = ServiceBuilder::new(&service_type, &instance_name, port)
+        .map_err(|e| format!("invalid service params: {e}"))?;
+
+    for (name, ip) in ips {
+        svc = svc.add_ip_address(ip);
+        info!("Added IP address: {} ({})", ip, name);
+    }
+    // Add socket port to TXT records
+    let mut enhanced_txt = txt.clone();
+    enhanced_txt.push(format!("socketPort={}", socket_port));
+    // Records describing this build and server replace any the caller passed
+    enhanced_txt.retain(|rec| {
+        !["tls=", "version=", "proto=", "msgpack="]
+            .iter()
+            .any(|key| rec.starts_with(key))
+    });
+    enhanced_txt.push(format!("version={}", env!("CARGO_PKG_VERSION")));
+    enhanced_txt.push(format!("proto={}", PROTOCOL_VERSION));
+    enhanced_txt.push("msgpack=1".into());
+    if state.tls_fingerprint.lock_or_recover().is_some() {
+        enhanced_txt.push("tls=1".into());
+    }
+    // The configured room replaces any room the caller passed
+    if let Some(room) = state.settings.lock_or_recover().room.clone() {
+        enhanced_txt.retain(|rec| !rec.starts_with("room="));
+        enhanced_txt.push(format!("room={}", room));
+    }
+
+    // Store service info for potential goodbye messages before consuming txt
+    let service_info = ServiceInfo {
+        service_type: service_type.clone(),
+        instance_name: instance_name.clone(),
+        port,
+        txt: enhanced_txt.clone(),
+        ip_version,
+        include_virtual,
+        ttl_secs,
+        announce_interval_secs,
+    };
+
+    for rec in enhanced_txt {
+        svc = svc.add_txt_truncated(rec);
+    }
+    if let Some(ttl) = ttl_secs {
+        svc = svc.ttl(ttl);
+    }
+
+    let svc = svc
+        .build()
+        .map_err(|e| format!("service build failed: {e}"))?;
+
+    // Start broadcasting in the background and keep its handle
+    let broadcaster = broadcaster_for(&service_info)
+        .add_service(svc)
+        .build(ip_version)
+        .map_err(|e| format!("broadcaster build failed: {e}"))?
+        .run_in_background();
+
+    let mut guard = state.broadcaster.lock_or_recover();
+    if let Some(prev) = guard.take() {
+        info!("Shutting down previous broadcaster...");
+        let _ = prev.shutdown();
+    }
+    *guard = Some(broadcaster);
+
+    // Store the service info
+    *state.last_service_info.lock_or_recover() = Some(service_info);
+
+    {
+        let mut settings = state.settings.lock_or_recover();
+        // The requested name, so a restore dedupes against the network as it is then
+        settings.last_session.service = Some(SavedService {
+            service_type,
+            instance_name: requested_name,
+            port: (port != socket_port).then_some(port),
+            txt,
+            ip_version: Some(ip_version_name(ip_version).to_string()),
+            include_virtual: Some(include_virtual),
+            ttl_secs,
+            announce_interval_secs,
+        });
+        if let Err(e) = save_settings(&settings) {
+            error!("Failed to remember registered service: {}", e);
+        }
+    }
+
+    info!("Service registration completed successfully");
+    start_interface_watcher(app, state);
+    check_socket_port_consistency(app, state);
+    Ok(instance_name)
+}
+
+// Like Bonjour, dodge a name another host already uses by appending " (2)", " (3)", ...
+// Only a running discovery knows the names on the network; without one the name is
+// used as-is. Our own advertisement shows up in discovery too, so re-registering the
+// current name keeps it.
+fn unique_instance_name(state: &MdnsState, service_type: &str, instance_name: &str) -> String {
+    if state.discovery.lock_or_recover().is_none() {
+        return instance_name.to_string();
+    }
+    let own = state
+        .last_service_info
+        .lock_or_recover()
+        .as_ref()
+        .filter(|info| info.service_type.eq_ignore_ascii_case(service_type))
+        .map(|info| info.instance_name.clone());
+
+    // Discovered names are "<instance>.<service type>" without the trailing dot
+    let suffix = format!(".{}", service_type.trim_end_matches('.')).to_lowercase();
+    let taken: Vec<String> = state
+        .devices
+        .lock_or_recover()
+        .values()
+        .filter_map(|cached| {
+            let name = cached.device.name.trim_end_matches('.').to_lowercase();
+            name.strip_suffix(&suffix).map(str::to_string)
+        })
+        .filter(|instance| {
+            own.as_ref()
+                .is_none_or(|own| !own.eq_ignore_ascii_case(instance))
+        })
+        .collect();
+
+    let is_taken = |name: &str| taken.contains(&name.to_lowercase());
+    if !is_taken(instance_name) {
+        return instance_name.to_string();
+    }
+    (2..)
+        .map(|n| format!("{} ({})", instance_name, n))
+        .find(|candidate| !is_taken(candidate))
+        .expect("unbounded suffix search always finds a free name")
+}
+
+// ---- Interface watcher ----
+// The advertised addresses are a snapshot of `local_ips()`, so switching from Ethernet
+// to Wi-Fi would leave phones with addresses that no longer work. While a service is
+// registered, a background task re-reads the interfaces every INTERFACE_POLL_INTERVAL
+// and, when the non-loopback set changed, restarts the broadcaster from
+// `last_service_info` and emits "mdns:readvertised".
+const INTERFACE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+fn sorted_local_ips(include_virtual: bool) -> Vec<IpAddr> {
+    let mut ips = local_ips(include_virtual);
+    ips.sort();
+    ips.dedup();
+    ips
+}
+
+fn start_interface_watcher(app: &tauri::AppHandle, state: &MdnsState) {
+    let include_virtual = state
+        .last_service_info
+        .lock_or_recover()
+        .as_ref()
+        .is_some_and(|info| info.include_virtual);
+    *state.advertised_ips.lock_or_recover() = sorted_local_ips(include_virtual);
+
+    let mut watcher = state.interface_watcher.lock_or_recover();
+    if watcher.is_some() {
+        return;
+    }
+    let app = app.clone();
+    *watcher = Some(tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(INTERFACE_POLL_INTERVAL).await;
+            readvertise_if_changed(&app);
+        }
+    }));
+}
+
+pub(crate) fn stop_interface_watcher(state: &MdnsState) {
+    if let Some(task) = state.interface_watcher.lock_or_recover().take() {
+        task.abort();
+    }
+    state.advertised_ips.lock_or_recover().clear();
+}
+
+fn readvertise_if_changed(app: &tauri::AppHandle) {
+    let state: State<MdnsState> = app.state();
+    let Some(info) = state.last_service_info.lock_or_recover().clone() else {
+        return;
+    };
+    let current = sorted_local_ips(info.include_virtual);
+    let previous = state.advertised_ips.lock_or_recover().clone();
+    // With no usable interface at all, keep the old records until one comes back
+    if current == previous || current.is_empty() {
+        return;
+    }
+
+    info!(
+        "Network interfaces changed ({:?} -> {:?}), re-advertising {}",
+        previous, current, info.instance_name
+    );
+    let broadcaster = build_service(&info).and_then(|svc| {
+        broadcaster_for(&info)
+            .add_service(svc)
+            .build(info.ip_version)
+            .map_err(|e| format!("broadcaster build failed: {e}"))
+    });
+    let broadcaster = match broadcaster {
+        Ok(broadcaster) => broadcaster.run_in_background(),
+        Err(e) => {
+            error!("Failed to re-advertise after an interface change: {}", e);
+            *state.broadcaster_error.lock_or_recover() = Some(e);
+            return;
+        }
+    };
+    {
+        let mut guard = state.broadcaster.lock_or_recover();
+        if let Some(prev) = guard.replace(broadcaster) {
+            let _ = prev.shutdown();
+        }
+    }
+    *state.advertised_ips.lock_or_recover() = current.clone();
+
+    let added: Vec<String> = current
+        .iter()
+        .filter(|ip| !previous.contains(ip))
+        .map(|ip| ip.to_string())
+        .collect();
+    let removed: Vec<String> = previous
+        .iter()
+        .filter(|ip| !current.contains(ip))
+        .map(|ip| ip.to_string())
+        .collect();
+    let _ = app.emit(
+        "mdns:readvertised",
+        serde_json::json!({
+            "instance_name": info.instance_name,
+            "addresses": current.iter().map(|ip| ip.to_string()).collect::<Vec<_>>(),
+            "added": added,
+            "removed": removed,
+            "timestamp_ms": unix_millis()
+        }),
+    );
+}
+
+fn advertised_socket_port(info: &ServiceInfo) -> Option<u16> {
+    info.txt
+        .iter()
+        .find_map(|rec| rec.strip_prefix("socketPort="))
+        .and_then(|port| port.parse().ok())
+}
+
+// The advertised "socketPort=" TXT record must match the live listener, or clients
+// discover the desktop but can't connect. Returns (advertised, actual) on a mismatch.
+fn check_socket_port_consistency(
+    app: &tauri::AppHandle,
+    state: &MdnsState,
+) -> Option<(Option<u16>, Option<u16>)> {
+    let advertised = match state.last_service_info.lock_or_recover().as_ref() {
+        Some(info) => advertised_socket_port(info),
+        None => return None, // nothing advertised, nothing to compare
+    };
+    let actual = *state.socket_server_port.lock_or_recover();
+    if advertised.is_some() && advertised == actual {
+        return None;
+    }
+
+    warn!(
+        "Advertised socket port {:?} does not match the listener ({:?})",
+        advertised, actual
+    );
+    let _ = app.emit(
+        "mdns:port-mismatch",
+        serde_json::json!({ "advertised": advertised, "actual": actual }),
+    );
+    Some((advertised, actual))
+}
+
+// On-demand check; with `fix` set, a mismatched service is re-registered so its TXT
+// record carries the current port.
+#[tauri::command]
+pub(crate) fn check_socket_port(
+    app: tauri::AppHandle,
+    state: State<MdnsState>,
+    fix: Option<bool>,
+) -> Result<serde_json::Value, String> {
+    let Some((advertised, actual)) = check_socket_port_consistency(&app, &state) else {
+        return Ok(serde_json::json!({ "consistent": true, "reregistered": false }));
+    };
+
+    let mut reregistered = false;
+    if fix.unwrap_or(false) {
+        let actual =
+            actual.ok_or("Socket server is not running - start it before re-registering")?;
+        let info = state
+            .last_service_info
+            .lock_or_recover()
+            .clone()
+            .ok_or("No registered service to fix")?;
+        // register_service appends the current socketPort itself
+        let txt = info
+            .txt
+            .into_iter()
+            .filter(|rec| !rec.starts_with("socketPort="))
+            .collect();
+        // A service advertising the socket port as its own port follows the listener
+        let port = if Some(info.port) == advertised {
+            actual
+        } else {
+            info.port
+        };
+        info!(
+            "Re-registering {} to fix the advertised socket port",
+            info.instance_name
+        );
+        register_service(
+            app.clone(),
+            state.clone(),
+            info.service_type,
+            info.instance_name,
+            port,
+            txt,
+            Some(ip_version_name(info.ip_version).into()),
+            Some(info.include_virtual),
+            info.ttl_secs,
+            info.announce_interval_secs,
+        )?;
+        reregistered = true;
+    }
+
+    Ok(serde_json::json!({
+        "consistent": false,
+        "advertised": advertised,
+        "actual": actual,
+        "reregistered": reregistered
+    }))
+}
+
+// A TXT character-string holds at most 255 bytes, "room=" included
+const MAX_ROOM_LEN: usize = 255 - "room=".len();
+
+#[tauri::command]
+pub(crate) fn set_room(
+    app: tauri::AppHandle,
+    state: State<MdnsState>,
+    name: Option<String>,
+) -> Result<(), String> {
+    let room = name
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty());
+    if let Some(room) = &room {
+        if room.len() > MAX_ROOM_LEN {
+            return Err(format!("Room name must be at most {} bytes", MAX_ROOM_LEN));
+        }
+    }
+
+    {
+        let mut settings = state.settings.lock_or_recover();
+        settings.room = room.clone();
+        save_settings(&settings)?;
+    }
+    match &room {
+        Some(room) => info!("Room set to {}", room),
+        None => info!("Room cleared"),
+    }
+
+    // Re-announce so browsers see the new room right away
+    let Some(info) = state.last_service_info.lock_or_recover().clone() else {
+        return Ok(());
+    };
+    // register_service appends socketPort and the room itself
+    let txt = info
+        .txt
+        .into_iter()
+        .filter(|rec| !rec.starts_with("socketPort=") && !rec.starts_with("room="))
+        .collect();
+    register_service(
+        app.clone(),
+        state.clone(),
+        info.service_type,
+        info.instance_name,
+        info.port,
+        txt,
+        Some(ip_version_name(info.ip_version).into()),
+        Some(info.include_virtual),
+        info.ttl_secs,
+        info.announce_interval_secs,
+    )
+    .map(|_| ())
+}
+
+#[tauri::command]
+pub(crate) fn unregister_service(
+    app: tauri::AppHandle,
+    state: State<MdnsState>,
+) -> Result<(), String> {
+    info!("Unregistering service...");
+    stop_interface_watcher(&state);
+
+    // Taken out first so the lock isn't held while the goodbyes are queued
+    let handle = state.broadcaster.lock_or_recover().take();
+    if let Some(handle) = handle {
+        info!("Shutting down broadcaster service...");
+
+        // Shutdown the broadcaster - this should send goodbye messages
+        handle
+            .shutdown()
+            .map_err(|e| format!("broadcast shutdown failed: {e}"))?;
+
+        info!("Service unregistered successfully");
+
+        // Send explicit goodbye message to ensure immediate cache invalidation.
+        // The goodbyes go out in the background, so this returns right away.
+        if let Some(info) = state.last_service_info.lock_or_recover().take() {
+            spawn_goodbye(
+                &app,
+                info,
+                DEFAULT_GOODBYE_COUNT,
+                DEFAULT_GOODBYE_INTERVAL_MS,
+            );
+        }
+    } else {
+        info!("No service was registered");
+    }
+
+    Ok(())
+}
+
+// A service matches when its type equals `service_type` (if given) and it carries
+// the TXT key `txt_key` (if given), optionally with exactly `txt_value`.
+fn service_matches(
+    info: &ServiceInfo,
+    service_type: Option<&str>,
+    txt_key: Option<&str>,
+    txt_value: Option<&str>,
+) -> bool {
+    if let Some(wanted_type) = service_type {
+        if info.service_type != wanted_type {
+            return false;
+        }
+    }
+
+    if let Some(key) = txt_key {
+        let has_record = info.txt.iter().any(|rec| {
+            let (k, v) = rec.split_once('=').unwrap_or((rec.as_str(), ""));
+            k == key && txt_value.is_none_or(|wanted| v == wanted)
+        });
+        if !has_record {
+            return false;
+        }
+    }
+
+    true
+}
+
+// Unregisters the advertised service if it matches; returns how many were removed
+#[tauri::command]
+pub(crate) fn unregister_service_matching(
+    app: tauri::AppHandle,
+    state: State<MdnsState>,
+    service_type: Option<String>, // e.g. "_bruteconnect._tcp.local."
+    txt_key: Option<String>,      // e.g. "role"
+    txt_value: Option<String>,    // e.g. "media"
+) -> Result<usize, String> {
+    if service_type.is_none() && txt_key.is_none() {
+        return Err("Provide a service type and/or a TXT key to match against".into());
+    }
+    if txt_value.is_some() && txt_key.is_none() {
+        return Err("A TXT value can only be matched together with a TXT key".into());
+    }
+
+    // Only one service is advertised at a time (registering replaces it), so this is
+    // the full unregister, done only when that service matches
+    let matches = state
+        .last_service_info
+        .lock_or_recover()
+        .as_ref()
+        .is_some_and(|info| {
+            service_matches(
+                info,
+                service_type.as_deref(),
+                txt_key.as_deref(),
+                txt_value.as_deref(),
+            )
+        });
+    if !matches {
+        info!("The registered service doesn't match the unregister criteria");
+        return Ok(0);
+    }
+
+    unregister_service(app, state)?;
+    Ok(1)
+}
+
+#[tauri::command]
+pub(crate) fn start_discovery(
+    app: tauri::AppHandle,
+    state: State<MdnsState>,
+    service_type: String,        // e.g. "_bruteconnect._tcp.local."
+    timeout_secs: Option<u64>,   // stop automatically after this long (None = never)
+    filter: Option<Vec<String>>, // required TXT records, e.g. ["role=desktop"]
+    ip_version: Option<String>,  // "v4", "v6" or "both" (default)
+) -> Result<(), String> {
+    let result = begin_discovery(&app, &state, service_type, timeout_secs, filter, ip_version);
+    record_subsystem_result(&state.discovery_error, &result);
+    result
+}
+
+fn begin_discovery(
+    app: &tauri::AppHandle,
+    state: &MdnsState,
+    service_type: String,
+    timeout_secs: Option<u64>,
+    filter: Option<Vec<String>>,
+    ip_version: Option<String>,
+) -> Result<(), String> {
+    if let Some(secs) = timeout_secs {
+        if secs == 0 || secs > MAX_DISCOVERY_TIMEOUT_SECS {
+            return Err(format!(
+                "Discovery timeout must be between 1 and {} seconds",
+                MAX_DISCOVERY_TIMEOUT_SECS
+            ));
+        }
+    }
+
+    let filter = parse_txt_filter(filter.unwrap_or_default())?;
+    let ip_version = parse_ip_version(ip_version.as_deref())?;
+
+    let mut generation = 0;
+    let started = start_once(&state.discovery, || {
+        state.discovery_stats.reset();
+        state.devices.lock_or_recover().clear();
+        state.responder_cache.lock_or_recover().clear();
+        *state.discovery_filter.lock_or_recover() = filter;
+
+        let handle = build_discovery(app, &service_type, ip_version)?;
+        // Bumped before the slot is unlocked, so a stop can't slip in between and
+        // leave the supervisor below watching a session that's already gone
+        generation = state.discovery_generation.fetch_add(1, Ordering::SeqCst) + 1;
+        Ok(handle)
+    })?;
+    if !started {
+        return Ok(()); // already running
+    }
+    tauri::async_runtime::spawn(supervise_discovery(
+        app.clone(),
+        service_type.clone(),
+        ip_version,
+        generation,
+    ));
+    if let Some(secs) = timeout_secs {
+        tauri::async_runtime::spawn(stop_discovery_after(app.clone(), secs, generation));
+    }
+
+    {
+        let mut settings = state.settings.lock_or_recover();
+        settings.last_session.discovery_type = Some(service_type);
+        settings.last_session.discovery_ip_version = Some(ip_version_name(ip_version).into());
+        if let Err(e) = save_settings(&settings) {
+            error!("Failed to remember discovery type: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+const MAX_DISCOVERY_TIMEOUT_SECS: u64 = 24 * 60 * 60;
+
+// "key=value" constraints a responder's TXT records must all satisfy to be reported
+fn parse_txt_filter(filter: Vec<String>) -> Result<Vec<(String, String)>, String> {
+    filter
+        .iter()
+        .map(|rule| match rule.split_once('=') {
+            Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+            _ => Err(format!(
+                "Invalid TXT filter '{}' - expected key=value",
+                rule
+            )),
+        })
+        .collect()
+}
+
+fn txt_filter_matches(
+    filter: &[(String, String)],
+    txt_map: &std::collections::BTreeMap<String, String>,
+) -> bool {
+    filter
+        .iter()
+        .all(|(key, value)| txt_map.get(key) == Some(value))
+}
+
+// Stops the discovery session `generation` once `secs` have passed. A stop or restart
+// in the meantime bumps the generation, which turns this into a no-op.
+async fn stop_discovery_after(app: tauri::AppHandle, secs: u64, generation: u64) {
+    tokio::time::sleep(std::time::Duration::from_secs(secs)).await;
+
+    let state: State<MdnsState> = app.state();
+    if !discovery_current(&state, generation) {
+        return;
+    }
+    warn!("Discovery timed out after {}s", secs);
+    if let Err(e) = stop_discovery(state) {
+        error!("Failed to stop discovery after timeout: {}", e);
+    }
+    let _ = app.emit(
+        "mdns:discovery-stopped",
+        serde_json::json!({ "reason": "timeout", "timeout_secs": secs }),
+    );
+}
+
+fn build_discovery(
+    app: &tauri::AppHandle,
+    service_type: &str,
+    ip_version: IpVersion,
+) -> Result<DiscoveryHandle, String> {
+    let app_for_cb = app.clone();
+    let discovery = DiscoveryBuilder::new()
+        .service(service_type)
+        .map_err(|e| format!("invalid service type: {e}"))?
+        .build(ip_version)
+        .map_err(|e| format!("discovery build failed: {e}"))?
+        .run_in_background(move |event| {
+            let state: State<MdnsState> = app_for_cb.state();
+            let stats = &state.discovery_stats;
+            match event {
+                DiscoveryEvent::ResponderFound(responder) => {
+                    stats.responses.fetch_add(1, Ordering::Relaxed);
+                    stats.responders_found.fetch_add(1, Ordering::Relaxed);
+                    let _ = emit_responder(&app_for_cb, "mdns:found", &responder);
+                }
+                DiscoveryEvent::ResponderLost(responder) => {
+                    stats.responders_lost.fetch_add(1, Ordering::Relaxed);
+                    let _ = emit_responder(&app_for_cb, "mdns:lost", &responder);
+                    state
+                        .responder_cache
+                        .lock_or_recover()
+                        .remove(&responder_cache_key(&responder));
+                }
+                DiscoveryEvent::ResponseUpdate { new, .. } => {
+                    stats.responses.fetch_add(1, Ordering::Relaxed);
+                    let _ = emit_responder(&app_for_cb, "mdns:update", &new);
+                } // Fixed: Remove unreachable pattern since all enum variants are covered above
+            }
+        });
+    Ok(discovery)
+}
+
+// ---- Discovery supervisor ----
+// searchlight runs discovery on its own thread, and a socket error ends that thread
+// without telling anyone. While discovery is meant to be running, the supervisor polls
+// the handle and rebuilds a dead discovery with exponential backoff, emitting
+// "mdns:discovery-recovered" once it is back or "mdns:discovery-failed" after the
+// retry policy is exhausted. start/stop_discovery bump the generation, which retires
+// the previous supervisor.
+const DISCOVERY_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+fn discovery_current(state: &MdnsState, generation: u64) -> bool {
+    state.discovery_generation.load(Ordering::SeqCst) == generation
+}
+
+async fn supervise_discovery(
+    app: tauri::AppHandle,
+    service_type: String,
+    ip_version: IpVersion,
+    generation: u64,
+) {
+    loop {
+        tokio::time::sleep(DISCOVERY_CHECK_INTERVAL).await;
+
+        let state: State<MdnsState> = app.state();
+        if !discovery_current(&state, generation) {
+            return;
+        }
+        let dead = {
+            let mut discovery = state.discovery.lock_or_recover();
+            match discovery.take_if(|handle| handle.is_finished()) {
+                Some(handle) => {
+                    // Joins the finished thread; its error is the only diagnostic we get
+                    if let Err(e) = handle.shutdown() {
+                        error!("Discovery for {} stopped: {}", service_type, e);
+                    }
+                    true
+                }
+                None => discovery.is_none(),
+            }
+        };
+        if !dead {
+            continue;
+        }
+
+        warn!("Discovery for {} died, restarting", service_type);
+        if !restart_discovery(&app, &service_type, ip_version, generation).await {
+            return;
+        }
+    }
+}
+
+// Returns false when the supervisor should stop: retries ran out or discovery was
+// stopped/restarted by the user meanwhile
+async fn restart_discovery(
+    app: &tauri::AppHandle,
+    service_type: &str,
+    ip_version: IpVersion,
+    generation: u64,
+) -> bool {
+    let state: State<MdnsState> = app.state();
+    let policy = state.settings.lock_or_recover().discovery_retry;
+
+    let mut last_error = String::from("retries disabled");
+    for attempt in 1..=policy.max_retries {
+        let delay = policy
+            .base_delay_ms
+            .saturating_mul(1 << (attempt - 1).min(16))
+            .min(MAX_DISCOVERY_RETRY_DELAY_MS);
+        tokio::time::sleep(std::time::Duration::from_millis(delay)).await;
+        if !discovery_current(&state, generation) {
+            return false;
+        }
+
+        match build_discovery(app, service_type, ip_version) {
+            Ok(handle) => {
+                // stop_discovery may have run while we were building
+                let mut discovery = state.discovery.lock_or_recover();
+                if !discovery_current(&state, generation) {
+                    drop(discovery);
+                    let _ = handle.shutdown();
+                    return false;
+                }
+                *discovery = Some(handle);
+                info!(
+                    "Discovery for {} recovered after {} attempt(s)",
+                    service_type, attempt
+                );
+                let _ = app.emit(
+                    "mdns:discovery-recovered",
+                    serde_json::json!({ "service_type": service_type, "attempts": attempt }),
+                );
+                return true;
+            }
+            Err(e) => {
+                error!(
+                    "Discovery restart attempt {}/{} failed: {}",
+                    attempt, policy.max_retries, e
+                );
+                last_error = e;
+            }
+        }
+    }
+
+    if discovery_current(&state, generation) {
+        error!(
+            "Giving up on discovery for {}: {}",
+            service_type, last_error
+        );
+        *state.discovery_error.lock_or_recover() = Some(last_error.clone());
+        let _ = app.emit(
+            "mdns:discovery-failed",
+            serde_json::json!({
+                "service_type": service_type,
+                "attempts": policy.max_retries,
+                "error": last_error
+            }),
+        );
+    }
+    false
+}
+
+#[tauri::command]
+pub(crate) fn set_discovery_retry(
+    state: State<MdnsState>,
+    max_retries: u32,
+    base_delay_ms: u64,
+) -> Result<(), String> {
+    if max_retries > MAX_DISCOVERY_RETRIES {
+        return Err(format!(
+            "Retries must be between 0 and {}",
+            MAX_DISCOVERY_RETRIES
+        ));
+    }
+    if base_delay_ms == 0 || base_delay_ms > MAX_DISCOVERY_RETRY_DELAY_MS {
+        return Err(format!(
+            "Retry delay must be between 1 and {}ms",
+            MAX_DISCOVERY_RETRY_DELAY_MS
+        ));
+    }
+
+    let mut settings = state.settings.lock_or_recover();
+    settings.discovery_retry = DiscoveryRetryPolicy {
+        max_retries,
+        base_delay_ms,
+    };
+    save_settings(&settings)?;
+
+    info!(
+        "Discovery retry policy set to {} retries, {}ms base delay",
+        max_retries, base_delay_ms
+    );
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn get_discovery_stats(state: State<MdnsState>) -> serde_json::Value {
+    state.discovery_stats.snapshot()
+}
+
+// Current contents of the device cache, for a UI that loads after discovery started and
+// missed the found events. Deduplicated by instance name and address: a device answering
+// from several addresses is listed once per address.
+#[tauri::command]
+pub(crate) fn get_discovered_devices(state: State<MdnsState>) -> Vec<FoundDevice> {
+    let mut devices: Vec<FoundDevice> = state
+        .devices
+        .lock_or_recover()
+        .values()
+        .flat_map(|cached| cached.by_addr.values().cloned())
+        .collect();
+    devices.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.addr.cmp(&b.addr)));
+    devices
+}
+
+// The discovery socket's receive buffer can't be tuned: searchlight 0.3 creates and owns
+// its multicast sockets without exposing them or a buffer option. README.md ("Discovery
+// on busy networks") lists the OS defaults and how to raise them.
+
+// Latency probe: advertise a uniquely named throwaway service and time how long a
+// separate discovery session takes to see it. Runs alongside (and independent of) any
+// discovery the user already started.
+const DEFAULT_LATENCY_TIMEOUT_MS: u64 = 10_000;
+const MAX_LATENCY_TIMEOUT_MS: u64 = 60_000;
+const LATENCY_QUERY_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
+fn responder_has_instance(r: &Responder, instance_name: &str) -> bool {
+    let prefix = format!("{}.", instance_name.to_lowercase());
+    r.last_response
+        .answers()
+        .iter()
+        .chain(r.last_response.additionals())
+        .any(|rec| rec.name().to_utf8().to_lowercase().starts_with(&prefix))
+}
+
+#[tauri::command]
+pub(crate) async fn measure_discovery_latency(
+    service_type: Option<String>, // defaults to "_bruteconnect._tcp.local."
+    timeout_ms: Option<u64>,
+) -> Result<serde_json::Value, String> {
+    let service_type = service_type.unwrap_or_else(|| "_bruteconnect._tcp.local.".into());
+    let timeout_ms = timeout_ms.unwrap_or(DEFAULT_LATENCY_TIMEOUT_MS);
+    if timeout_ms == 0 || timeout_ms > MAX_LATENCY_TIMEOUT_MS {
+        return Err(format!(
+            "Timeout must be between 1 and {}ms",
+            MAX_LATENCY_TIMEOUT_MS
+        ));
+    }
+
+    let nonce = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let instance_name = format!("bruteconnect-latency-{:x}", nonce);
+    let service = build_service(&ServiceInfo {
+        service_type: service_type.clone(),
+        instance_name: instance_name.clone(),
+        port: 9, // discard; nothing connects to the probe
+        txt: vec!["probe=latency".into()],
+        ip_version: IpVersion::Both,
+        include_virtual: false,
+        ttl_secs: None,
+        announce_interval_secs: None,
+    })?;
+
+    let (found_tx, mut found_rx) = tokio::sync::mpsc::unbounded_channel();
+    let wanted = instance_name.clone();
+    let discovery = DiscoveryBuilder::new()
+        .service(&service_type)
+        .map_err(|e| format!("invalid service type: {e}"))?
+        .interval(LATENCY_QUERY_INTERVAL)
+        .loopback()
+        .build(IpVersion::Both)
+        .map_err(|e| format!("discovery build failed: {e}"))?
+        .run_in_background(move |event| {
+            // Our own machine may already be a known responder (the app's real service),
+            // in which case the probe shows up as an update rather than a new responder
+            let responder = match event {
+                DiscoveryEvent::ResponderFound(r) => r,
+                DiscoveryEvent::ResponseUpdate { new, .. } => new,
+                DiscoveryEvent::ResponderLost(_) => return,
+            };
+            if responder_has_instance(&responder, &wanted) {
+                let _ = found_tx.send(());
+            }
+        });
+
+    info!("Measuring discovery latency with probe {}", instance_name);
+    let started = std::time::Instant::now();
+    let broadcaster = match BroadcasterBuilder::new()
+        .add_service(service)
+        .loopback()
+        .build(IpVersion::Both)
+    {
+        Ok(b) => b.run_in_background(),
+        Err(e) => {
+            let _ = discovery.shutdown();
+            return Err(format!("broadcaster build failed: {e}"));
+        }
+    };
+
+    let outcome = tokio::time::timeout(
+        std::time::Duration::from_millis(timeout_ms),
+        found_rx.recv(),
+    )
+    .await;
+    let latency = started.elapsed();
+
+    // Shutting down joins searchlight's threads, so keep it off the async workers
+    let _ = tauri::async_runtime::spawn_blocking(move || {
+        if let Err(e) = broadcaster.shutdown() {
+            error!("Error shutting down latency probe broadcaster: {}", e);
+        }
+        if let Err(e) = discovery.shutdown() {
+            error!("Error shutting down latency probe discovery: {}", e);
+        }
+    })
+    .await;
+
+    match outcome {
+        Ok(Some(())) => {
+            info!("Probe {} discovered after {:?}", instance_name, latency);
+            Ok(serde_json::json!({
+                "latency_ms": latency.as_secs_f64() * 1000.0,
+                "service_type": service_type,
+                "instance_name": instance_name
+            }))
+        }
+        _ => Err(format!(
+            "Probe service was not discovered within {}ms - multicast may be blocked on this network",
+            timeout_ms
+        )),
+    }
+}
+
+#[tauri::command]
+pub(crate) fn stop_discovery(state: State<MdnsState>) -> Result<(), String> {
+    info!("Stopping discovery...");
+    state.discovery_generation.fetch_add(1, Ordering::SeqCst);
+
+    if let Some(handle) = state.discovery.lock_or_recover().take() {
+        info!("Shutting down discovery service...");
+        handle
+            .shutdown()
+            .map_err(|e| format!("discovery shutdown failed: {e}"))?;
+        info!("Discovery stopped successfully");
+    } else {
+        info!("No discovery was running");
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn get_service_status(state: State<MdnsState>) -> Result<serde_json::Value, String> {
+    let broadcaster_active = state.broadcaster.lock_or_recover().is_some();
+
+    let discovery_active = state.discovery.lock_or_recover().is_some();
+
+    let service = state
+        .last_service_info
+        .lock_or_recover()
+        .as_ref()
+        .map(|info| {
+            serde_json::json!({
+                "service_type": info.service_type,
+                "instance_name": info.instance_name,
+                "port": info.port,
+                "txt": info.txt,
+                "ip_version": ip_version_name(info.ip_version)
+            })
+        });
+
+    Ok(serde_json::json!({
+        "broadcaster_active": broadcaster_active,
+        "discovery_active": discovery_active,
+        "broadcaster_error": *state.broadcaster_error.lock_or_recover(),
+        "discovery_error": *state.discovery_error.lock_or_recover(),
+        "service": service
+    }))
+}
+
+// ---- Goodbyes ----
+// searchlight 0.3 has no call for sending a goodbye (the service withdrawn with TTL 0)
+// on its own, so a goodbye is a teardown of a temporary broadcaster for the service:
+// it runs for `interval_ms` and is then shut down. A single teardown is the default;
+// `count` repeats it, `interval_ms` apart, for lossy networks. Goodbyes run on a
+// background thread because of the sleeps; "mdns:goodbye-complete" reports the outcome.
+const DEFAULT_GOODBYE_COUNT: u32 = 1;
+const MAX_GOODBYE_COUNT: u32 = 5;
+const DEFAULT_GOODBYE_INTERVAL_MS: u64 = 100;
+const MAX_GOODBYE_INTERVAL_MS: u64 = 2_000;
+
+#[tauri::command]
+pub(crate) fn send_goodbye_message(
+    app: tauri::AppHandle,
+    state: State<MdnsState>,
+    count: Option<u32>,       // goodbyes to send (default 1)
+    interval_ms: Option<u64>, // time between them (default 100)
+) -> Result<(), String> {
+    info!("Sending goodbye message...");
+
+    let count = count.unwrap_or(DEFAULT_GOODBYE_COUNT);
+    if !(1..=MAX_GOODBYE_COUNT).contains(&count) {
+        return Err(format!(
+            "Goodbye count must be between 1 and {}",
+            MAX_GOODBYE_COUNT
+        ));
+    }
+    let interval_ms = interval_ms.unwrap_or(DEFAULT_GOODBYE_INTERVAL_MS);
+    if interval_ms > MAX_GOODBYE_INTERVAL_MS {
+        return Err(format!(
+            "Goodbye interval must be at most {} ms",
+            MAX_GOODBYE_INTERVAL_MS
+        ));
+    }
+
+    // Get the last service info
+    let service_info = {
+        let guard = state.last_service_info.lock_or_recover();
+        guard.clone()
+    };
+
+    if let Some(info) = service_info {
+        spawn_goodbye(&app, info, count, interval_ms);
+    } else {
+        info!("No service info available for goodbye message");
+    }
+
+    Ok(())
+}
+
+fn spawn_goodbye(app: &tauri::AppHandle, info: ServiceInfo, count: u32, interval_ms: u64) {
+    let app = app.clone();
+    let spawned = std::thread::Builder::new()
+        .name("mdns-goodbye".into())
+        .spawn(move || {
+            let result = send_goodbye_for(&info, count, interval_ms);
+            if let Err(e) = &result {
+                warn!("Failed to send goodbye for {}: {}", info.instance_name, e);
+            }
+            let _ = app.emit(
+                "mdns:goodbye-complete",
+                serde_json::json!({
+                    "service_type": info.service_type,
+                    "instance_name": info.instance_name,
+                    "count": count,
+                    "interval_ms": interval_ms,
+                    "ok": result.is_ok(),
+                    "error": result.err()
+                }),
+            );
+        });
+    if let Err(e) = spawned {
+        error!("Failed to start goodbye thread: {}", e);
+    }
+}
+
+// Blocks for about `count * interval_ms`; only call it from `spawn_goodbye`
+fn send_goodbye_for(info: &ServiceInfo, count: u32, interval_ms: u64) -> Result<(), String> {
+    info!(
+        "Sending {} goodbye(s) for service: {} ({})",
+        count, info.instance_name, info.service_type
+    );
+
+    let interval = std::time::Duration::from_millis(interval_ms);
+    for i in 1..=count {
+        let broadcaster = BroadcasterBuilder::new()
+            .add_service(build_service(info)?)
+            .build(info.ip_version)
+            .map_err(|e| format!("goodbye broadcaster build failed: {e}"))?
+            .run_in_background();
+        std::thread::sleep(interval);
+        broadcaster
+            .shutdown()
+            .map_err(|e| format!("goodbye broadcast shutdown failed: {e}"))?;
+        debug!("Goodbye {}/{} sent", i, count);
+    }
+
+    info!("Goodbye for {} sent", info.instance_name);
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn set_max_cached_devices(
+    app: tauri::AppHandle,
+    state: State<MdnsState>,
+    max_devices: usize,
+) -> Result<(), String> {
+    if max_devices == 0 || max_devices > MAX_CACHED_DEVICES_LIMIT {
+        return Err(format!(
+            "Device cache size must be between 1 and {}",
+            MAX_CACHED_DEVICES_LIMIT
+        ));
+    }
+
+    {
+        let mut settings = state.settings.lock_or_recover();
+        settings.max_cached_devices = Some(max_devices);
+        save_settings(&settings)?;
+    }
+
+    info!("Device cache size set to {}", max_devices);
+    enforce_device_cap(&app);
+    Ok(())
+}
+
+// The selected device is the one the user is connected to or about to connect to;
+// it is exempt from cache eviction. Pass None to clear the selection.
+#[tauri::command]
+pub(crate) fn select_device(state: State<MdnsState>, key: Option<String>) -> Result<(), String> {
+    if let Some(key) = &key {
+        if !state.devices.lock_or_recover().contains_key(key) {
+            return Err(format!("No discovered device named {}", key));
+        }
+    }
+
+    info!("Selected device: {:?}", key);
+    *state.selected_device.lock_or_recover() = key;
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn set_restore_last_session(
+    state: State<MdnsState>,
+    enabled: bool,
+) -> Result<(), String> {
+    let mut settings = state.settings.lock_or_recover();
+    settings.restore_last_session = enabled;
+    save_settings(&settings)?;
+
+    info!(
+        "Restoring the last session {}",
+        if enabled { "enabled" } else { "disabled" }
+    );
+    Ok(())
+}
+
+// Re-register the last advertised service and restart discovery for the last browsed
+// type. Requires the socket server to be running, like `register_service`.
+#[tauri::command]
+pub(crate) fn restore_last_session(
+    app: tauri::AppHandle,
+    state: State<MdnsState>,
+) -> Result<serde_json::Value, String> {
+    let (enabled, session) = {
+        let settings = state.settings.lock_or_recover();
+        (settings.restore_last_session, settings.last_session.clone())
+    };
+    if !enabled {
+        return Err(
+            "Restoring the last session is disabled - enable it with set_restore_last_session"
+                .into(),
+        );
+    }
+    if session.service.is_none() && session.discovery_type.is_none() {
+        return Err("No previous session to restore".into());
+    }
+    // Registering with no usable interface would just advertise nothing
+    if local_ips(true).is_empty() {
+        return Err("No network connection - not restoring the last session".into());
+    }
+
+    let mut registered = false;
+    if let Some(service) = session.service {
+        let socket_port = (*state.socket_server_port.lock_or_recover())
+            .ok_or("Socket server must be running to restore the last session")?;
+        info!(
+            "Restoring service {} as {}",
+            service.service_type, service.instance_name
+        );
+        register_service(
+            app.clone(),
+            state.clone(),
+            service.service_type,
+            service.instance_name,
+            service.port.unwrap_or(socket_port),
+            service.txt,
+            service.ip_version,
+            service.include_virtual,
+            service.ttl_secs,
+            service.announce_interval_secs,
+        )?;
+        registered = true;
+    }
+
+    let mut discovery_started = false;
+    if let Some(service_type) = session.discovery_type {
+        info!("Restoring discovery for {}", service_type);
+        start_discovery(
+            app,
+            state,
+            service_type,
+            None,
+            None,
+            session.discovery_ip_version,
+        )?;
+        discovery_started = true;
+    }
+
+    Ok(serde_json::json!({
+        "registered": registered,
+        "discovery_started": discovery_started
+    }))
+}
+
+#[tauri::command]
+pub(crate) fn set_device_alias(
+    app: tauri::AppHandle,
+    state: State<MdnsState>,
+    key: String,   // TXT "id" value or instance name, e.g. "BruteConnect-1234"
+    alias: String, // e.g. "Conference Room TV"
+) -> Result<(), String> {
+    let key = key.trim().to_string();
+    let alias = alias.trim().to_string();
+    if key.is_empty() {
+        return Err("Device key must not be empty".into());
+    }
+    if alias.is_empty() {
+        return Err("Alias must not be empty - use clear_device_alias to remove it".into());
+    }
+    if alias.chars().count() > MAX_ALIAS_LEN {
+        return Err(format!(
+            "Alias must be at most {} characters",
+            MAX_ALIAS_LEN
+        ));
+    }
+
+    {
+        let mut settings = state.settings.lock_or_recover();
+        settings.device_aliases.insert(key.clone(), alias.clone());
+        save_settings(&settings)?;
+    }
+
+    info!("Alias for {} set to '{}'", key, alias);
+    let _ = app.emit(
+        "mdns:alias-changed",
+        serde_json::json!({ "key": key, "alias": alias }),
+    );
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn clear_device_alias(
+    app: tauri::AppHandle,
+    state: State<MdnsState>,
+    key: String,
+) -> Result<bool, String> {
+    let removed = {
+        let mut settings = state.settings.lock_or_recover();
+        let removed = settings.device_aliases.remove(key.trim()).is_some();
+        if removed {
+            save_settings(&settings)?;
+        }
+        removed
+    };
+
+    if removed {
+        info!("Alias for {} cleared", key.trim());
+        let _ = app.emit(
+            "mdns:alias-changed",
+            serde_json::json!({ "key": key.trim(), "alias": null }),
+        );
+    }
+    Ok(removed)
+}
+
+// ---- Responder parse cache ----
+// Responders re-announce on every query interval, usually with the exact same records,
+// so the SRV/TXT/A/AAAA walk is cached per service instance and only redone when the
+// record set changes. Entries are keyed by instance name rather than address because one
+// host can publish several instances. Instead of keeping a copy of the records, an entry
+// stores a hash of exactly the fields the walk reads, so checking for a hit is one pass
+// over the additionals with no allocation. Everything downstream (aliases, filters,
+// change detection) still runs per response since it depends on settings that can
+// change in between.
+pub(crate) struct ParsedResponder {
+    additionals_hash: u64,
+    name: String,
+    port: u16,
+    hostname: String,
+    pub(crate) txt: Vec<String>,
+    priority: Option<u16>,
+    weight: Option<u16>,
+    pub(crate) addresses: Vec<String>,
+}
+
+fn hash_additionals(records: &[searchlight::dns::rr::Record]) -> u64 {
+    use searchlight::dns::rr::{Name, RData};
+    use std::hash::{Hash, Hasher};
+
+    // Name's own Hash lowercases every label into a new allocation; the raw labels are
+    // cheaper, and exact, since the parse keeps names as they were sent
+    fn hash_name(name: &Name, hasher: &mut impl Hasher) {
+        for label in name.iter() {
+            label.hash(hasher);
+        }
+    }
+
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    for rec in records {
+        match rec.data() {
+            Some(RData::SRV(srv)) => {
+                hash_name(rec.name(), &mut hasher);
+                (srv.priority(), srv.weight(), srv.port()).hash(&mut hasher);
+                hash_name(srv.target(), &mut hasher);
+            }
+            Some(RData::TXT(t)) => t.txt_data().hash(&mut hasher),
+            Some(RData::A(ip)) => {
+                hash_name(rec.name(), &mut hasher);
+                ip.hash(&mut hasher);
+            }
+            Some(RData::AAAA(ip)) => {
+                hash_name(rec.name(), &mut hasher);
+                ip.hash(&mut hasher);
+            }
+            _ => continue,
+        }
+        rec.record_type().hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+// The SRV owner name ("Desk._bruteconnect._tcp.local"), falling back to the address for
+// a response without one
+fn responder_cache_key(r: &Responder) -> String {
+    use searchlight::dns::rr::RData;
+
+    r.last_response
+        .additionals()
+        .iter()
+        .find(|rec| matches!(rec.data(), Some(RData::SRV(_))))
+        .map(|rec| rec.name().to_utf8().trim_end_matches('.').to_string())
+        .unwrap_or_else(|| r.addr.to_string())
+}
+
+// `additionals_hash` is hash_additionals(records), which the caller already has
+pub(crate) fn parse_records(
+    records: &[searchlight::dns::rr::Record],
+    additionals_hash: u64,
+) -> ParsedResponder {
+    use searchlight::dns::rr::RData;
+
+    let mut name = String::new();
+    let mut port: u16 = 0;
+    let mut hostname = String::new();
+    let mut txt: Vec<String> = Vec::new();
+    let mut priority: Option<u16> = None;
+    let mut weight: Option<u16> = None;
+    // (owner name, address) from A/AAAA records, matched to the SRV target below
+    let mut host_ips: Vec<(String, IpAddr)> = Vec::new();
+
+    // Walk additionals to pull SRV/TXT/A/AAAA
+    for rec in records {
+        match rec.data() {
+            Some(RData::SRV(srv)) => {
+                hostname = srv.target().to_utf8().trim_end_matches('.').to_string();
+                port = srv.port();
+                priority = Some(srv.priority());
+                weight = Some(srv.weight());
+                name = rec.name().to_utf8().trim_end_matches('.').to_string();
+            }
+            Some(RData::TXT(t)) => {
+                for d in t.txt_data() {
+                    if let Ok(s) = std::str::from_utf8(d) {
+                        txt.push(s.to_string());
+                    }
+                }
+            }
+            Some(RData::A(ip)) => host_ips.push((rec.name().to_utf8(), IpAddr::V4(*ip))),
+            Some(RData::AAAA(ip)) => host_ips.push((rec.name().to_utf8(), IpAddr::V6(*ip))),
+            _ => {}
+        }
+    }
+
+    // Address records can describe other hosts in the same packet, so only keep the
+    // ones for the SRV target (all of them if there was no SRV record)
+    let mut addresses: Vec<String> = Vec::new();
+    for (owner, ip) in host_ips {
+        let owner = owner.trim_end_matches('.');
+        if (hostname.is_empty() || owner.eq_ignore_ascii_case(&hostname))
+            && !addresses.contains(&ip.to_string())
+        {
+            addresses.push(ip.to_string());
+        }
+    }
+    ParsedResponder {
+        additionals_hash,
+        name,
+        port,
+        hostname,
+        txt,
+        priority,
+        weight,
+        addresses,
+    }
+}
+
+pub(crate) fn cached_parse(
+    state: &MdnsState,
+    key: String,
+    records: &[searchlight::dns::rr::Record],
+) -> std::sync::Arc<ParsedResponder> {
+    let hash = hash_additionals(records);
+    let mut cache = state.responder_cache.lock_or_recover();
+    if let Some(cached) = cache.get(&key) {
+        if cached.additionals_hash == hash {
+            state
+                .discovery_stats
+                .parse_cache_hits
+                .fetch_add(1, Ordering::Relaxed);
+            return cached.clone();
+        }
+    }
+    let parsed = std::sync::Arc::new(parse_records(records, hash));
+    cache.insert(key, parsed.clone());
+    parsed
+}
+
+fn emit_responder(
+    app: &tauri::AppHandle,
+    topic: &str,
+    r: &std::sync::Arc<Responder>,
+) -> Result<(), tauri::Error> {
+    let state: State<MdnsState> = app.state();
+    let parsed = cached_parse(
+        &state,
+        responder_cache_key(r),
+        r.last_response.additionals(),
+    );
+    let mut addresses = parsed.addresses.clone();
+    let addr = r.addr.ip().to_string();
+    if !addresses.contains(&addr) {
+        addresses.push(addr.clone());
+    }
+
+    // A response without an SRV record can't be turned into a usable device
+    if parsed.priority.is_none() {
+        state
+            .discovery_stats
+            .parse_failures
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    let alias = {
+        let settings = state.settings.lock_or_recover();
+        lookup_alias(&settings.device_aliases, &parsed.name, &parsed.txt)
+    };
+    let txt_map: std::collections::BTreeMap<String, String> = parsed
+        .txt
+        .iter()
+        .map(|rec| match rec.split_once('=') {
+            Some((key, value)) => (key.to_string(), value.to_string()),
+            None => (rec.clone(), String::new()),
+        })
+        .collect();
+    let socket_port = txt_map.get("socketPort").and_then(|port| port.parse().ok());
+    let room = txt_map.get("room").filter(|room| !room.is_empty()).cloned();
+    let version = txt_map
+        .get("version")
+        .filter(|version| !version.is_empty())
+        .cloned();
+    let proto = txt_map.get("proto").and_then(|proto| proto.parse().ok());
+
+    let payload = FoundDevice {
+        name: parsed.name.clone(),
+        hostname: parsed.hostname.clone(),
+        addr,
+        addresses,
+        port: parsed.port,
+        txt: parsed.txt.clone(),
+        txt_map,
+        socket_port,
+        priority: parsed.priority,
+        weight: parsed.weight,
+        alias,
+        room,
+        version,
+        proto,
+    };
+
+    let matches = txt_filter_matches(&state.discovery_filter.lock_or_recover(), &payload.txt_map);
+    let topic = if matches {
+        topic
+    } else {
+        state
+            .discovery_stats
+            .filtered_out
+            .fetch_add(1, Ordering::Relaxed);
+        // Ignored, unless it's a listed device whose TXT records stopped matching:
+        // as far as the UI is concerned that device is gone
+        if !state
+            .devices
+            .lock_or_recover()
+            .contains_key(&device_key(&payload))
+        {
+            return Ok(());
+        }
+        "mdns:lost"
+    };
+
+    if topic == "mdns:lost" {
+        forget_device(app, &payload);
+    } else {
+        let changed = cache_device(app, &payload);
+        // searchlight reports every re-announcement as an update; only forward real
+        // changes. Found/lost always go out immediately.
+        if topic == "mdns:update" && !changed {
+            state
+                .discovery_stats
+                .suppressed_updates
+                .fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }
+        if topic == "mdns:update" && !debounce_update(app, &payload) {
+            return Ok(());
+        }
+    }
+
+    publish_device_event(&state, topic, &payload);
+    app.emit(topic, payload)
+}
@@ -0,0 +1,200 @@
+//! The optional HTTP endpoint that serves the device cache to other local apps.
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use log::{error, info};
+use tauri::{Manager, State};
+
+use crate::discovery::FoundDevice;
+use crate::{LockExt, MdnsState};
+
+// ---- Discovery HTTP endpoint ----
+// Optional loopback-only HTTP server so scripts and dashboards outside the UI can read
+// the device cache. Off by default; started with `start_discovery_http`.
+//   GET /devices  JSON snapshot of the cache
+//   GET /events   server-sent events: a "snapshot" event, then found/update/lost
+pub(crate) struct DiscoveryHttp {
+    port: u16,
+    pub(crate) task: tokio::task::JoinHandle<()>,
+    events: tokio::sync::broadcast::Sender<String>,
+}
+
+const DISCOVERY_HTTP_MAX_REQUEST: usize = 8 * 1024;
+const SSE_KEEPALIVE: std::time::Duration = std::time::Duration::from_secs(15);
+
+fn device_snapshot(state: &MdnsState) -> Vec<serde_json::Value> {
+    let devices = state.devices.lock_or_recover();
+    let mut snapshot: Vec<_> = devices
+        .iter()
+        .map(|(key, cached)| {
+            serde_json::json!({
+                "key": key,
+                "device": cached.device,
+                "last_seen_secs": cached.last_seen.elapsed().as_secs()
+            })
+        })
+        .collect();
+    snapshot.sort_by(|a, b| a["key"].as_str().cmp(&b["key"].as_str()));
+    snapshot
+}
+
+// Forward a discovery event to connected SSE clients, if the endpoint is running
+pub(crate) fn publish_device_event(state: &MdnsState, topic: &str, device: &FoundDevice) {
+    if let Some(http) = state.discovery_http.lock_or_recover().as_ref() {
+        let event = topic.trim_start_matches("mdns:");
+        let data = serde_json::to_string(device).unwrap_or_default();
+        // Err only means nobody is subscribed right now
+        let _ = http
+            .events
+            .send(format!("event: {}\ndata: {}\n\n", event, data));
+    }
+}
+
+async fn serve_discovery_http(app: tauri::AppHandle, listener: TcpListener) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(handle_discovery_http(app.clone(), stream));
+            }
+            Err(e) => error!("Discovery HTTP accept failed: {}", e),
+        }
+    }
+}
+
+async fn handle_discovery_http(app: tauri::AppHandle, mut stream: TcpStream) {
+    let mut request = Vec::new();
+    let mut buffer = [0; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        match stream.read(&mut buffer).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => request.extend_from_slice(&buffer[..n]),
+        }
+        if request.len() > DISCOVERY_HTTP_MAX_REQUEST {
+            let _ = stream
+                .write_all(b"HTTP/1.1 431 Request Header Fields Too Large\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                .await;
+            return;
+        }
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let mut request_line = request
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .split_whitespace();
+    let (method, path) = (request_line.next(), request_line.next());
+
+    let state: State<MdnsState> = app.state();
+    let response = match (method, path) {
+        (Some("GET"), Some("/devices")) => {
+            let body = serde_json::Value::Array(device_snapshot(&state)).to_string();
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+        }
+        (Some("GET"), Some("/events")) => {
+            // Subscribe before taking the snapshot so no event falls in between
+            let events = match state.discovery_http.lock_or_recover().as_ref() {
+                Some(http) => http.events.subscribe(),
+                None => return,
+            };
+            let snapshot = serde_json::Value::Array(device_snapshot(&state));
+            stream_discovery_events(stream, events, snapshot).await;
+            return;
+        }
+        (Some("GET"), _) => {
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+        }
+        _ => "HTTP/1.1 405 Method Not Allowed\r\nAllow: GET\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+    };
+
+    let _ = stream.write_all(response.as_bytes()).await;
+}
+
+async fn stream_discovery_events(
+    mut stream: TcpStream,
+    mut events: tokio::sync::broadcast::Receiver<String>,
+    snapshot: serde_json::Value,
+) {
+    let header = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n\r\nevent: snapshot\ndata: {}\n\n",
+        snapshot
+    );
+    if stream.write_all(header.as_bytes()).await.is_err() {
+        return;
+    }
+
+    let mut keepalive = tokio::time::interval(SSE_KEEPALIVE);
+    keepalive.tick().await;
+    loop {
+        let chunk = tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) => event,
+                // A slow reader missed events; tell it to refetch /devices
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(missed)) => {
+                    format!("event: lagged\ndata: {}\n\n", missed)
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => return,
+            },
+            _ = keepalive.tick() => ": keepalive\n\n".to_string(),
+        };
+        if stream.write_all(chunk.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
+
+#[tauri::command]
+pub(crate) async fn start_discovery_http(
+    app: tauri::AppHandle,
+    state: State<'_, MdnsState>,
+    port: Option<u16>, // None picks a free port
+) -> Result<u16, String> {
+    if let Some(http) = state.discovery_http.lock_or_recover().as_ref() {
+        info!(
+            "Discovery HTTP endpoint already running on port {}",
+            http.port
+        );
+        return Ok(http.port);
+    }
+
+    let listener = TcpListener::bind(("127.0.0.1", port.unwrap_or(0)))
+        .await
+        .map_err(|e| format!("failed to bind discovery HTTP endpoint: {e}"))?;
+    let port = listener
+        .local_addr()
+        .map_err(|e| format!("failed to read discovery HTTP address: {e}"))?
+        .port();
+
+    let (events, _) = tokio::sync::broadcast::channel(256);
+    let task = tokio::spawn(serve_discovery_http(app, listener));
+
+    let mut guard = state.discovery_http.lock_or_recover();
+    if let Some(previous) = guard.replace(DiscoveryHttp { port, task, events }) {
+        // Lost a race with a concurrent start; keep the newest
+        previous.task.abort();
+    }
+
+    info!(
+        "Discovery HTTP endpoint listening on http://127.0.0.1:{}",
+        port
+    );
+    Ok(port)
+}
+
+#[tauri::command]
+pub(crate) fn stop_discovery_http(state: State<MdnsState>) -> Result<(), String> {
+    match state.discovery_http.lock_or_recover().take() {
+        // Dropping the sender ends every open event stream
+        Some(http) => {
+            http.task.abort();
+            info!("Discovery HTTP endpoint stopped");
+        }
+        None => info!("No discovery HTTP endpoint was running"),
+    }
+    Ok(())
+}
@@ -0,0 +1,331 @@
+//! Files sent from a client to the Downloads folder.
+
+use std::path::PathBuf;
+
+use log::{info, warn};
+use tauri::{Emitter, Manager, State};
+
+use crate::protocol::send_reply;
+use crate::server::ClientConnection;
+use crate::settings::save_settings;
+use crate::{unix_millis, LockExt, MdnsState};
+
+// ---- File transfer ----
+// Phones can send a file to the desktop's Downloads folder. Off until the user enables
+// it with `set_file_transfer_enabled`. One transfer per connection at a time:
+//   {"type":"file","action":"begin","name":"foo.pdf","size":12345}
+//   {"type":"file","action":"chunk","seq":0,"data":"<base64>"}   (seq counts from 0)
+//   {"type":"file","action":"end"}
+// Chunks are written to a temp file; on "end" the size is checked and the file moves
+// to Downloads (renamed "foo (1).pdf" etc. if the name is taken). Each step is answered
+// with {"type":"file","action":...,"status":"ok"} or "status":"error" with a reason,
+// and any error abandons the transfer. Chunk lines must stay under MAX_MESSAGE_LEN,
+// so keep raw chunks to about 32 KiB.
+const DEFAULT_MAX_FILE_SIZE: u64 = 100 * 1024 * 1024;
+const MAX_FILE_SIZE_LIMIT: u64 = 4 * 1024 * 1024 * 1024;
+
+pub(crate) struct FileTransfer {
+    pub(crate) name: String,
+    size: u64,
+    received: u64,
+    next_seq: u64,
+    temp_path: PathBuf,
+    file: std::fs::File,
+}
+
+impl Drop for FileTransfer {
+    // Removes the partial file; after a successful move there is nothing left to remove
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.temp_path);
+    }
+}
+
+pub(crate) fn handle_file_command(
+    app: &tauri::AppHandle,
+    conn: &ClientConnection,
+    action: &str,
+    json_data: &serde_json::Value,
+) {
+    let result = if !*app
+        .state::<MdnsState>()
+        .file_transfer_enabled
+        .lock_or_recover()
+    {
+        Err("file transfer is disabled".to_string())
+    } else {
+        match action {
+            "begin" => begin_file_transfer(app, conn, json_data),
+            "chunk" => write_file_chunk(conn, json_data),
+            "end" => {
+                finish_file_transfer(app, conn);
+                return;
+            }
+            _ => Err(format!("unknown file action: {}", action)),
+        }
+    };
+    if let Err(e) = &result {
+        warn!("File transfer from {} failed: {}", conn.addr, e);
+        conn.file_transfer.lock_or_recover().take();
+    }
+    send_reply(&conn.reply(), file_reply(action, result));
+}
+
+fn file_reply(action: &str, result: Result<serde_json::Value, String>) -> serde_json::Value {
+    let mut reply = serde_json::json!({ "type": "file", "action": action });
+    match result {
+        Ok(extra) => {
+            reply["status"] = "ok".into();
+            if let serde_json::Value::Object(extra) = extra {
+                reply.as_object_mut().unwrap().extend(extra);
+            }
+        }
+        Err(e) => {
+            reply["status"] = "error".into();
+            reply["reason"] = e.into();
+        }
+    }
+    reply
+}
+
+// Only the final path component is kept, so a name can't climb out of Downloads. Names
+// Windows would treat as something other than a plain file are refused on every
+// platform: a ":" makes "C:evil.exe" drive-relative (joining it replaces Downloads) and
+// "a.txt:stream" an alternate data stream, device names like "NUL.txt" open the device,
+// and trailing dots or spaces are silently dropped, so the saved name would differ.
+const RESERVED_FILE_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+fn sanitize_file_name(name: &str) -> Option<String> {
+    let name = name.rsplit(['/', '\\']).next()?.trim_start();
+    let name: String = name.chars().filter(|c| !c.is_control()).collect();
+    if name.is_empty() || name == "." || name == ".." {
+        return None;
+    }
+    if name.contains([':', '<', '>', '"', '|', '?', '*']) || name.ends_with(['.', ' ']) {
+        return None;
+    }
+    let stem = name.split('.').next().unwrap_or_default().trim_end();
+    if RESERVED_FILE_NAMES
+        .iter()
+        .any(|reserved| stem.eq_ignore_ascii_case(reserved))
+    {
+        return None;
+    }
+    Some(name)
+}
+
+fn begin_file_transfer(
+    app: &tauri::AppHandle,
+    conn: &ClientConnection,
+    json_data: &serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    let name = json_data
+        .get("name")
+        .and_then(|v| v.as_str())
+        .and_then(sanitize_file_name)
+        .ok_or("missing or invalid file name")?;
+    let size = json_data
+        .get("size")
+        .and_then(|v| v.as_u64())
+        .ok_or("missing file size")?;
+    let max_size = app
+        .state::<MdnsState>()
+        .settings
+        .lock_or_recover()
+        .max_file_size
+        .unwrap_or(DEFAULT_MAX_FILE_SIZE);
+    if size > max_size {
+        return Err(format!(
+            "file is {} bytes, the limit is {} bytes",
+            size, max_size
+        ));
+    }
+
+    let temp_path = std::env::temp_dir().join(format!(
+        "bruteconnect-{}-{}.part",
+        unix_millis(),
+        rand::random::<u32>()
+    ));
+    let file = std::fs::File::create(&temp_path)
+        .map_err(|e| format!("failed to create temp file: {e}"))?;
+
+    let mut slot = conn.file_transfer.lock_or_recover();
+    if let Some(previous) = slot.take() {
+        info!(
+            "{} started a new transfer; dropping {}",
+            conn.addr, previous.name
+        );
+    }
+    info!("Receiving {} ({} bytes) from {}", name, size, conn.addr);
+    *slot = Some(FileTransfer {
+        name,
+        size,
+        received: 0,
+        next_seq: 0,
+        temp_path,
+        file,
+    });
+    Ok(serde_json::json!({}))
+}
+
+fn write_file_chunk(
+    conn: &ClientConnection,
+    json_data: &serde_json::Value,
+) -> Result<serde_json::Value, String> {
+    use base64::Engine;
+    use std::io::Write;
+
+    let mut slot = conn.file_transfer.lock_or_recover();
+    let transfer = slot.as_mut().ok_or("no transfer in progress")?;
+
+    let seq = json_data
+        .get("seq")
+        .and_then(|v| v.as_u64())
+        .ok_or("missing chunk seq")?;
+    if seq != transfer.next_seq {
+        return Err(format!("expected chunk {}, got {}", transfer.next_seq, seq));
+    }
+    let data = json_data
+        .get("data")
+        .and_then(|v| v.as_str())
+        .ok_or("missing chunk data")?;
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(data)
+        .map_err(|e| format!("invalid base64 in chunk {}: {e}", seq))?;
+    if transfer.received + bytes.len() as u64 > transfer.size {
+        return Err(format!(
+            "received more than the announced {} bytes",
+            transfer.size
+        ));
+    }
+
+    transfer
+        .file
+        .write_all(&bytes)
+        .map_err(|e| format!("failed to write chunk {}: {e}", seq))?;
+    transfer.received += bytes.len() as u64;
+    transfer.next_seq += 1;
+    Ok(serde_json::json!({ "seq": seq, "received": transfer.received }))
+}
+
+// Flushing and moving a file of up to the size limit can take a while (a copy when
+// temp is on another filesystem), so it runs on the blocking pool and replies from there
+fn finish_file_transfer(app: &tauri::AppHandle, conn: &ClientConnection) {
+    let Some(transfer) = conn.file_transfer.lock_or_recover().take() else {
+        let reply = file_reply("end", Err("no transfer in progress".into()));
+        send_reply(&conn.reply(), reply);
+        return;
+    };
+    let app = app.clone();
+    let addr = conn.addr;
+    let reply_tx = conn.reply();
+    tokio::task::spawn_blocking(move || {
+        let result = save_received_file(&app, addr, transfer);
+        if let Err(e) = &result {
+            warn!("File transfer from {} failed: {}", addr, e);
+        }
+        send_reply(&reply_tx, file_reply("end", result));
+    });
+}
+
+fn save_received_file(
+    app: &tauri::AppHandle,
+    addr: std::net::SocketAddr,
+    transfer: FileTransfer,
+) -> Result<serde_json::Value, String> {
+    if transfer.received != transfer.size {
+        return Err(format!(
+            "received {} of {} bytes",
+            transfer.received, transfer.size
+        ));
+    }
+    transfer
+        .file
+        .sync_all()
+        .map_err(|e| format!("failed to flush file: {e}"))?;
+
+    let dir = dirs::download_dir()
+        .or_else(dirs::home_dir)
+        .ok_or("no Downloads folder available")?;
+    let dest = unique_download_path(&dir, &transfer.name);
+    // rename fails across filesystems (temp on its own mount); copy instead
+    if std::fs::rename(&transfer.temp_path, &dest).is_err() {
+        std::fs::copy(&transfer.temp_path, &dest)
+            .map_err(|e| format!("failed to save {}: {e}", dest.display()))?;
+    }
+
+    let saved_name = dest
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    info!(
+        "Received {} ({} bytes) from {}, saved to {}",
+        transfer.name,
+        transfer.size,
+        addr,
+        dest.display()
+    );
+    let _ = app.emit(
+        "file:received",
+        serde_json::json!({
+            "name": saved_name,
+            "path": dest.to_string_lossy(),
+            "size": transfer.size,
+            "addr": addr.to_string(),
+            "timestamp_ms": unix_millis()
+        }),
+    );
+    Ok(serde_json::json!({ "name": saved_name, "size": transfer.size }))
+}
+
+fn unique_download_path(dir: &std::path::Path, name: &str) -> PathBuf {
+    let candidate = dir.join(name);
+    if !candidate.exists() {
+        return candidate;
+    }
+    let path = std::path::Path::new(name);
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| name.to_string());
+    let extension = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    (1..)
+        .map(|n| dir.join(format!("{} ({}){}", stem, n, extension)))
+        .find(|candidate| !candidate.exists())
+        .expect("unbounded suffix search always finds a free name")
+}
+
+#[tauri::command]
+pub(crate) fn set_file_transfer_enabled(
+    state: State<MdnsState>,
+    enabled: bool,
+) -> Result<(), String> {
+    *state.file_transfer_enabled.lock_or_recover() = enabled;
+    info!(
+        "File transfer {}",
+        if enabled { "enabled" } else { "disabled" }
+    );
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn set_max_file_size(state: State<MdnsState>, bytes: u64) -> Result<(), String> {
+    if bytes == 0 || bytes > MAX_FILE_SIZE_LIMIT {
+        return Err(format!(
+            "Max file size must be between 1 and {} bytes",
+            MAX_FILE_SIZE_LIMIT
+        ));
+    }
+
+    let mut settings = state.settings.lock_or_recover();
+    settings.max_file_size = Some(bytes);
+    save_settings(&settings)?;
+
+    info!("Max file size set to {} bytes", bytes);
+    Ok(())
+}
@@ -0,0 +1,2400 @@
+//! Simulated mouse and keyboard input, and the settings that shape it.
+
+use enigo::{Axis, Button, Coordinate, Direction, Enigo, Key, Keyboard, Mouse, Settings};
+use std::sync::atomic::Ordering;
+
+use log::{debug, error, info, warn};
+use tauri::{Emitter, Manager, State};
+
+use crate::protocol::{
+    ack_message, ack_sender, finish_input_command, queue_input_command, send_ack, send_reply,
+};
+use crate::server::ClientConnection;
+use crate::settings::{
+    save_settings, DragConflictPolicy, InputRetryPolicy, MAX_INPUT_RETRIES,
+    MAX_INPUT_RETRY_DELAY_MS,
+};
+use crate::{noop_input, unix_millis, LockExt, MdnsState};
+
+// ---- Input worker ----
+// Enigo calls (including retries and their backoff sleeps) run on a dedicated thread so
+// slow input never stalls a connection's read loop. Jobs run in the order received.
+// Every blocking sleep in input handling (retry backoff, double-click spacing, move
+// path and smooth-move steps, clipboard restore) relies on running here, never on a
+// tokio worker or the UI thread.
+pub(crate) type InputJob = Box<dyn FnOnce() + Send>;
+
+pub(crate) fn queue_input(app: &tauri::AppHandle, job: InputJob) {
+    let state: State<MdnsState> = app.state();
+    let mut sender = state.input_tx.lock_or_recover();
+
+    let job = match sender.as_ref() {
+        Some(tx) => match tx.send(job) {
+            Ok(()) => return,
+            Err(std::sync::mpsc::SendError(job)) => {
+                error!("Input worker stopped unexpectedly - restarting it");
+                job
+            }
+        },
+        None => job,
+    };
+
+    let (tx, rx) = std::sync::mpsc::channel::<InputJob>();
+    let spawned = std::thread::Builder::new()
+        .name("input-worker".into())
+        .spawn(move || {
+            for job in rx {
+                job();
+            }
+            info!("Input worker stopped");
+        });
+
+    match spawned {
+        Ok(_) => {
+            info!("Input worker started");
+            let _ = tx.send(job);
+            *sender = Some(tx);
+        }
+        Err(e) => error!("Failed to start input worker: {}", e),
+    }
+}
+
+// Hand out the shared Enigo instance, creating it on first use. A failed creation
+// (e.g. no display yet) leaves the slot empty so the next command tries again.
+fn shared_enigo<'a>(slot: &'a mut Option<Enigo>, purpose: &str) -> Result<&'a mut Enigo, String> {
+    if slot.is_none() {
+        match Enigo::new(&Settings::default()) {
+            Ok(enigo) => {
+                info!("Created Enigo instance");
+                *slot = Some(enigo);
+            }
+            Err(e) => {
+                error!("Failed to create Enigo instance for {}: {}", purpose, e);
+                return Err(format!("Input backend unavailable: {e}"));
+            }
+        }
+    }
+    Ok(slot.as_mut().expect("Enigo slot was just filled"))
+}
+
+// Retry a failing Enigo operation with exponential backoff, per the saved retry policy.
+// Only called from the input worker, so sleeping here is fine.
+fn with_retry<F>(app: &tauri::AppHandle, operation: &str, mut op: F) -> enigo::InputResult<()>
+where
+    F: FnMut() -> enigo::InputResult<()>,
+{
+    let policy = {
+        let state: State<MdnsState> = app.state();
+        let policy = state.settings.lock_or_recover().input_retry;
+        policy
+    };
+
+    let mut attempt = 0;
+    loop {
+        match op() {
+            Ok(()) => return Ok(()),
+            Err(e) if attempt < policy.max_retries => {
+                attempt += 1;
+                let delay_ms = policy.base_delay_ms.saturating_mul(1 << (attempt - 1));
+                warn!(
+                    "Retrying {} in {}ms (attempt {}/{}): {}",
+                    operation, delay_ms, attempt, policy.max_retries, e
+                );
+                let _ = app.emit(
+                    "input:retry",
+                    serde_json::json!({
+                        "operation": operation,
+                        "attempt": attempt,
+                        "max_retries": policy.max_retries,
+                        "delay_ms": delay_ms,
+                        "error": e.to_string()
+                    }),
+                );
+                std::thread::sleep(std::time::Duration::from_millis(delay_ms));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// ---- Held key safety ----
+// Keys the app pressed without releasing are tracked in `MdnsState::held_keys`. A
+// watchdog periodically compares them with the OS key state and releases any key the
+// app believes is held but the OS reports as up, so the two can't drift apart and a
+// missed release can never leave the desktop with a stuck modifier. Only Windows
+// exposes the OS key state, so the watchdog is not started anywhere else.
+#[cfg(target_os = "windows")]
+const DEFAULT_HELD_KEY_CHECK_MS: u64 = 2_000;
+const MIN_HELD_KEY_CHECK_MS: u64 = 100;
+const MAX_HELD_KEY_CHECK_MS: u64 = 60_000;
+
+fn track_key_press(app: &tauri::AppHandle, key: Key) {
+    let state: State<MdnsState> = app.state();
+    state
+        .held_keys
+        .lock_or_recover()
+        .insert(key, std::time::Instant::now());
+}
+
+fn track_key_release(app: &tauri::AppHandle, key: Key) {
+    let state: State<MdnsState> = app.state();
+    state.held_keys.lock_or_recover().remove(&key);
+}
+
+// Whether the OS currently reports `key` as down, or None for keys it can't be asked
+// about. Enigo has no query API, so this goes to the platform directly.
+#[cfg(target_os = "windows")]
+fn os_key_state(key: Key) -> Option<bool> {
+    use windows_sys::Win32::UI::Input::KeyboardAndMouse::*;
+
+    let vk = match key {
+        Key::Control => VK_CONTROL,
+        Key::Shift => VK_SHIFT,
+        Key::Alt => VK_MENU,
+        Key::Meta => VK_LWIN,
+        Key::LeftArrow => VK_LEFT,
+        Key::RightArrow => VK_RIGHT,
+        Key::UpArrow => VK_UP,
+        Key::DownArrow => VK_DOWN,
+        Key::Space => VK_SPACE,
+        Key::Return => VK_RETURN,
+        Key::Tab => VK_TAB,
+        Key::Escape => VK_ESCAPE,
+        _ => return None,
+    };
+
+    // The high bit is set while the key is down
+    let state = unsafe { GetAsyncKeyState(vk as i32) };
+    Some((state as u16) & 0x8000 != 0)
+}
+
+#[cfg(not(target_os = "windows"))]
+pub(crate) fn spawn_held_key_watchdog(_app: tauri::AppHandle) {
+    // Neither X11/Wayland nor macOS key state is reachable without extra native
+    // bindings, so there is nothing to compare the held keys against
+    debug!("OS key state is not queryable on this platform - held key check disabled");
+}
+
+#[cfg(target_os = "windows")]
+pub(crate) fn spawn_held_key_watchdog(app: tauri::AppHandle) {
+    // No-op input never presses anything, so there is nothing to fall out of sync
+    if noop_input() {
+        return;
+    }
+    tauri::async_runtime::spawn(async move {
+        loop {
+            // Re-read every round so set_held_key_check_interval applies right away
+            let interval_ms = {
+                let state: State<MdnsState> = app.state();
+                let interval_ms = state.settings.lock_or_recover().held_key_check_ms;
+                interval_ms.unwrap_or(DEFAULT_HELD_KEY_CHECK_MS)
+            };
+            if interval_ms == 0 {
+                // Switched off; look again later in case it is switched back on
+                tokio::time::sleep(std::time::Duration::from_millis(DEFAULT_HELD_KEY_CHECK_MS))
+                    .await;
+                continue;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(interval_ms)).await;
+
+            let held: Vec<Key> = {
+                let state: State<MdnsState> = app.state();
+                let keys = state.held_keys.lock_or_recover().keys().copied().collect();
+                keys
+            };
+
+            let desynced: Vec<Key> = held
+                .into_iter()
+                .filter(|key| os_key_state(*key) == Some(false))
+                .collect();
+            if desynced.is_empty() {
+                continue;
+            }
+
+            let app_for_job = app.clone();
+            queue_input(
+                &app,
+                Box::new(move || release_desynced_keys(&app_for_job, desynced)),
+            );
+        }
+    });
+}
+
+#[tauri::command]
+pub(crate) fn set_held_key_check_interval(
+    state: State<MdnsState>,
+    interval_ms: u64,
+) -> Result<(), String> {
+    if interval_ms != 0 && !(MIN_HELD_KEY_CHECK_MS..=MAX_HELD_KEY_CHECK_MS).contains(&interval_ms) {
+        return Err(format!(
+            "Held key check interval must be 0 (off) or between {} and {} ms",
+            MIN_HELD_KEY_CHECK_MS, MAX_HELD_KEY_CHECK_MS
+        ));
+    }
+
+    let mut settings = state.settings.lock_or_recover();
+    settings.held_key_check_ms = Some(interval_ms);
+    save_settings(&settings)?;
+
+    if interval_ms == 0 {
+        info!("Held key check disabled");
+    } else {
+        info!(
+            "Held keys checked against the OS key state every {} ms",
+            interval_ms
+        );
+    }
+    Ok(())
+}
+
+// Runs on the input worker
+#[cfg(target_os = "windows")]
+fn release_desynced_keys(app: &tauri::AppHandle, keys: Vec<Key>) {
+    if noop_input() {
+        return;
+    }
+    let state: State<MdnsState> = app.state();
+    let mut enigo_slot = state.enigo.lock_or_recover();
+    let Ok(enigo) = shared_enigo(&mut enigo_slot, "key release") else {
+        return;
+    };
+
+    for key in keys {
+        warn!("Held key {:?} is up according to the OS - releasing", key);
+        if let Err(e) = enigo.key(key, Direction::Release) {
+            error!("Failed to release desynced key {:?}: {}", key, e);
+        }
+        track_key_release(app, key);
+        let _ = app.emit(
+            "input:desync-corrected",
+            serde_json::json!({ "key": format!("{:?}", key) }),
+        );
+    }
+}
+
+// Cursor control functions
+pub(crate) fn handle_cursor_command(
+    app: &tauri::AppHandle,
+    action: &str,
+    json_data: &serde_json::Value,
+) -> Result<(), String> {
+    debug!("Handling cursor command: {}", action);
+
+    let state: State<MdnsState> = app.state();
+    let mut enigo_slot = state.enigo.lock_or_recover();
+    let enigo = shared_enigo(&mut enigo_slot, "cursor")?;
+
+    match action {
+        "left_click" => {
+            debug!("Simulating left mouse click");
+            with_retry(app, "left_click", || {
+                enigo.button(Button::Left, Direction::Click)
+            })
+            .map_err(|e| format!("Failed to simulate left click: {e}"))?;
+            emit_input_applied(app, "click", serde_json::json!({ "button": "left" }));
+        }
+        "right_click" => {
+            debug!("Simulating right mouse click");
+            with_retry(app, "right_click", || {
+                enigo.button(Button::Right, Direction::Click)
+            })
+            .map_err(|e| format!("Failed to simulate right click: {e}"))?;
+            emit_input_applied(app, "click", serde_json::json!({ "button": "right" }));
+        }
+        "double_click" => {
+            let interval_ms = json_data
+                .get("intervalMs")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(DEFAULT_DOUBLE_CLICK_INTERVAL_MS)
+                .min(MAX_DOUBLE_CLICK_INTERVAL_MS);
+            debug!("Simulating double click ({}ms apart)", interval_ms);
+            with_retry(app, "double_click", || {
+                enigo.button(Button::Left, Direction::Click)
+            })
+            .and_then(|()| {
+                std::thread::sleep(std::time::Duration::from_millis(interval_ms));
+                with_retry(app, "double_click", || {
+                    enigo.button(Button::Left, Direction::Click)
+                })
+            })
+            .map_err(|e| format!("Failed to simulate double click: {e}"))?;
+            emit_input_applied(
+                app,
+                "double_click",
+                serde_json::json!({ "button": "left", "interval_ms": interval_ms }),
+            );
+        }
+        "middle_click" => {
+            debug!("Simulating middle mouse click");
+            with_retry(app, "middle_click", || {
+                enigo.button(Button::Middle, Direction::Click)
+            })
+            .map_err(|e| format!("Failed to simulate middle click: {e}"))?;
+            emit_input_applied(app, "click", serde_json::json!({ "button": "middle" }));
+        }
+        // Drags are a raw press, any number of "move" deltas, then a release. Which
+        // connection holds the button is tracked in handle_drag_action before these run:
+        // conflicting drags follow the DragConflictPolicy and release_drags_owned_by lets
+        // go for a client that disconnects mid-drag. "release_all" is the manual recovery.
+        "drag_start" => {
+            debug!("Drag start: pressing left mouse button");
+            with_retry(app, "drag_start", || {
+                enigo.button(Button::Left, Direction::Press)
+            })
+            .map_err(|e| format!("Failed to press left button for drag: {e}"))?;
+            emit_input_applied(
+                app,
+                "button",
+                serde_json::json!({ "button": "left", "direction": "press" }),
+            );
+        }
+        "drag_end" => {
+            debug!("Drag end: releasing left mouse button");
+            with_retry(app, "drag_end", || {
+                enigo.button(Button::Left, Direction::Release)
+            })
+            .map_err(|e| format!("Failed to release left button after drag: {e}"))?;
+            emit_input_applied(
+                app,
+                "button",
+                serde_json::json!({ "button": "left", "direction": "release" }),
+            );
+        }
+        "release_all" => {
+            debug!("Releasing all mouse buttons");
+            let mut failed = Vec::new();
+            for (name, button) in [
+                ("left", Button::Left),
+                ("right", Button::Right),
+                ("middle", Button::Middle),
+            ] {
+                match enigo.button(button, Direction::Release) {
+                    Ok(()) => debug!("Released {} mouse button", name),
+                    Err(e) => {
+                        error!("Failed to release {} mouse button: {}", name, e);
+                        failed.push(name);
+                    }
+                }
+            }
+            if !failed.is_empty() {
+                return Err(format!(
+                    "Failed to release mouse button(s): {}",
+                    failed.join(", ")
+                ));
+            }
+        }
+        "move" => {
+            let (Some(delta_x), Some(delta_y)) = (
+                json_data.get("deltaX").and_then(|v| v.as_i64()),
+                json_data.get("deltaY").and_then(|v| v.as_i64()),
+            ) else {
+                return Err("Invalid cursor move command - missing deltaX or deltaY".into());
+            };
+            debug!("Moving cursor by deltaX: {}, deltaY: {}", delta_x, delta_y);
+            let (dx, dy) = scale_cursor_delta(&state, delta_x as f32, delta_y as f32);
+            let smooth = json_data
+                .get("smooth")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            if smooth {
+                let (steps, duration_ms) = smooth_move_settings(&state);
+                smooth_move(app, enigo, dx, dy, steps, duration_ms)?;
+            } else {
+                with_retry(app, "move", || enigo.move_mouse(dx, dy, Coordinate::Rel))
+                    .map_err(|e| format!("Failed to move cursor: {e}"))?;
+            }
+            emit_input_applied(app, "move", serde_json::json!({ "dx": dx, "dy": dy }));
+            emit_laser_position(app, enigo);
+        }
+        "move_abs" => {
+            let (Some(x), Some(y)) = (
+                json_data.get("x").and_then(|v| v.as_i64()),
+                json_data.get("y").and_then(|v| v.as_i64()),
+            ) else {
+                return Err("Invalid cursor move_abs command - missing x or y".into());
+            };
+            debug!("Moving cursor to x: {}, y: {}", x, y);
+            let (x, y) = (x as i32, y as i32);
+            with_retry(app, "move_abs", || enigo.move_mouse(x, y, Coordinate::Abs))
+                .map_err(|e| format!("Failed to move cursor: {e}"))?;
+            emit_input_applied(app, "move_abs", serde_json::json!({ "x": x, "y": y }));
+            emit_laser_position(app, enigo);
+            verify_cursor_position(app, enigo, "move_abs", (x, y));
+        }
+        "move_to" => {
+            let (Some(x), Some(y)) = (
+                json_data.get("x").and_then(|v| v.as_f64()),
+                json_data.get("y").and_then(|v| v.as_f64()),
+            ) else {
+                return Err("Invalid cursor move_to command - missing x or y".into());
+            };
+            let display = json_data
+                .get("display")
+                .and_then(|v| v.as_u64())
+                .map(|index| index as usize);
+            let (x, y) = display_point(app, display, x, y)?;
+            debug!(
+                "Moving cursor to x: {}, y: {} (display {:?})",
+                x, y, display
+            );
+            with_retry(app, "move_to", || enigo.move_mouse(x, y, Coordinate::Abs))
+                .map_err(|e| format!("Failed to move cursor: {e}"))?;
+            emit_input_applied(app, "move_to", serde_json::json!({ "x": x, "y": y }));
+            emit_laser_position(app, enigo);
+            verify_cursor_position(app, enigo, "move_to", (x, y));
+        }
+        "scroll" => {
+            let (Some(direction), Some(delta)) = (
+                json_data.get("direction").and_then(|v| v.as_str()),
+                json_data.get("delta").and_then(|v| v.as_i64()),
+            ) else {
+                return Err("Invalid scroll command - missing direction or delta".into());
+            };
+            let scroll_amount = if direction == "up" {
+                delta as i32
+            } else {
+                -(delta as i32)
+            };
+            debug!("Scrolling {} by delta: {}", direction, scroll_amount);
+            with_retry(app, "scroll", || {
+                enigo.scroll(scroll_amount, Axis::Vertical)
+            })
+            .map_err(|e| format!("Failed to scroll: {e}"))?;
+            emit_input_applied(
+                app,
+                "scroll",
+                serde_json::json!({ "axis": "vertical", "amount": scroll_amount }),
+            );
+        }
+        _ => return Err(format!("Unknown cursor action: {}", action)),
+    }
+
+    Ok(())
+}
+
+// ---- Scroll inertia ----
+// A scroll with `{"inertia":true,"velocity":<units per step>}` keeps scrolling after the
+// flick: every scroll step interval another step goes out, and the velocity is
+// multiplied by the decay factor until it falls below SCROLL_STOP_VELOCITY. Fractions
+// of a unit are carried over. "velocity" defaults to "delta". Any later scroll from the
+// same connection cancels the glide, so flicks replace each other instead of stacking.
+const DEFAULT_SCROLL_DECAY: f32 = 0.85;
+const DEFAULT_SCROLL_STEP_INTERVAL_MS: u64 = 16;
+const MAX_SCROLL_STEP_INTERVAL_MS: u64 = 200;
+const SCROLL_STOP_VELOCITY: f32 = 0.5;
+const MAX_SCROLL_INERTIA_STEPS: u32 = 500;
+
+pub(crate) fn handle_scroll_action(
+    app: &tauri::AppHandle,
+    conn: &ClientConnection,
+    json_data: &serde_json::Value,
+) {
+    if let Some(task) = conn.scroll_inertia.lock_or_recover().take() {
+        task.abort();
+    }
+
+    let inertia = json_data
+        .get("inertia")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    if !inertia {
+        queue_input_command(app, ack_sender(conn), "cursor", "scroll", json_data);
+        return;
+    }
+
+    let direction = json_data.get("direction").and_then(|v| v.as_str());
+    let velocity = json_data
+        .get("velocity")
+        .or_else(|| json_data.get("delta"))
+        .and_then(|v| v.as_f64());
+    let (Some(direction), Some(velocity)) = (direction, velocity) else {
+        send_ack(
+            conn,
+            "cursor",
+            "scroll",
+            &Err("Invalid scroll command - missing direction or velocity".into()),
+        );
+        return;
+    };
+    let sign = if direction == "up" { 1.0 } else { -1.0 };
+    let velocity = sign * velocity as f32;
+
+    let (decay, interval_ms) = {
+        let state: State<MdnsState> = app.state();
+        let settings = state.settings.lock_or_recover();
+        (
+            settings.scroll_decay.unwrap_or(DEFAULT_SCROLL_DECAY),
+            settings
+                .scroll_step_interval_ms
+                .unwrap_or(DEFAULT_SCROLL_STEP_INTERVAL_MS),
+        )
+    };
+    debug!(
+        "Inertia scroll from {} at {} (decay {}, every {} ms)",
+        conn.addr, velocity, decay, interval_ms
+    );
+
+    let task = tokio::spawn(run_scroll_inertia(
+        app.clone(),
+        velocity,
+        decay,
+        interval_ms,
+    ));
+    *conn.scroll_inertia.lock_or_recover() = Some(task);
+    send_ack(conn, "cursor", "scroll", &Ok(()));
+}
+
+async fn run_scroll_inertia(
+    app: tauri::AppHandle,
+    mut velocity: f32,
+    decay: f32,
+    interval_ms: u64,
+) {
+    let interval = std::time::Duration::from_millis(interval_ms);
+    let mut carried = 0.0f32;
+    for _ in 0..MAX_SCROLL_INERTIA_STEPS {
+        if velocity.abs() < SCROLL_STOP_VELOCITY {
+            break;
+        }
+        let wanted = velocity + carried;
+        let amount = wanted.trunc();
+        carried = wanted - amount;
+        velocity *= decay;
+
+        if amount != 0.0 {
+            let app_for_job = app.clone();
+            let amount = amount as i32;
+            queue_input(
+                &app,
+                Box::new(move || {
+                    if let Err(e) = scroll_step(&app_for_job, amount) {
+                        warn!("Inertia scroll step failed: {}", e);
+                    }
+                }),
+            );
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+// Runs on the input worker
+fn scroll_step(app: &tauri::AppHandle, amount: i32) -> Result<(), String> {
+    if noop_input() {
+        return Ok(());
+    }
+    let state: State<MdnsState> = app.state();
+    let mut enigo_slot = state.enigo.lock_or_recover();
+    let enigo = shared_enigo(&mut enigo_slot, "scroll")?;
+    with_retry(app, "scroll", || enigo.scroll(amount, Axis::Vertical))
+        .map_err(|e| format!("Failed to scroll: {e}"))?;
+    emit_input_applied(
+        app,
+        "scroll",
+        serde_json::json!({ "axis": "vertical", "amount": amount, "inertia": true }),
+    );
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn set_scroll_inertia(
+    state: State<MdnsState>,
+    decay: f32,
+    step_interval_ms: u64,
+) -> Result<(), String> {
+    if !(decay > 0.0 && decay < 1.0) {
+        return Err("Scroll decay must be between 0 and 1 (exclusive)".into());
+    }
+    if step_interval_ms == 0 || step_interval_ms > MAX_SCROLL_STEP_INTERVAL_MS {
+        return Err(format!(
+            "Scroll step interval must be between 1 and {} ms",
+            MAX_SCROLL_STEP_INTERVAL_MS
+        ));
+    }
+
+    let mut settings = state.settings.lock_or_recover();
+    settings.scroll_decay = Some(decay);
+    settings.scroll_step_interval_ms = Some(step_interval_ms);
+    save_settings(&settings)?;
+
+    info!(
+        "Scroll inertia set to decay {} every {} ms",
+        decay, step_interval_ms
+    );
+    Ok(())
+}
+
+// Enigo reports success even when the OS drops the event (a secure input field,
+// Wayland without the right portal), so when verification is on the cursor is read
+// back after an absolute move and "input:ineffective" is emitted if it isn't where it
+// was sent. Off by default: it costs a position query per move. Relative moves and
+// keys can't be checked this way and are never verified.
+const CURSOR_VERIFY_TOLERANCE: i32 = 1;
+
+fn verify_cursor_position(
+    app: &tauri::AppHandle,
+    enigo: &Enigo,
+    action: &str,
+    expected: (i32, i32),
+) {
+    let state: State<MdnsState> = app.state();
+    let enabled = *state.input_verification.lock_or_recover();
+    if !enabled {
+        return;
+    }
+
+    let actual = match enigo.location() {
+        Ok(actual) => actual,
+        Err(e) => {
+            error!("Failed to read cursor position for verification: {}", e);
+            return;
+        }
+    };
+    if (actual.0 - expected.0).abs() <= CURSOR_VERIFY_TOLERANCE
+        && (actual.1 - expected.1).abs() <= CURSOR_VERIFY_TOLERANCE
+    {
+        return;
+    }
+
+    info!(
+        "{} had no effect: cursor at {:?}, expected {:?}",
+        action, actual, expected
+    );
+    let _ = app.emit(
+        "input:ineffective",
+        serde_json::json!({
+            "action": action,
+            "expected": { "x": expected.0, "y": expected.1 },
+            "actual": { "x": actual.0, "y": actual.1 }
+        }),
+    );
+}
+
+// Gap between the two clicks of a double click. Most desktops treat clicks up to
+// ~500ms apart as a double click, so longer gaps are clamped.
+const DEFAULT_DOUBLE_CLICK_INTERVAL_MS: u64 = 50;
+const MAX_DOUBLE_CLICK_INTERVAL_MS: u64 = 400;
+
+// Gesture replay: a "move_path" cursor command carries the whole path as
+// {"steps": [{"dx": 4, "dy": -2, "delay_ms": 8}, ...]} and is replayed on the input
+// worker. Starting a new path cancels any path still running.
+const MAX_MOVE_PATH_STEPS: usize = 500;
+const MAX_MOVE_PATH_DURATION_MS: u64 = 5_000;
+
+struct MoveStep {
+    dx: i32,
+    dy: i32,
+    delay_ms: u64,
+}
+
+fn parse_move_path(json_data: &serde_json::Value) -> Result<Vec<MoveStep>, String> {
+    let steps = json_data
+        .get("steps")
+        .and_then(|v| v.as_array())
+        .ok_or("missing steps array")?;
+
+    if steps.is_empty() {
+        return Err("path has no steps".into());
+    }
+    if steps.len() > MAX_MOVE_PATH_STEPS {
+        return Err(format!(
+            "path has {} steps (max {})",
+            steps.len(),
+            MAX_MOVE_PATH_STEPS
+        ));
+    }
+
+    let mut parsed = Vec::with_capacity(steps.len());
+    let mut total_ms: u64 = 0;
+    for (i, step) in steps.iter().enumerate() {
+        let (Some(dx), Some(dy)) = (
+            step.get("dx").and_then(|v| v.as_i64()),
+            step.get("dy").and_then(|v| v.as_i64()),
+        ) else {
+            return Err(format!("step {} is missing dx or dy", i));
+        };
+        let delay_ms = step.get("delay_ms").and_then(|v| v.as_u64()).unwrap_or(0);
+
+        total_ms = total_ms.saturating_add(delay_ms);
+        if total_ms > MAX_MOVE_PATH_DURATION_MS {
+            return Err(format!(
+                "path is longer than {}ms",
+                MAX_MOVE_PATH_DURATION_MS
+            ));
+        }
+
+        parsed.push(MoveStep {
+            dx: dx as i32,
+            dy: dy as i32,
+            delay_ms,
+        });
+    }
+
+    Ok(parsed)
+}
+
+pub(crate) fn queue_move_path(
+    app: &tauri::AppHandle,
+    conn: &ClientConnection,
+    json_data: &serde_json::Value,
+) {
+    let steps = match parse_move_path(json_data) {
+        Ok(steps) => steps,
+        Err(e) => {
+            warn!("Invalid move_path command - {}", e);
+            send_ack(
+                conn,
+                "cursor",
+                "move_path",
+                &Err(format!("Invalid move_path command - {e}")),
+            );
+            return;
+        }
+    };
+
+    // Bumped before queueing so a path that is already replaying stops at its next step
+    let state: State<MdnsState> = app.state();
+    let generation = state.move_path_generation.fetch_add(1, Ordering::SeqCst) + 1;
+
+    let app_for_job = app.clone();
+    let ack = ack_sender(conn);
+    queue_input(
+        app,
+        Box::new(move || {
+            let result = run_move_path(&app_for_job, steps, generation);
+            finish_input_command(&app_for_job, ack, "cursor", "move_path", &result);
+        }),
+    );
+}
+
+// A path cancelled by a newer one still counts as a success
+fn run_move_path(
+    app: &tauri::AppHandle,
+    steps: Vec<MoveStep>,
+    generation: u64,
+) -> Result<(), String> {
+    debug!("Replaying move path with {} steps", steps.len());
+
+    if noop_input() {
+        debug!("No-op input: move path with {} steps", steps.len());
+        return Ok(());
+    }
+
+    let state: State<MdnsState> = app.state();
+    let mut enigo_slot = state.enigo.lock_or_recover();
+    let enigo = shared_enigo(&mut enigo_slot, "move path")?;
+
+    for (i, step) in steps.iter().enumerate() {
+        if state.move_path_generation.load(Ordering::SeqCst) != generation {
+            debug!("Move path cancelled by a newer path after {} steps", i);
+            return Ok(());
+        }
+
+        with_retry(app, "move_path", || {
+            enigo.move_mouse(step.dx, step.dy, Coordinate::Rel)
+        })
+        .map_err(|e| format!("Failed to move cursor during path replay: {e}"))?;
+        emit_input_applied(
+            app,
+            "move",
+            serde_json::json!({ "dx": step.dx, "dy": step.dy, "path_step": i }),
+        );
+        emit_laser_position(app, enigo);
+
+        if step.delay_ms > 0 {
+            std::thread::sleep(std::time::Duration::from_millis(step.delay_ms));
+        }
+    }
+
+    debug!("Move path replay completed");
+    Ok(())
+}
+
+// ---- Displays ----
+// "move_to" takes x/y normalized to 0..1 across one display, so a phone can point at a
+// spot without knowing the desktop's resolution or layout. `display` is an index into
+// `get_displays` (the primary display when absent); the point is clamped to it.
+// Listing monitors is slow, so the layout is cached: get_displays re-reads it, and so
+// does a move_to naming a display the cache doesn't have.
+#[derive(Clone)]
+pub(crate) struct DisplayInfo {
+    name: String,
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+    primary: bool,
+}
+
+fn display_bounds(monitor: &xcap::Monitor) -> Result<(i32, i32, u32, u32), String> {
+    let read = |e: xcap::XCapError| format!("failed to read display bounds: {e}");
+    Ok((
+        monitor.x().map_err(read)?,
+        monitor.y().map_err(read)?,
+        monitor.width().map_err(read)?,
+        monitor.height().map_err(read)?,
+    ))
+}
+
+fn list_displays() -> Result<Vec<DisplayInfo>, String> {
+    let monitors = xcap::Monitor::all().map_err(|e| format!("failed to list displays: {e}"))?;
+    monitors
+        .iter()
+        .map(|monitor| {
+            let (x, y, width, height) = display_bounds(monitor)?;
+            Ok(DisplayInfo {
+                name: monitor.name().unwrap_or_default(),
+                x,
+                y,
+                width,
+                height,
+                primary: monitor.is_primary().unwrap_or(false),
+            })
+        })
+        .collect()
+}
+
+// Re-read the layout and store it for later lookups
+fn refresh_displays(app: &tauri::AppHandle) -> Result<Vec<DisplayInfo>, String> {
+    let displays = list_displays()?;
+    let state: State<MdnsState> = app.state();
+    *state.displays.lock_or_recover() = Some(displays.clone());
+    Ok(displays)
+}
+
+fn find_display(displays: &[DisplayInfo], display: Option<usize>) -> Option<DisplayInfo> {
+    match display {
+        Some(index) => displays.get(index).cloned(),
+        None => displays
+            .iter()
+            .find(|d| d.primary)
+            .or_else(|| displays.first())
+            .cloned(),
+    }
+}
+
+// The requested display from the cache, re-reading the layout once if it isn't there
+fn cached_display(app: &tauri::AppHandle, display: Option<usize>) -> Result<DisplayInfo, String> {
+    let cached = {
+        let state: State<MdnsState> = app.state();
+        let displays = state.displays.lock_or_recover();
+        displays
+            .as_deref()
+            .and_then(|displays| find_display(displays, display))
+    };
+    if let Some(found) = cached {
+        return Ok(found);
+    }
+
+    let displays = refresh_displays(app)?;
+    find_display(&displays, display).ok_or_else(|| match display {
+        Some(index) => format!(
+            "invalid display index {} ({} display(s) available)",
+            index,
+            displays.len()
+        ),
+        None => "no displays found".to_string(),
+    })
+}
+
+fn display_point(
+    app: &tauri::AppHandle,
+    display: Option<usize>,
+    x: f64,
+    y: f64,
+) -> Result<(i32, i32), String> {
+    let DisplayInfo {
+        x: origin_x,
+        y: origin_y,
+        width,
+        height,
+        ..
+    } = cached_display(app, display)?;
+
+    let along = |fraction: f64, side: u32| {
+        (fraction.clamp(0.0, 1.0) * side.saturating_sub(1) as f64).round() as i32
+    };
+    Ok((origin_x + along(x, width), origin_y + along(y, height)))
+}
+
+#[tauri::command]
+pub(crate) fn get_displays(app: tauri::AppHandle) -> Result<Vec<serde_json::Value>, String> {
+    let displays = refresh_displays(&app)?;
+    Ok(displays
+        .into_iter()
+        .enumerate()
+        .map(|(index, display)| {
+            serde_json::json!({
+                "index": index,
+                "name": display.name,
+                "x": display.x,
+                "y": display.y,
+                "width": display.width,
+                "height": display.height,
+                "primary": display.primary
+            })
+        })
+        .collect())
+}
+
+// ---- Cursor sensitivity ----
+// Relative "move" deltas are multiplied by the sensitivity, and with a non-zero
+// acceleration faster moves are amplified more: the gain grows by `acceleration` for
+// every ACCELERATION_REFERENCE_PX of distance in one move. The total gain is clamped
+// so one move can't throw the cursor off-screen. Sub-pixel remainders are carried
+// over, so a low sensitivity still moves the cursor on slow swipes.
+pub(crate) const DEFAULT_CURSOR_SENSITIVITY: f32 = 1.0;
+const MIN_CURSOR_SENSITIVITY: f32 = 0.1;
+const MAX_CURSOR_SENSITIVITY: f32 = 10.0;
+const MAX_CURSOR_ACCELERATION: f32 = 2.0;
+const ACCELERATION_REFERENCE_PX: f32 = 20.0;
+const MAX_CURSOR_GAIN: f32 = 20.0;
+
+fn scale_cursor_delta(state: &MdnsState, dx: f32, dy: f32) -> (i32, i32) {
+    let sensitivity = *state.cursor_sensitivity.lock_or_recover();
+    let acceleration = *state.cursor_acceleration.lock_or_recover();
+    let distance = dx.hypot(dy);
+    let gain = (sensitivity * (1.0 + acceleration * distance / ACCELERATION_REFERENCE_PX))
+        .min(MAX_CURSOR_GAIN);
+
+    let mut remainder = state.cursor_remainder.lock_or_recover();
+    let x = dx * gain + remainder.0;
+    let y = dy * gain + remainder.1;
+    let (out_x, out_y) = (x.trunc(), y.trunc());
+    *remainder = (x - out_x, y - out_y);
+    (out_x as i32, out_y as i32)
+}
+
+#[tauri::command]
+pub(crate) fn set_cursor_sensitivity(
+    state: State<MdnsState>,
+    sensitivity: f32,
+    acceleration: Option<f32>,
+) -> Result<(), String> {
+    if !(MIN_CURSOR_SENSITIVITY..=MAX_CURSOR_SENSITIVITY).contains(&sensitivity) {
+        return Err(format!(
+            "Cursor sensitivity must be between {} and {}",
+            MIN_CURSOR_SENSITIVITY, MAX_CURSOR_SENSITIVITY
+        ));
+    }
+    let acceleration = acceleration.unwrap_or(0.0);
+    if !(0.0..=MAX_CURSOR_ACCELERATION).contains(&acceleration) {
+        return Err(format!(
+            "Cursor acceleration must be between 0 and {}",
+            MAX_CURSOR_ACCELERATION
+        ));
+    }
+
+    *state.cursor_sensitivity.lock_or_recover() = sensitivity;
+    *state.cursor_acceleration.lock_or_recover() = acceleration;
+    *state.cursor_remainder.lock_or_recover() = (0.0, 0.0);
+    info!(
+        "Cursor sensitivity set to {} (acceleration {})",
+        sensitivity, acceleration
+    );
+    Ok(())
+}
+
+// ---- Smooth moves ----
+// A "move" with `{"smooth":true}` is split into several smaller relative steps spread
+// over a few milliseconds, so sparse large deltas from a laggy link glide instead of
+// jumping. Steps never go below one pixel, so short moves use fewer of them. The
+// input worker is blocked for the duration, which is why it is capped.
+const DEFAULT_SMOOTH_MOVE_STEPS: u32 = 8;
+const MAX_SMOOTH_MOVE_STEPS: u32 = 64;
+const DEFAULT_SMOOTH_MOVE_DURATION_MS: u64 = 16;
+const MAX_SMOOTH_MOVE_DURATION_MS: u64 = 200;
+
+fn smooth_move_settings(state: &MdnsState) -> (u32, u64) {
+    let settings = state.settings.lock_or_recover();
+    (
+        settings
+            .smooth_move_steps
+            .unwrap_or(DEFAULT_SMOOTH_MOVE_STEPS),
+        settings
+            .smooth_move_duration_ms
+            .unwrap_or(DEFAULT_SMOOTH_MOVE_DURATION_MS),
+    )
+}
+
+fn smooth_move(
+    app: &tauri::AppHandle,
+    enigo: &mut Enigo,
+    dx: i32,
+    dy: i32,
+    steps: u32,
+    duration_ms: u64,
+) -> Result<(), String> {
+    let longest = dx.unsigned_abs().max(dy.unsigned_abs());
+    let steps = steps.min(longest).max(1) as i64;
+    let delay = std::time::Duration::from_millis(duration_ms) / steps as u32;
+
+    // Each step moves to the i/steps point, so the parts always add up to the
+    // full delta
+    let (mut moved_x, mut moved_y) = (0i64, 0i64);
+    for i in 1..=steps {
+        let target_x = dx as i64 * i / steps;
+        let target_y = dy as i64 * i / steps;
+        let (step_x, step_y) = ((target_x - moved_x) as i32, (target_y - moved_y) as i32);
+        moved_x = target_x;
+        moved_y = target_y;
+
+        with_retry(app, "move", || {
+            enigo.move_mouse(step_x, step_y, Coordinate::Rel)
+        })
+        .map_err(|e| format!("Failed to move cursor: {e}"))?;
+
+        if i < steps && !delay.is_zero() {
+            std::thread::sleep(delay);
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn set_smooth_move(
+    state: State<MdnsState>,
+    steps: u32,
+    duration_ms: u64,
+) -> Result<(), String> {
+    if !(1..=MAX_SMOOTH_MOVE_STEPS).contains(&steps) {
+        return Err(format!(
+            "Smooth move steps must be between 1 and {}",
+            MAX_SMOOTH_MOVE_STEPS
+        ));
+    }
+    if duration_ms > MAX_SMOOTH_MOVE_DURATION_MS {
+        return Err(format!(
+            "Smooth move duration must be at most {} ms",
+            MAX_SMOOTH_MOVE_DURATION_MS
+        ));
+    }
+
+    let mut settings = state.settings.lock_or_recover();
+    settings.smooth_move_steps = Some(steps);
+    settings.smooth_move_duration_ms = Some(duration_ms);
+    save_settings(&settings)?;
+
+    info!(
+        "Smooth moves set to {} steps over {} ms",
+        steps, duration_ms
+    );
+    Ok(())
+}
+
+// Presentation control functions
+pub(crate) fn handle_presentation_command(
+    app: &tauri::AppHandle,
+    action: &str,
+    json_data: &serde_json::Value,
+) -> Result<(), String> {
+    debug!("Handling presentation command: {}", action);
+
+    let (key, name) = match action {
+        "left" => (Key::LeftArrow, "left"),
+        "right" => (Key::RightArrow, "right"),
+        "start" => (Key::F5, "f5"),
+        "end" => (Key::Escape, "escape"),
+        "blank_black" | "blank_white" => blank_screen_key(action, json_data)?,
+        _ => return Err(format!("Unknown presentation action: {}", action)),
+    };
+
+    let state: State<MdnsState> = app.state();
+    let mut enigo_slot = state.enigo.lock_or_recover();
+    let enigo = shared_enigo(&mut enigo_slot, "presentation")?;
+
+    debug!("Simulating {} key press", name);
+    with_retry(app, action, || enigo.key(key, enigo::Direction::Click))
+        .map_err(|e| format!("Failed to simulate {} key: {e}", name))?;
+    emit_input_applied(app, "key", serde_json::json!({ "key": name }));
+    Ok(())
+}
+
+// Blanking the screen is app-specific: PowerPoint (the default) uses B and W for a
+// black or white screen, Google Slides uses "." and ",". The optional "app" hint
+// ("powerpoint" or "slides") picks the mapping.
+fn blank_screen_key(
+    action: &str,
+    json_data: &serde_json::Value,
+) -> Result<(Key, &'static str), String> {
+    let app_hint = json_data
+        .get("app")
+        .and_then(|v| v.as_str())
+        .unwrap_or("powerpoint");
+    let black = action == "blank_black";
+
+    match app_hint {
+        "powerpoint" if black => Ok((Key::Unicode('b'), "b")),
+        "powerpoint" => Ok((Key::Unicode('w'), "w")),
+        "slides" if black => Ok((Key::Unicode('.'), ".")),
+        "slides" => Ok((Key::Unicode(','), ",")),
+        _ => Err(format!("Unknown presentation app: {}", app_hint)),
+    }
+}
+
+// Media control functions
+pub(crate) fn handle_media_command(app: &tauri::AppHandle, action: &str) -> Result<(), String> {
+    debug!("Handling media command: {}", action);
+
+    let key = match action {
+        "play_pause" => Key::MediaPlayPause,
+        "next" => Key::MediaNextTrack,
+        "previous" => Key::MediaPrevTrack,
+        "volume_up" => Key::VolumeUp,
+        "volume_down" => Key::VolumeDown,
+        "mute" => Key::VolumeMute,
+        _ => return Err(format!("Unknown media action: {}", action)),
+    };
+
+    let state: State<MdnsState> = app.state();
+    let mut enigo_slot = state.enigo.lock_or_recover();
+    let enigo = shared_enigo(&mut enigo_slot, "media")?;
+
+    debug!("Simulating media key {:?}", key);
+    with_retry(app, action, || enigo.key(key, Direction::Click))
+        .map_err(|e| format!("Failed to simulate media key {:?}: {e}", key))?;
+    emit_input_applied(app, "key", serde_json::json!({ "key": action }));
+    Ok(())
+}
+
+// ---- Clipboard ----
+// {"type":"clipboard","action":"set_and_paste","text":"...","restore":true} puts the
+// text on the clipboard and presses the paste shortcut. With "restore", the previous
+// text is put back after a short delay, since apps read the clipboard asynchronously
+// after the keystroke. Only text contents can be restored.
+const CLIPBOARD_RESTORE_DELAY: std::time::Duration = std::time::Duration::from_millis(300);
+
+fn shared_clipboard(
+    slot: &mut Option<arboard::Clipboard>,
+) -> Result<&mut arboard::Clipboard, String> {
+    if slot.is_none() {
+        let clipboard =
+            arboard::Clipboard::new().map_err(|e| format!("Clipboard unavailable: {e}"))?;
+        *slot = Some(clipboard);
+    }
+    Ok(slot.as_mut().expect("clipboard slot was just filled"))
+}
+
+pub(crate) fn handle_clipboard_command(
+    app: &tauri::AppHandle,
+    action: &str,
+    json_data: &serde_json::Value,
+) -> Result<(), String> {
+    debug!("Handling clipboard command: {}", action);
+
+    match action {
+        "set_and_paste" => {
+            let text = json_data
+                .get("text")
+                .and_then(|v| v.as_str())
+                .ok_or("Invalid clipboard command - missing text")?;
+            let restore = json_data
+                .get("restore")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            set_and_paste(app, text, restore)
+        }
+        _ => Err(format!("Unknown clipboard action: {}", action)),
+    }
+}
+
+// {"type":"clipboard","action":"get"} is answered with {"type":"clipboard","text":"..."}.
+// An empty clipboard, or one holding an image or files, reads as "".
+fn read_clipboard_text(app: &tauri::AppHandle) -> Result<String, String> {
+    let state: State<MdnsState> = app.state();
+    let mut clipboard_slot = state.clipboard.lock_or_recover();
+    let clipboard = shared_clipboard(&mut clipboard_slot)?;
+    Ok(clipboard.get_text().unwrap_or_else(|e| {
+        debug!("No text on the clipboard: {}", e);
+        String::new()
+    }))
+}
+
+fn clipboard_message(text: &str) -> serde_json::Value {
+    serde_json::json!({ "type": "clipboard", "text": text })
+}
+
+// Read on the input worker, which owns clipboard access
+pub(crate) fn queue_clipboard_get(app: &tauri::AppHandle, conn: &ClientConnection) {
+    if noop_input() {
+        debug!("No-op input: clipboard get from {}", conn.addr);
+        send_reply(&conn.reply(), clipboard_message(""));
+        return;
+    }
+    let app_for_job = app.clone();
+    let reply = conn.reply();
+    queue_input(
+        app,
+        Box::new(move || match read_clipboard_text(&app_for_job) {
+            Ok(text) => send_reply(&reply, clipboard_message(&text)),
+            Err(e) => {
+                warn!("Failed to read the clipboard: {}", e);
+                send_reply(&reply, ack_message("clipboard", "get", &Err(e)));
+            }
+        }),
+    );
+}
+
+// Send the desktop's clipboard text to every paired client; returns how many got it.
+// The read is queued on the input worker like a client's "get", so it can't race a paste.
+#[tauri::command]
+pub(crate) async fn push_clipboard_to_clients(app: tauri::AppHandle) -> Result<usize, String> {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    let app_for_job = app.clone();
+    queue_input(
+        &app,
+        Box::new(move || {
+            let _ = tx.send(read_clipboard_text(&app_for_job));
+        }),
+    );
+    let text = rx
+        .await
+        .map_err(|_| "Input worker stopped before the clipboard was read".to_string())??;
+    let message = clipboard_message(&text);
+
+    let state: State<MdnsState> = app.state();
+    let clients = state.clients.lock_or_recover();
+    let mut sent = 0;
+    for client in clients.values() {
+        if client.authenticated.load(Ordering::Relaxed) {
+            send_reply(&client.reply, message.clone());
+            sent += 1;
+        }
+    }
+    info!(
+        "Pushed {} clipboard character(s) to {} client(s)",
+        text.chars().count(),
+        sent
+    );
+    Ok(sent)
+}
+
+fn set_and_paste(app: &tauri::AppHandle, text: &str, restore: bool) -> Result<(), String> {
+    let state: State<MdnsState> = app.state();
+    let mut clipboard_slot = state.clipboard.lock_or_recover();
+    let clipboard = shared_clipboard(&mut clipboard_slot)?;
+
+    let previous = if restore {
+        clipboard.get_text().ok()
+    } else {
+        None
+    };
+    clipboard
+        .set_text(text)
+        .map_err(|e| format!("Failed to write clipboard: {e}"))?;
+
+    {
+        let mut enigo_slot = state.enigo.lock_or_recover();
+        let enigo = shared_enigo(&mut enigo_slot, "paste")?;
+        press_combo(
+            app,
+            enigo,
+            "paste",
+            &[primary_modifier()],
+            Key::Unicode('v'),
+        )?;
+    }
+    info!(
+        "Pasted {} characters from the clipboard",
+        text.chars().count()
+    );
+    emit_input_applied(
+        app,
+        "paste",
+        serde_json::json!({ "chars": text.chars().count() }),
+    );
+
+    if let Some(previous) = previous {
+        std::thread::sleep(CLIPBOARD_RESTORE_DELAY);
+        clipboard
+            .set_text(previous)
+            .map_err(|e| format!("Pasted, but failed to restore the clipboard: {e}"))?;
+    }
+    Ok(())
+}
+
+// Keyboard control functions
+pub(crate) fn handle_keyboard_command(
+    app: &tauri::AppHandle,
+    action: &str,
+    json_data: &serde_json::Value,
+) -> Result<(), String> {
+    debug!("Handling keyboard command: {}", action);
+
+    match action {
+        "type" => match json_data.get("text").and_then(|v| v.as_str()) {
+            Some(text) => type_text(app, text),
+            None => Err("Invalid keyboard type command - missing text".into()),
+        },
+        "type_buffer" => type_buffer(app, json_data),
+        "backspace" => backspace(app, json_data),
+        "combo" => key_combo(app, json_data),
+        "key_down" | "key_up" => hold_key(app, action, json_data),
+        "release_all_keys" => release_all_keys(app),
+        _ => Err(format!("Unknown keyboard action: {}", action)),
+    }
+}
+
+// Types one character at a time so a character the platform can't produce (some emoji
+// on some backends) is skipped instead of failing the whole message. Line breaks and
+// tabs are sent as key presses because not every backend types them as text.
+// Only fails when nothing at all could be typed.
+fn type_text(app: &tauri::AppHandle, text: &str) -> Result<(), String> {
+    let state: State<MdnsState> = app.state();
+    let mut enigo_slot = state.enigo.lock_or_recover();
+    let enigo = shared_enigo(&mut enigo_slot, "keyboard")?;
+
+    let mut typed = 0;
+    let mut skipped = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let result = match c {
+            '\r' if chars.peek() == Some(&'\n') => continue,
+            '\r' | '\n' => with_retry(app, "type", || enigo.key(Key::Return, Direction::Click)),
+            '\t' => with_retry(app, "type", || enigo.key(Key::Tab, Direction::Click)),
+            _ => {
+                let mut buf = [0u8; 4];
+                let s = c.encode_utf8(&mut buf);
+                with_retry(app, "type", || enigo.text(s))
+            }
+        };
+
+        match result {
+            Ok(()) => typed += 1,
+            Err(e) => {
+                warn!("Skipping character {:?} that could not be typed: {}", c, e);
+                skipped += 1;
+            }
+        }
+    }
+
+    debug!("Typed {} characters ({} skipped)", typed, skipped);
+    if typed == 0 && skipped > 0 {
+        return Err(format!(
+            "None of the {} character(s) could be typed",
+            skipped
+        ));
+    }
+    if typed > 0 {
+        emit_input_applied(
+            app,
+            "text",
+            serde_json::json!({ "chars": typed, "skipped": skipped }),
+        );
+    }
+    Ok(())
+}
+
+// On-screen keyboards collect a word or line and send it in one message:
+// {"type":"keyboard","action":"type_buffer","text":"hello","submit":true} types the text
+// and, with "submit", presses Enter after it. {"action":"backspace","count":3} deletes
+// the way a held Backspace would; the count is capped so one message can't queue an
+// unbounded number of key presses.
+const MAX_BACKSPACE_COUNT: u64 = 1_000;
+
+fn type_buffer(app: &tauri::AppHandle, json_data: &serde_json::Value) -> Result<(), String> {
+    let text = json_data
+        .get("text")
+        .and_then(|v| v.as_str())
+        .ok_or("Invalid keyboard type_buffer command - missing text")?;
+    let submit = json_data
+        .get("submit")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+
+    if !text.is_empty() {
+        type_text(app, text)?;
+    }
+    if submit {
+        let state: State<MdnsState> = app.state();
+        let mut enigo_slot = state.enigo.lock_or_recover();
+        let enigo = shared_enigo(&mut enigo_slot, "keyboard")?;
+        with_retry(app, "type_buffer", || {
+            enigo.key(Key::Return, Direction::Click)
+        })
+        .map_err(|e| format!("Failed to press Enter: {e}"))?;
+        emit_input_applied(app, "key", serde_json::json!({ "key": "enter" }));
+    }
+    Ok(())
+}
+
+fn backspace(app: &tauri::AppHandle, json_data: &serde_json::Value) -> Result<(), String> {
+    let count = match json_data.get("count") {
+        None => 1,
+        Some(count) => count
+            .as_u64()
+            .filter(|count| (1..=MAX_BACKSPACE_COUNT).contains(count))
+            .ok_or_else(|| {
+                format!(
+                    "Backspace count must be between 1 and {}",
+                    MAX_BACKSPACE_COUNT
+                )
+            })?,
+    };
+
+    let state: State<MdnsState> = app.state();
+    let mut enigo_slot = state.enigo.lock_or_recover();
+    let enigo = shared_enigo(&mut enigo_slot, "keyboard")?;
+    for _ in 0..count {
+        with_retry(app, "backspace", || {
+            enigo.key(Key::Backspace, Direction::Click)
+        })
+        .map_err(|e| format!("Failed to press Backspace: {e}"))?;
+    }
+    emit_input_applied(
+        app,
+        "key",
+        serde_json::json!({ "key": "backspace", "count": count }),
+    );
+    Ok(())
+}
+
+// The modifier for copy/paste/select-all style shortcuts: Cmd on macOS, Ctrl elsewhere.
+// Use this for any such shortcut rather than naming the key.
+pub(crate) fn primary_modifier() -> Key {
+    if cfg!(target_os = "macos") {
+        Key::Meta
+    } else {
+        Key::Control
+    }
+}
+
+// "primary" (or "mod") lets a client ask for the platform's shortcut modifier
+fn parse_modifier(name: &str) -> Option<Key> {
+    match name.to_lowercase().as_str() {
+        "primary" | "mod" => Some(primary_modifier()),
+        "ctrl" | "control" => Some(Key::Control),
+        "alt" | "option" => Some(Key::Alt),
+        "shift" => Some(Key::Shift),
+        "meta" | "cmd" | "command" | "super" | "win" => Some(Key::Meta),
+        _ => None,
+    }
+}
+
+// Key names accepted by `parse_key`, shared with `list_supported_keys` so the UI only
+// offers keys Enigo can press on this platform. Any single character is accepted too.
+const KEY_NAMES: &[(&str, Key)] = &[
+    ("enter", Key::Return),
+    ("return", Key::Return),
+    ("tab", Key::Tab),
+    ("space", Key::Space),
+    ("escape", Key::Escape),
+    ("esc", Key::Escape),
+    ("backspace", Key::Backspace),
+    ("delete", Key::Delete),
+    ("home", Key::Home),
+    ("end", Key::End),
+    ("pageup", Key::PageUp),
+    ("pagedown", Key::PageDown),
+    ("up", Key::UpArrow),
+    ("down", Key::DownArrow),
+    ("left", Key::LeftArrow),
+    ("right", Key::RightArrow),
+    ("capslock", Key::CapsLock),
+    ("f1", Key::F1),
+    ("f2", Key::F2),
+    ("f3", Key::F3),
+    ("f4", Key::F4),
+    ("f5", Key::F5),
+    ("f6", Key::F6),
+    ("f7", Key::F7),
+    ("f8", Key::F8),
+    ("f9", Key::F9),
+    ("f10", Key::F10),
+    ("f11", Key::F11),
+    ("f12", Key::F12),
+    ("f13", Key::F13),
+    ("f14", Key::F14),
+    ("f15", Key::F15),
+    ("f16", Key::F16),
+    ("f17", Key::F17),
+    ("f18", Key::F18),
+    ("f19", Key::F19),
+    ("f20", Key::F20),
+    #[cfg(any(target_os = "windows", all(unix, not(target_os = "macos"))))]
+    ("f21", Key::F21),
+    #[cfg(any(target_os = "windows", all(unix, not(target_os = "macos"))))]
+    ("f22", Key::F22),
+    #[cfg(any(target_os = "windows", all(unix, not(target_os = "macos"))))]
+    ("f23", Key::F23),
+    #[cfg(any(target_os = "windows", all(unix, not(target_os = "macos"))))]
+    ("f24", Key::F24),
+    #[cfg(any(target_os = "windows", all(unix, not(target_os = "macos"))))]
+    ("insert", Key::Insert),
+    #[cfg(any(target_os = "windows", all(unix, not(target_os = "macos"))))]
+    ("numlock", Key::Numlock),
+    #[cfg(any(target_os = "windows", all(unix, not(target_os = "macos"))))]
+    ("pause", Key::Pause),
+    #[cfg(any(target_os = "windows", all(unix, not(target_os = "macos"))))]
+    ("print", Key::Print),
+    #[cfg(any(target_os = "windows", all(unix, not(target_os = "macos"))))]
+    ("help", Key::Help),
+    #[cfg(all(unix, not(target_os = "macos")))]
+    ("scrolllock", Key::ScrollLock),
+    #[cfg(target_os = "windows")]
+    ("scrolllock", Key::Scroll),
+];
+
+pub(crate) fn parse_key(name: &str) -> Option<Key> {
+    let lower = name.to_lowercase();
+    if let Some(&(_, key)) = KEY_NAMES.iter().find(|(n, _)| *n == lower) {
+        return Some(key);
+    }
+
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(Key::Unicode(c.to_ascii_lowercase())),
+        _ => None,
+    }
+}
+
+#[tauri::command]
+pub(crate) fn list_supported_keys() -> Vec<String> {
+    KEY_NAMES.iter().map(|(name, _)| name.to_string()).collect()
+}
+
+// {"action":"combo","modifiers":["ctrl","shift"],"key":"c"}: press the modifiers in
+// order, click the key, then release the modifiers in reverse. Modifiers that were
+// pressed are always released, even when a later step fails.
+fn key_combo(app: &tauri::AppHandle, json_data: &serde_json::Value) -> Result<(), String> {
+    let Some(key_name) = json_data.get("key").and_then(|v| v.as_str()) else {
+        return Err("Invalid keyboard combo command - missing key".into());
+    };
+    let Some(key) = parse_key(key_name) else {
+        return Err(format!("Aborting combo - unknown key '{}'", key_name));
+    };
+
+    let modifier_names: Vec<&str> = json_data
+        .get("modifiers")
+        .and_then(|v| v.as_array())
+        .map(|names| names.iter().filter_map(|v| v.as_str()).collect())
+        .unwrap_or_default();
+    let mut modifiers = Vec::with_capacity(modifier_names.len());
+    for name in &modifier_names {
+        match parse_modifier(name) {
+            Some(modifier) => modifiers.push(modifier),
+            None => return Err(format!("Aborting combo - unknown modifier '{}'", name)),
+        }
+    }
+
+    let state: State<MdnsState> = app.state();
+    let mut enigo_slot = state.enigo.lock_or_recover();
+    let enigo = shared_enigo(&mut enigo_slot, "combo")?;
+
+    debug!("Simulating combo {:?} + {}", modifier_names, key_name);
+    press_combo(app, enigo, "combo", &modifiers, key)?;
+    emit_input_applied(
+        app,
+        "combo",
+        serde_json::json!({ "modifiers": modifier_names, "key": key_name }),
+    );
+    Ok(())
+}
+
+// {"action":"key_down","key":"down"} holds a key until the matching "key_up", so the
+// phone can keep it pressed while the user's finger is down. Modifiers can be held too.
+// Held keys are tracked per connection and released if it drops; see "Held key safety"
+// for the global watchdog.
+fn hold_key(
+    app: &tauri::AppHandle,
+    action: &str,
+    json_data: &serde_json::Value,
+) -> Result<(), String> {
+    let Some(key_name) = json_data.get("key").and_then(|v| v.as_str()) else {
+        return Err(format!("Invalid keyboard {} command - missing key", action));
+    };
+    let Some(key) = parse_key(key_name).or_else(|| parse_modifier(key_name)) else {
+        return Err(format!("Unknown key '{}'", key_name));
+    };
+    let down = action == "key_down";
+    let direction = if down {
+        Direction::Press
+    } else {
+        Direction::Release
+    };
+
+    let state: State<MdnsState> = app.state();
+    let mut enigo_slot = state.enigo.lock_or_recover();
+    let enigo = shared_enigo(&mut enigo_slot, "key hold")?;
+
+    debug!("Key {} {}", key_name, if down { "down" } else { "up" });
+    with_retry(app, action, || enigo.key(key, direction)).map_err(|e| {
+        format!(
+            "Failed to {} key '{}': {e}",
+            if down { "press" } else { "release" },
+            key_name
+        )
+    })?;
+    if down {
+        track_key_press(app, key);
+    } else {
+        track_key_release(app, key);
+    }
+    emit_input_applied(app, action, serde_json::json!({ "key": key_name }));
+    Ok(())
+}
+
+// Safety action: release every key the app is holding, whoever pressed it
+fn release_all_keys(app: &tauri::AppHandle) -> Result<(), String> {
+    let state: State<MdnsState> = app.state();
+    let keys: Vec<Key> = state.held_keys.lock_or_recover().keys().copied().collect();
+    let mut enigo_slot = state.enigo.lock_or_recover();
+    let enigo = shared_enigo(&mut enigo_slot, "key release")?;
+
+    info!("Releasing {} held key(s)", keys.len());
+    release_keys(app, enigo, &keys)
+}
+
+// Release `keys`, carrying on past failures so one bad key can't leave the rest stuck
+fn release_keys(app: &tauri::AppHandle, enigo: &mut Enigo, keys: &[Key]) -> Result<(), String> {
+    let mut result = Ok(());
+    for &key in keys {
+        match with_retry(app, "key_up", || enigo.key(key, Direction::Release)) {
+            Ok(()) => track_key_release(app, key),
+            Err(e) => {
+                error!("Failed to release held key {:?}: {}", key, e);
+                result = Err(format!("Failed to release key {:?}: {e}", key));
+            }
+        }
+    }
+    result
+}
+
+// Press `modifiers` in order, click `key`, release the modifiers in reverse. Whatever
+// was pressed is released even when a later step fails.
+fn press_combo(
+    app: &tauri::AppHandle,
+    enigo: &mut Enigo,
+    operation: &str,
+    modifiers: &[Key],
+    key: Key,
+) -> Result<(), String> {
+    let mut pressed = Vec::with_capacity(modifiers.len());
+    let mut result = Ok(());
+    for &modifier in modifiers {
+        match with_retry(app, operation, || enigo.key(modifier, Direction::Press)) {
+            Ok(()) => {
+                track_key_press(app, modifier);
+                pressed.push(modifier);
+            }
+            Err(e) => {
+                result = Err(format!("Failed to press modifier {:?}: {e}", modifier));
+                break;
+            }
+        }
+    }
+
+    if result.is_ok() {
+        if let Err(e) = with_retry(app, operation, || enigo.key(key, Direction::Click)) {
+            result = Err(format!("Failed to click combo key {:?}: {e}", key));
+        }
+    }
+
+    for &modifier in pressed.iter().rev() {
+        match with_retry(app, operation, || enigo.key(modifier, Direction::Release)) {
+            Ok(()) => track_key_release(app, modifier),
+            Err(e) => error!("Failed to release modifier {:?}: {}", modifier, e),
+        }
+    }
+
+    result
+}
+
+// ---- System shortcuts ----
+// "system" commands press the platform's own shortcut for an OS action. They are off
+// until enabled with `set_system_shortcuts_enabled`, since locking the screen from a
+// phone is not something every user wants. Mappings (README.md lists them too):
+//   show_desktop   Win+D           F11          Super+D
+//   task_switcher  Alt+Tab         Cmd+Tab      Alt+Tab
+// task_switcher keeps its modifier held so repeated commands step through the
+// switcher; task_switcher_end releases it, which picks the highlighted window.
+// Actions with no mapping on this platform fail with "unsupported".
+#[cfg(target_os = "macos")]
+const TASK_SWITCHER_MODIFIER: Key = Key::Meta;
+#[cfg(not(target_os = "macos"))]
+const TASK_SWITCHER_MODIFIER: Key = Key::Alt;
+
+fn system_shortcut(action: &str) -> Option<(&'static [Key], Key)> {
+    #[cfg(target_os = "macos")]
+    let shortcut = match action {
+        "show_desktop" => Some((&[][..], Key::F11)),
+        _ => None,
+    };
+    #[cfg(not(target_os = "macos"))]
+    let shortcut = match action {
+        "show_desktop" => Some((&[Key::Meta][..], Key::Unicode('d'))),
+        _ => None,
+    };
+    shortcut
+}
+
+pub(crate) fn handle_system_command(app: &tauri::AppHandle, action: &str) -> Result<(), String> {
+    debug!("Handling system command: {}", action);
+
+    let state: State<MdnsState> = app.state();
+    let mut enigo_slot = state.enigo.lock_or_recover();
+    let enigo = shared_enigo(&mut enigo_slot, "system")?;
+
+    match action {
+        "task_switcher" => {
+            let held = state
+                .held_keys
+                .lock_or_recover()
+                .contains_key(&TASK_SWITCHER_MODIFIER);
+            if !held {
+                with_retry(app, action, || {
+                    enigo.key(TASK_SWITCHER_MODIFIER, Direction::Press)
+                })
+                .map_err(|e| format!("Failed to open task switcher: {e}"))?;
+                track_key_press(app, TASK_SWITCHER_MODIFIER);
+            }
+            with_retry(app, action, || enigo.key(Key::Tab, Direction::Click))
+                .map_err(|e| format!("Failed to step task switcher: {e}"))?;
+        }
+        "task_switcher_end" => {
+            with_retry(app, action, || {
+                enigo.key(TASK_SWITCHER_MODIFIER, Direction::Release)
+            })
+            .map_err(|e| format!("Failed to close task switcher: {e}"))?;
+            track_key_release(app, TASK_SWITCHER_MODIFIER);
+        }
+        _ => {
+            let (modifiers, key) = system_shortcut(action).ok_or("unsupported")?;
+            press_combo(app, enigo, action, modifiers, key)?;
+        }
+    }
+
+    emit_input_applied(app, "system", serde_json::json!({ "action": action }));
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn set_system_shortcuts_enabled(
+    state: State<MdnsState>,
+    enabled: bool,
+) -> Result<(), String> {
+    *state.system_shortcuts_enabled.lock_or_recover() = enabled;
+    info!(
+        "System shortcuts {}",
+        if enabled { "enabled" } else { "disabled" }
+    );
+    Ok(())
+}
+
+// ---- Power actions ----
+// {"type":"system","action":"sleep"|"display_off"|"lock"} run the OS's own command for a
+// meeting-room remote. They always take this path and are refused until
+// `set_allow_system_commands` turns them on. Every attempt emits "system:action".
+//   lock         LockWorkStation   pmset displaysleepnow   loginctl lock-session
+//   sleep        SetSuspendState   pmset sleepnow          systemctl suspend
+//   display_off  SC_MONITORPOWER   pmset displaysleepnow   xset dpms force off
+// macOS locks on display sleep only when a password is required right after it.
+pub(crate) fn is_power_action(action: &str) -> bool {
+    matches!(action, "lock" | "sleep" | "display_off")
+}
+
+pub(crate) fn handle_power_action(app: &tauri::AppHandle, conn: &ClientConnection, action: &str) {
+    if !*app
+        .state::<MdnsState>()
+        .allow_system_commands
+        .lock_or_recover()
+    {
+        send_ack(
+            conn,
+            "system",
+            action,
+            &Err("system commands are disabled".into()),
+        );
+        return;
+    }
+
+    if noop_input() {
+        debug!("No-op input: power action {} for {}", action, conn.addr);
+        send_ack(conn, "system", action, &Ok(()));
+        return;
+    }
+
+    info!("Running power action {} for {}", action, conn.addr);
+    let app = app.clone();
+    let ack = ack_sender(conn);
+    let addr = conn.addr;
+    let action = action.to_string();
+    // The OS commands can take a moment; keep them off the async workers
+    tokio::task::spawn_blocking(move || {
+        let result = run_power_action(&action);
+        if let Err(e) = &result {
+            warn!("Power action {} failed: {}", action, e);
+        }
+        let _ = app.emit(
+            "system:action",
+            serde_json::json!({
+                "action": action,
+                "addr": addr.to_string(),
+                "ok": result.is_ok(),
+                "error": result.as_ref().err(),
+                "timestamp_ms": unix_millis()
+            }),
+        );
+        if let Some(ack) = ack {
+            send_reply(&ack, ack_message("system", &action, &result));
+        }
+    });
+}
+
+#[cfg(target_os = "windows")]
+fn run_power_action(action: &str) -> Result<(), String> {
+    use windows_sys::Win32::System::Power::SetSuspendState;
+    use windows_sys::Win32::System::Shutdown::LockWorkStation;
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        PostMessageW, HWND_BROADCAST, SC_MONITORPOWER, WM_SYSCOMMAND,
+    };
+
+    let ok = match action {
+        "lock" => unsafe { LockWorkStation() != 0 },
+        "sleep" => unsafe { SetSuspendState(0, 0, 0) != 0 },
+        // lParam 2 = power the display off
+        "display_off" => unsafe {
+            PostMessageW(HWND_BROADCAST, WM_SYSCOMMAND, SC_MONITORPOWER as usize, 2) != 0
+        },
+        _ => return Err("unsupported".into()),
+    };
+    if ok {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} failed: {}",
+            action,
+            std::io::Error::last_os_error()
+        ))
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn run_power_action(action: &str) -> Result<(), String> {
+    #[cfg(target_os = "macos")]
+    let (program, args): (&str, &[&str]) = match action {
+        "lock" | "display_off" => ("pmset", &["displaysleepnow"]),
+        "sleep" => ("pmset", &["sleepnow"]),
+        _ => return Err("unsupported".into()),
+    };
+    #[cfg(not(target_os = "macos"))]
+    let (program, args): (&str, &[&str]) = match action {
+        "lock" => ("loginctl", &["lock-session"]),
+        "sleep" => ("systemctl", &["suspend"]),
+        "display_off" => ("xset", &["dpms", "force", "off"]),
+        _ => return Err("unsupported".into()),
+    };
+
+    let output = std::process::Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => {
+                format!("`{}` is not available on this system", program)
+            }
+            _ => format!("failed to run `{}`: {e}", program),
+        })?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Err(format!(
+        "`{} {}` failed ({}): {}",
+        program,
+        args.join(" "),
+        output.status,
+        stderr.trim()
+    ))
+}
+
+#[tauri::command]
+pub(crate) fn set_allow_system_commands(
+    state: State<MdnsState>,
+    allowed: bool,
+) -> Result<(), String> {
+    *state.allow_system_commands.lock_or_recover() = allowed;
+    warn!(
+        "Power actions from clients are now {}",
+        if allowed { "allowed" } else { "refused" }
+    );
+    Ok(())
+}
+
+// Report an input operation that Enigo actually executed, for the optional on-screen
+// overlay. Disabled by default so normal use doesn't pay for an event per move.
+// Moves are coalesced into at most `activity_events_per_sec` summaries; every other
+// operation is emitted immediately, after flushing pending moves to keep the order.
+fn emit_input_applied(app: &tauri::AppHandle, operation: &str, params: serde_json::Value) {
+    let state: State<MdnsState> = app.state();
+    let enabled = *state.input_overlay_enabled.lock_or_recover();
+    if !enabled {
+        return;
+    }
+
+    if operation == "move" {
+        batch_move_activity(app, &params);
+        return;
+    }
+
+    flush_move_activity(app);
+    let _ = app.emit(
+        "input:applied",
+        serde_json::json!({ "operation": operation, "params": params }),
+    );
+}
+
+const DEFAULT_ACTIVITY_EVENTS_PER_SEC: u32 = 30;
+const MAX_ACTIVITY_EVENTS_PER_SEC: u32 = 120;
+
+#[derive(Default)]
+pub(crate) struct MoveActivity {
+    count: u64,
+    dx: i64,
+    dy: i64,
+    last_emit: Option<std::time::Instant>,
+    flush_scheduled: bool,
+}
+
+impl MoveActivity {
+    fn take_summary(&mut self) -> serde_json::Value {
+        let summary = serde_json::json!({
+            "operation": "move",
+            "params": { "dx": self.dx, "dy": self.dy, "count": self.count }
+        });
+        self.count = 0;
+        self.dx = 0;
+        self.dy = 0;
+        self.last_emit = Some(std::time::Instant::now());
+        summary
+    }
+}
+
+fn batch_move_activity(app: &tauri::AppHandle, params: &serde_json::Value) {
+    let state: State<MdnsState> = app.state();
+    let per_sec = state
+        .settings
+        .lock_or_recover()
+        .activity_events_per_sec
+        .unwrap_or(DEFAULT_ACTIVITY_EVENTS_PER_SEC);
+    let interval = std::time::Duration::from_secs(1) / per_sec.max(1);
+
+    let summary = {
+        let mut batch = state.move_activity.lock_or_recover();
+        batch.count += 1;
+        batch.dx += params.get("dx").and_then(|v| v.as_i64()).unwrap_or(0);
+        batch.dy += params.get("dy").and_then(|v| v.as_i64()).unwrap_or(0);
+
+        let since_last = batch.last_emit.map(|t| t.elapsed());
+        if since_last.is_none_or(|elapsed| elapsed >= interval) {
+            Some(batch.take_summary())
+        } else {
+            // Make sure the tail of a burst still goes out once the window closes
+            if !batch.flush_scheduled {
+                batch.flush_scheduled = true;
+                let wait = interval.saturating_sub(since_last.unwrap_or_default());
+                let app = app.clone();
+                tauri::async_runtime::spawn(async move {
+                    tokio::time::sleep(wait).await;
+                    flush_move_activity(&app);
+                });
+            }
+            None
+        }
+    };
+
+    if let Some(summary) = summary {
+        let _ = app.emit("input:applied", summary);
+    }
+}
+
+fn flush_move_activity(app: &tauri::AppHandle) {
+    let state: State<MdnsState> = app.state();
+    let summary = {
+        let mut batch = state.move_activity.lock_or_recover();
+        batch.flush_scheduled = false;
+        if batch.count == 0 {
+            return;
+        }
+        batch.take_summary()
+    };
+    let _ = app.emit("input:applied", summary);
+}
+
+// ---- Input self-test ----
+// Lets the UI check at startup whether the OS accepts simulated input, rather than the
+// user finding out from a phone that seems to do nothing. The probe is a 0,0 relative
+// move on the input worker: it goes through the OS event path like any other input
+// (and fails the same way without access) but leaves the cursor where it is.
+#[tauri::command]
+pub(crate) async fn test_input(app: tauri::AppHandle) -> Result<(), String> {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    let app_for_job = app.clone();
+    queue_input(
+        &app,
+        Box::new(move || {
+            let _ = tx.send(probe_input(&app_for_job));
+        }),
+    );
+    let result = rx
+        .await
+        .map_err(|_| "Input worker stopped before the self-test ran".to_string())?;
+
+    match result {
+        Ok(()) => {
+            let state: State<MdnsState> = app.state();
+            state
+                .input_permission_checked
+                .store(true, Ordering::Relaxed);
+            info!("Input self-test passed");
+            Ok(())
+        }
+        Err(e) => {
+            warn!("Input self-test failed: {}", e);
+            Err(format!("{} - {}", e, input_permission_hint()))
+        }
+    }
+}
+
+fn probe_input(app: &tauri::AppHandle) -> Result<(), String> {
+    let state: State<MdnsState> = app.state();
+    let mut enigo_slot = state.enigo.lock_or_recover();
+    let enigo = shared_enigo(&mut enigo_slot, "input self-test")?;
+    enigo
+        .move_mouse(0, 0, Coordinate::Rel)
+        .map_err(|e| format!("Failed to simulate input: {e}"))
+}
+
+fn input_permission_hint() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "grant this app Accessibility access in System Settings > Privacy & Security, then restart it"
+    } else if cfg!(target_os = "windows") {
+        "Windows blocks input to apps running as administrator unless this app is elevated too"
+    } else if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        "Wayland compositors may refuse simulated input; try an X11 session"
+    } else {
+        "check that an X server is running and this app may connect to it"
+    }
+}
+
+// ---- Laser pointer ----
+// Presenter highlight. Enigo can't restyle the OS cursor, so while the laser is on the
+// app opens a transparent, click-through, always-on-top window and the frontend draws
+// the highlight there from the "laser:move" events emitted after each cursor move.
+pub(crate) const LASER_WINDOW_LABEL: &str = "laser";
+
+fn emit_laser_position(app: &tauri::AppHandle, enigo: &Enigo) {
+    let state: State<MdnsState> = app.state();
+    let enabled = *state.laser_pointer.lock_or_recover();
+    if !enabled {
+        return;
+    }
+
+    match enigo.location() {
+        Ok((x, y)) => {
+            let _ = app.emit("laser:move", serde_json::json!({ "x": x, "y": y }));
+        }
+        Err(e) => error!("Failed to read cursor position for laser pointer: {}", e),
+    }
+}
+
+// Async so the window is created off the main thread (sync commands that build
+// windows deadlock on Windows)
+#[tauri::command]
+pub(crate) async fn start_laser_pointer(app: tauri::AppHandle) -> Result<(), String> {
+    if app.get_webview_window(LASER_WINDOW_LABEL).is_none() {
+        let overlay = tauri::WebviewWindowBuilder::new(
+            &app,
+            LASER_WINDOW_LABEL,
+            tauri::WebviewUrl::App("index.html#laser".into()),
+        )
+        .title("Laser pointer")
+        .transparent(true)
+        .decorations(false)
+        .shadow(false)
+        .always_on_top(true)
+        .skip_taskbar(true)
+        .maximized(true)
+        .resizable(false)
+        .focused(false)
+        .build()
+        .map_err(|e| format!("failed to open laser pointer overlay: {e}"))?;
+
+        // Clicks must reach whatever is being presented underneath
+        overlay
+            .set_ignore_cursor_events(true)
+            .map_err(|e| format!("failed to make laser pointer overlay click-through: {e}"))?;
+    }
+
+    let state: State<MdnsState> = app.state();
+    *state.laser_pointer.lock_or_recover() = true;
+    info!("Laser pointer started");
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn stop_laser_pointer(
+    app: tauri::AppHandle,
+    state: State<MdnsState>,
+) -> Result<(), String> {
+    *state.laser_pointer.lock_or_recover() = false;
+    if let Some(overlay) = app.get_webview_window(LASER_WINDOW_LABEL) {
+        overlay
+            .close()
+            .map_err(|e| format!("failed to close laser pointer overlay: {e}"))?;
+    }
+    info!("Laser pointer stopped");
+    Ok(())
+}
+
+// ---- Drag ownership ----
+// Enigo has one button state shared by every client, so the connection that pressed
+// a button owns it until it releases. A conflicting drag_start is settled by the
+// saved DragConflictPolicy.
+const DRAG_BUTTON: &str = "left";
+
+pub(crate) fn handle_drag_action(
+    app: &tauri::AppHandle,
+    conn: &ClientConnection,
+    action: &str,
+    json_data: &serde_json::Value,
+) {
+    let state: State<MdnsState> = app.state();
+    let policy = state.settings.lock_or_recover().drag_conflict_policy;
+
+    match action {
+        "drag_start" => {
+            let previous = {
+                let mut owners = state.drag_owners.lock_or_recover();
+                let previous = owners.get(DRAG_BUTTON).copied().filter(|o| *o != conn.addr);
+                if previous.is_none() || policy == DragConflictPolicy::LastWins {
+                    owners.insert(DRAG_BUTTON, conn.addr);
+                }
+                previous
+            };
+
+            if let Some(owner) = previous {
+                let outcome = match policy {
+                    DragConflictPolicy::FirstWins => "rejected",
+                    DragConflictPolicy::LastWins => "taken_over",
+                };
+                info!(
+                    "Drag conflict on {} button: {} is dragging, {} started a drag ({})",
+                    DRAG_BUTTON, owner, conn.addr, outcome
+                );
+                let _ = app.emit(
+                    "input:drag-conflict",
+                    serde_json::json!({
+                        "button": DRAG_BUTTON,
+                        "policy": policy,
+                        "owner": owner.to_string(),
+                        "challenger": conn.addr.to_string(),
+                        "outcome": outcome
+                    }),
+                );
+
+                if policy == DragConflictPolicy::FirstWins {
+                    send_reply(
+                        &conn.reply(),
+                        serde_json::json!({
+                            "ok": false,
+                            "type": "cursor",
+                            "action": action,
+                            "error": "another client is dragging",
+                            // Pre-acknowledgement fields, kept for older clients
+                            "status": "error",
+                            "reason": "another client is dragging"
+                        }),
+                    );
+                    return;
+                }
+                // Let go of the previous drag before pressing for the new owner
+                queue_input_command(app, None, "cursor", "drag_end", json_data);
+            }
+        }
+        "drag_end" => {
+            let mut owners = state.drag_owners.lock_or_recover();
+            if let Some(owner) = owners.get(DRAG_BUTTON).copied() {
+                if owner != conn.addr {
+                    info!(
+                        "Ignoring drag_end from {} - the drag belongs to {}",
+                        conn.addr, owner
+                    );
+                    send_ack(
+                        conn,
+                        "cursor",
+                        action,
+                        &Err("the drag belongs to another client".into()),
+                    );
+                    return;
+                }
+            }
+            owners.remove(DRAG_BUTTON);
+        }
+        // Recovery action: always honoured, whoever owns the button
+        _ => state.drag_owners.lock_or_recover().clear(),
+    }
+
+    queue_input_command(app, ack_sender(conn), "cursor", action, json_data);
+}
+
+// Keep track of which keys each connection holds so a drop mid-hold can release them
+pub(crate) fn handle_key_hold_action(
+    app: &tauri::AppHandle,
+    conn: &ClientConnection,
+    action: &str,
+    json_data: &serde_json::Value,
+) {
+    let key = json_data
+        .get("key")
+        .and_then(|v| v.as_str())
+        .and_then(|name| parse_key(name).or_else(|| parse_modifier(name)));
+    {
+        let mut held = conn.held_keys.lock_or_recover();
+        match (action, key) {
+            ("key_down", Some(key)) => {
+                held.insert(key);
+            }
+            ("key_up", Some(key)) => {
+                held.remove(&key);
+            }
+            ("release_all_keys", _) => held.clear(),
+            _ => {}
+        }
+    }
+
+    queue_input_command(app, ack_sender(conn), "keyboard", action, json_data);
+}
+
+// A client that disconnects mid-hold can never send key_up, so release for it
+pub(crate) fn release_keys_held_by(app: &tauri::AppHandle, conn: &ClientConnection) {
+    let keys: Vec<Key> = conn.held_keys.lock_or_recover().drain().collect();
+    if keys.is_empty() || noop_input() {
+        return;
+    }
+
+    warn!(
+        "{} disconnected holding {} key(s) - releasing them",
+        conn.addr,
+        keys.len()
+    );
+    let app_for_job = app.clone();
+    queue_input(
+        app,
+        Box::new(move || {
+            let state: State<MdnsState> = app_for_job.state();
+            let mut enigo_slot = state.enigo.lock_or_recover();
+            if let Ok(enigo) = shared_enigo(&mut enigo_slot, "key release") {
+                let _ = release_keys(&app_for_job, enigo, &keys);
+            }
+        }),
+    );
+}
+
+// A client that disconnects mid-drag can never send drag_end, so release for it
+pub(crate) fn release_drags_owned_by(app: &tauri::AppHandle, addr: std::net::SocketAddr) {
+    let state: State<MdnsState> = app.state();
+    let owned = {
+        let mut owners = state.drag_owners.lock_or_recover();
+        let before = owners.len();
+        owners.retain(|_, owner| *owner != addr);
+        owners.len() != before
+    };
+
+    if owned {
+        warn!("{} disconnected mid-drag - releasing the button", addr);
+        queue_input_command(app, None, "cursor", "drag_end", &serde_json::Value::Null);
+    }
+}
+
+#[tauri::command]
+pub(crate) fn set_drag_conflict_policy(
+    state: State<MdnsState>,
+    policy: String,
+) -> Result<(), String> {
+    let policy = match policy.as_str() {
+        "first-wins" => DragConflictPolicy::FirstWins,
+        "last-wins" => DragConflictPolicy::LastWins,
+        other => {
+            return Err(format!(
+                "Unknown drag conflict policy '{}' - use first-wins or last-wins",
+                other
+            ))
+        }
+    };
+
+    let mut settings = state.settings.lock_or_recover();
+    settings.drag_conflict_policy = policy;
+    save_settings(&settings)?;
+
+    info!("Drag conflict policy set to {:?}", policy);
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn set_input_overlay(state: State<MdnsState>, enabled: bool) -> Result<(), String> {
+    *state.input_overlay_enabled.lock_or_recover() = enabled;
+    info!(
+        "Input overlay events {}",
+        if enabled { "enabled" } else { "disabled" }
+    );
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn set_input_verification(state: State<MdnsState>, enabled: bool) -> Result<(), String> {
+    *state.input_verification.lock_or_recover() = enabled;
+    info!(
+        "Input verification {}",
+        if enabled { "enabled" } else { "disabled" }
+    );
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn set_activity_event_rate(state: State<MdnsState>, per_sec: u32) -> Result<(), String> {
+    if per_sec == 0 || per_sec > MAX_ACTIVITY_EVENTS_PER_SEC {
+        return Err(format!(
+            "Activity event rate must be between 1 and {} per second",
+            MAX_ACTIVITY_EVENTS_PER_SEC
+        ));
+    }
+
+    let mut settings = state.settings.lock_or_recover();
+    settings.activity_events_per_sec = Some(per_sec);
+    save_settings(&settings)?;
+
+    info!("Move activity events capped at {}/sec", per_sec);
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn set_input_retry(
+    state: State<MdnsState>,
+    max_retries: u32,
+    base_delay_ms: u64,
+) -> Result<(), String> {
+    if max_retries > MAX_INPUT_RETRIES {
+        return Err(format!(
+            "Retries must be between 0 and {}",
+            MAX_INPUT_RETRIES
+        ));
+    }
+    if base_delay_ms == 0 || base_delay_ms > MAX_INPUT_RETRY_DELAY_MS {
+        return Err(format!(
+            "Retry delay must be between 1 and {}ms",
+            MAX_INPUT_RETRY_DELAY_MS
+        ));
+    }
+
+    let mut settings = state.settings.lock_or_recover();
+    settings.input_retry = InputRetryPolicy {
+        max_retries,
+        base_delay_ms,
+    };
+    save_settings(&settings)?;
+
+    info!(
+        "Input retry policy set to {} retries, {}ms base delay",
+        max_retries, base_delay_ms
+    );
+    Ok(())
+}
@@ -0,0 +1,203 @@
+//! Log output, mirrored into the UI as "log:line" events.
+
+use once_cell::sync::OnceCell;
+use std::sync::Mutex;
+
+use log::info;
+use tauri::{Emitter, State};
+
+use crate::settings::save_settings;
+use crate::{unix_millis, LockExt, MdnsState};
+
+// ---- Logging ----
+// Logs go through the `log` macros to env_logger on stderr: info for lifecycle events,
+// debug for per-command traces (every move while dragging), warn/error for failures.
+// The logger lets everything from this crate through and `log::max_level` does the
+// filtering, so `set_log_level` can change it while running. RUST_LOG, when set,
+// replaces that with env_logger's usual filtering and the saved level is ignored.
+//
+// Every record that passes is also sent to the frontend as a "log:line" event
+// {level, message, target, timestamp_ms} for the in-app console. Debug and trace lines
+// can arrive thousands per second while dragging, so at most LOG_DEBUG_EVENTS_PER_SEC
+// of them are forwarded per second and the rest are summarised in one line. Warnings get
+// the same treatment with their own budget, since a misbehaving client on the LAN can
+// trigger one per message.
+pub(crate) const DEFAULT_LOG_LEVEL: &str = "info";
+const LOG_DEBUG_EVENTS_PER_SEC: u32 = 50;
+const LOG_WARN_EVENTS_PER_SEC: u32 = 20;
+
+// Set in setup; lines logged before that only reach stderr
+pub(crate) static LOG_APP: OnceCell<tauri::AppHandle> = OnceCell::new();
+
+thread_local! {
+    // Emitting can log in turn; don't forward those lines back into the bridge
+    static FORWARDING_LOG: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+struct AppLogger {
+    inner: env_logger::Logger,
+    debug_window: Mutex<LogWindow>,
+    warn_window: Mutex<LogWindow>,
+}
+
+#[derive(Default)]
+struct LogWindow {
+    started: Option<std::time::Instant>,
+    forwarded: u32,
+    suppressed: u32,
+}
+
+impl log::Log for AppLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.inner.matches(record) {
+            return;
+        }
+        self.inner.log(record);
+        if FORWARDING_LOG.with(|forwarding| forwarding.replace(true)) {
+            return;
+        }
+        self.forward(record);
+        FORWARDING_LOG.with(|forwarding| forwarding.set(false));
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+impl AppLogger {
+    fn forward(&self, record: &log::Record) {
+        let Some(app) = LOG_APP.get() else {
+            return;
+        };
+
+        let budget = match record.level() {
+            log::Level::Warn => Some((&self.warn_window, LOG_WARN_EVENTS_PER_SEC, "warning")),
+            log::Level::Debug | log::Level::Trace => {
+                Some((&self.debug_window, LOG_DEBUG_EVENTS_PER_SEC, "debug"))
+            }
+            _ => None,
+        };
+        if let Some((window, per_sec, kind)) = budget {
+            let Some(suppressed) = take_log_slot(window, per_sec) else {
+                return;
+            };
+            if suppressed > 0 {
+                emit_log_line(
+                    app,
+                    record.level(),
+                    module_path!(),
+                    &format!(
+                        "{} {} line(s) not forwarded to the console",
+                        suppressed, kind
+                    ),
+                );
+            }
+        }
+
+        emit_log_line(
+            app,
+            record.level(),
+            record.target(),
+            &record.args().to_string(),
+        );
+    }
+}
+
+// None when `window` already forwarded `per_sec` lines this second; otherwise how many
+// lines the previous second held back, to be reported once
+fn take_log_slot(window: &Mutex<LogWindow>, per_sec: u32) -> Option<u32> {
+    let mut window = window.lock_or_recover();
+    let now = std::time::Instant::now();
+    let expired = window
+        .started
+        .is_none_or(|at| now.duration_since(at) >= std::time::Duration::from_secs(1));
+    let suppressed = if expired {
+        let suppressed = window.suppressed;
+        *window = LogWindow {
+            started: Some(now),
+            ..LogWindow::default()
+        };
+        suppressed
+    } else {
+        0
+    };
+    if window.forwarded >= per_sec {
+        window.suppressed += 1;
+        return None;
+    }
+    window.forwarded += 1;
+    Some(suppressed)
+}
+
+fn emit_log_line(app: &tauri::AppHandle, level: log::Level, target: &str, message: &str) {
+    let _ = app.emit(
+        "log:line",
+        serde_json::json!({
+            "level": level.as_str().to_lowercase(),
+            "message": message,
+            "target": target,
+            "timestamp_ms": unix_millis()
+        }),
+    );
+}
+
+// Returns whether RUST_LOG configured the logger
+pub(crate) fn init_logging() -> bool {
+    let mut logger = env_logger::Builder::new();
+    let from_env = match std::env::var("RUST_LOG") {
+        Ok(spec) if !spec.trim().is_empty() => {
+            logger.parse_filters(&spec);
+            true
+        }
+        _ => {
+            // Dependencies stay at warn so debug doesn't drown in their traces
+            logger
+                .filter_level(log::LevelFilter::Warn)
+                .filter_module(module_path!(), log::LevelFilter::Trace);
+            false
+        }
+    };
+    let inner = logger.build();
+    let max_level = if from_env {
+        inner.filter()
+    } else {
+        log::LevelFilter::Info
+    };
+    let logger = AppLogger {
+        inner,
+        debug_window: Mutex::new(LogWindow::default()),
+        warn_window: Mutex::new(LogWindow::default()),
+    };
+    if log::set_logger(Box::leak(Box::new(logger))).is_ok() {
+        log::set_max_level(max_level);
+    }
+    from_env
+}
+
+#[tauri::command]
+pub(crate) fn set_log_level(state: State<MdnsState>, level: String) -> Result<(), String> {
+    let level = level.trim().to_lowercase();
+    let filter: log::LevelFilter = level
+        .parse()
+        .ok()
+        .filter(|filter| *filter != log::LevelFilter::Off)
+        .ok_or_else(|| {
+            format!(
+                "Unknown log level '{}' (use error, warn, info, debug or trace)",
+                level
+            )
+        })?;
+
+    let mut settings = state.settings.lock_or_recover();
+    settings.log_level = Some(level);
+    save_settings(&settings)?;
+    log::set_max_level(filter);
+
+    info!("Log level set to {}", filter);
+    Ok(())
+}
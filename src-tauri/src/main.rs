@@ -1,677 +1,370 @@
-// //src-tauri/src/main.rs
-// // Prevents additional console window on Windows in release, DO NOT REMOVE!!
-// #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
-
-// fn main() {
-//     bruteconnect_desktop_lib::run()
-// }
+// Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use enigo::{Axis, Button, Coordinate, Direction, Enigo, Key, Keyboard, Mouse, Settings};
-use std::{net::IpAddr, sync::Mutex};
-use tauri::Emitter;
-use tokio::io::AsyncReadExt;
-use tokio::net::{TcpListener, TcpStream};
-
-use if_addrs::get_if_addrs;
-use searchlight::{
-    broadcast::{BroadcasterBuilder, BroadcasterHandle, ServiceBuilder},
-    discovery::{DiscoveryBuilder, DiscoveryEvent, DiscoveryHandle, Responder},
-    net::IpVersion,
+mod discovery;
+mod discovery_http;
+mod file_transfer;
+mod input;
+mod logging;
+mod protocol;
+mod screenshot;
+mod server;
+mod settings;
+mod tls;
+
+use enigo::{Enigo, Key};
+use once_cell::sync::OnceCell;
+use std::{
+    collections::{HashMap, HashSet},
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex,
+    },
 };
-use serde::Serialize;
-use tauri::{Manager, State};
 
-// ---- State ----
-#[derive(Default)]
-struct MdnsState {
-    discovery: Mutex<Option<DiscoveryHandle>>,
-    broadcaster: Mutex<Option<BroadcasterHandle>>,
-    last_service_info: Mutex<Option<ServiceInfo>>,
-    socket_server_port: Mutex<Option<u16>>,
-    socket_server_handle: Mutex<Option<tokio::task::JoinHandle<()>>>,
-}
+use log::{debug, error, info, warn};
+use searchlight::{broadcast::BroadcasterHandle, discovery::DiscoveryHandle};
+use tauri::{Manager, State};
 
-#[derive(Clone)]
-struct ServiceInfo {
-    service_type: String,
-    instance_name: String,
-    port: u16,
-    txt: Vec<String>,
+use crate::discovery::{
+    stop_interface_watcher, CachedDevice, DiscoveryStats, ParsedResponder, ServiceInfo,
+};
+use crate::discovery_http::DiscoveryHttp;
+use crate::input::{
+    spawn_held_key_watchdog, DisplayInfo, InputJob, MoveActivity, DEFAULT_CURSOR_SENSITIVITY,
+    LASER_WINDOW_LABEL,
+};
+use crate::logging::{init_logging, DEFAULT_LOG_LEVEL, LOG_APP};
+use crate::server::{
+    emit_pairing_pin, generate_pin, signal_socket_server_shutdown, socket_uptime_secs,
+    start_socket_server, AuthFailures, ClientInfo, PeerChurn, Session,
+};
+use crate::settings::{load_settings, save_settings, AppSettings};
+
+// ---- Async runtime ----
+// Tauri normally builds its own Tokio runtime; we build it ourselves so the worker
+// count can be tuned. Tokio rejects 0 workers, and going far beyond the core count
+// only adds scheduling overhead, so values are clamped to 1..=MAX_WORKER_THREADS.
+// The BRUTECONNECT_WORKER_THREADS environment variable overrides the saved setting.
+// Changes only apply on the next launch since the runtime cannot be resized.
+const MAX_WORKER_THREADS: usize = 64;
+const WORKER_THREADS_ENV: &str = "BRUTECONNECT_WORKER_THREADS";
+static RUNTIME_WORKER_THREADS: OnceCell<usize> = OnceCell::new();
+
+fn available_cores() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
 }
 
-impl Drop for MdnsState {
-    fn drop(&mut self) {
-        println!("MdnsState being dropped - performing final cleanup");
-
-        // Cleanup broadcaster
-        if let Ok(mut broadcaster_guard) = self.broadcaster.lock() {
-            if let Some(handle) = broadcaster_guard.take() {
-                println!("Dropping broadcaster handle...");
-                if let Err(e) = handle.shutdown() {
-                    eprintln!("Error during broadcaster drop cleanup: {}", e);
-                }
-            }
-        }
-
-        // Cleanup socket server
-        if let Ok(mut socket_handle_guard) = self.socket_server_handle.lock() {
-            if let Some(handle) = socket_handle_guard.take() {
-                println!("Dropping socket server handle...");
-                handle.abort();
-            }
-        }
-
-        // Clear service info
-        if let Ok(mut service_info_guard) = self.last_service_info.lock() {
-            *service_info_guard = None;
-        }
-
-        // Cleanup discovery
-        if let Ok(mut discovery_guard) = self.discovery.lock() {
-            if let Some(handle) = discovery_guard.take() {
-                println!("Dropping discovery handle...");
-                if let Err(e) = handle.shutdown() {
-                    eprintln!("Error during discovery drop cleanup: {}", e);
-                }
-            }
-        }
-
-        println!("MdnsState drop cleanup completed");
-    }
-}
+fn resolve_worker_threads(configured: Option<usize>) -> usize {
+    let env_override = std::env::var(WORKER_THREADS_ENV)
+        .ok()
+        .and_then(|v| v.trim().parse::<usize>().ok());
 
-// Collect non-loopback IPs so we can advertise the service.
-fn local_ips() -> Vec<IpAddr> {
-    let mut out = Vec::new();
-    if let Ok(ifaces) = get_if_addrs() {
-        for iface in ifaces {
-            // Skip loopback
-            if iface.is_loopback() {
-                continue;
-            }
-            out.push(iface.ip());
-        }
-    }
-    out
+    env_override
+        .or(configured)
+        .unwrap_or_else(available_cores)
+        .clamp(1, MAX_WORKER_THREADS)
 }
 
-#[derive(Serialize, Clone)]
-struct FoundDevice {
-    name: String,
-    hostname: String,
-    addr: String,
-    port: u16,
-    txt: Vec<String>,
+// ---- Headless mode ----
+// With the `headless` feature the app can run without a window so CI can drive the full
+// socket/dispatch stack. Enabled with `--headless` or BRUTECONNECT_HEADLESS=1; see
+// README.md for the other options. Input goes to a no-op backend unless real input is
+// explicitly requested, so a test run never moves the build machine's cursor.
+#[cfg(feature = "headless")]
+struct HeadlessOptions {
+    mdns: bool,
+    real_input: bool,
 }
 
-#[tauri::command]
-fn register_service(
-    state: State<MdnsState>,
-    service_type: String,  // e.g. "_bruteconnect._tcp.local."
-    instance_name: String, // e.g. "BruteConnect-1234"
-    port: u16,             // e.g. 9000
-    txt: Vec<String>,      // e.g. ["role=desktop"]
-) -> Result<(), String> {
-    // Check if socket server is running
-    let socket_port = state.socket_server_port.lock().unwrap();
-    if socket_port.is_none() {
-        return Err("Socket server must be started before registering mDNS service. Please start the socket server first.".into());
-    }
-    let socket_port = socket_port.unwrap();
-    println!(
-        "Registering service: {} as {} on port {}",
-        service_type, instance_name, port
-    );
-
-    let ips = local_ips();
-    if ips.is_empty() {
-        return Err("No non-loopback IPs found for advertisement".into());
-    }
+#[cfg(feature = "headless")]
+static HEADLESS: OnceCell<HeadlessOptions> = OnceCell::new();
 
-    // Build the service to broadcast
-    let mut svc = ServiceBuilder::new(&service_type, &instance_name, port)
-        .map_err(|e| format!("invalid service params: {e}"))?;
-
-    for ip in ips {
-        svc = svc.add_ip_address(ip);
-        println!("Added IP address: {}", ip);
-    }
-    // Add socket port to TXT records
-    let mut enhanced_txt = txt.clone();
-    enhanced_txt.push(format!("socketPort={}", socket_port));
-
-    // Store service info for potential goodbye messages before consuming txt
-    let service_info = ServiceInfo {
-        service_type: service_type.clone(),
-        instance_name: instance_name.clone(),
-        port,
-        txt: enhanced_txt.clone(),
-    };
+#[cfg(feature = "headless")]
+fn env_flag(name: &str) -> bool {
+    std::env::var(name).is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+}
 
-    for rec in enhanced_txt {
-        svc = svc.add_txt_truncated(rec);
-    }
+#[cfg(feature = "headless")]
+fn headless_options() -> Option<HeadlessOptions> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let has_arg = |name: &str| args.iter().any(|a| a == name);
 
-    let svc = svc
-        .build()
-        .map_err(|e| format!("service build failed: {e}"))?;
-
-    // Start broadcasting in the background and keep its handle
-    let broadcaster = BroadcasterBuilder::new()
-        .add_service(svc)
-        .build(IpVersion::Both)
-        .map_err(|e| format!("broadcaster build failed: {e}"))?
-        .run_in_background();
-
-    let mut guard = state.broadcaster.lock().unwrap();
-    if let Some(prev) = guard.take() {
-        println!("Shutting down previous broadcaster...");
-        let _ = prev.shutdown();
+    if !has_arg("--headless") && !env_flag("BRUTECONNECT_HEADLESS") {
+        return None;
     }
-    *guard = Some(broadcaster);
-
-    // Store the service info
-    *state.last_service_info.lock().unwrap() = Some(service_info);
 
-    println!("Service registration completed successfully");
-    Ok(())
+    Some(HeadlessOptions {
+        mdns: has_arg("--mdns") || env_flag("BRUTECONNECT_HEADLESS_MDNS"),
+        real_input: has_arg("--real-input") || env_flag("BRUTECONNECT_HEADLESS_REAL_INPUT"),
+    })
 }
 
-#[tauri::command]
-fn unregister_service(state: State<MdnsState>) -> Result<(), String> {
-    println!("Unregistering service...");
-
-    match state.broadcaster.lock() {
-        Ok(mut broadcaster_guard) => {
-            if let Some(handle) = broadcaster_guard.take() {
-                println!("Shutting down broadcaster service...");
-
-                // Shutdown the broadcaster - this should send goodbye messages
-                handle
-                    .shutdown()
-                    .map_err(|e| format!("broadcast shutdown failed: {e}"))?;
-
-                println!("Service unregistered successfully");
+#[cfg(feature = "headless")]
+fn without_windows(mut context: tauri::Context) -> tauri::Context {
+    context.config_mut().app.windows.clear();
+    context
+}
 
-                // Send explicit goodbye message to ensure immediate cache invalidation
-                drop(broadcaster_guard); // Release the lock before calling send_goodbye_message
-                if let Err(e) = send_goodbye_message(state.clone()) {
-                    eprintln!("Warning: Failed to send goodbye message: {}", e);
-                }
+// Called once the auto-started socket server is up. Prints a fixed-format line that
+// test harnesses can wait for to learn the port.
+#[cfg(feature = "headless")]
+fn headless_ready(app: &tauri::AppHandle, port: u16) {
+    let Some(options) = HEADLESS.get() else {
+        return;
+    };
 
-                // Clear the service info
-                *state.last_service_info.lock().unwrap() = None;
-            } else {
-                println!("No service was registered");
-            }
-        }
-        Err(e) => {
-            return Err(format!("Failed to acquire broadcaster lock: {e}"));
+    if options.mdns {
+        let state: State<MdnsState> = app.state();
+        if let Err(e) = discovery::register_service(
+            app.clone(),
+            state,
+            "_bruteconnect._tcp.local.".into(),
+            format!("BruteConnect-headless-{}", port),
+            port,
+            vec!["role=desktop".into(), "mode=headless".into()],
+            None,
+            None,
+            None,
+            None,
+        ) {
+            error!("Headless mode failed to register mDNS service: {}", e);
         }
     }
 
-    Ok(())
+    let state: State<MdnsState> = app.state();
+    let pin = state.pairing_pin.lock_or_recover().clone();
+    println!("HEADLESS_READY port={} pin={}", port, pin);
 }
 
-#[tauri::command]
-fn start_discovery(
-    app: tauri::AppHandle,
-    state: State<MdnsState>,
-    service_type: String, // e.g. "_bruteconnect._tcp.local."
-) -> Result<(), String> {
-    if state.discovery.lock().unwrap().is_some() {
-        return Ok(()); // already running
+fn noop_input() -> bool {
+    #[cfg(feature = "headless")]
+    {
+        HEADLESS.get().is_some_and(|options| !options.real_input)
     }
-
-    let app_for_cb = app.clone();
-    let discovery = DiscoveryBuilder::new()
-        .service(&service_type)
-        .map_err(|e| format!("invalid service type: {e}"))?
-        .build(IpVersion::Both)
-        .map_err(|e| format!("discovery build failed: {e}"))?
-        .run_in_background(move |event| match event {
-            DiscoveryEvent::ResponderFound(responder) => {
-                let _ = emit_responder(&app_for_cb, "mdns:found", &responder);
-            }
-            DiscoveryEvent::ResponderLost(responder) => {
-                let _ = emit_responder(&app_for_cb, "mdns:lost", &responder);
-            }
-            DiscoveryEvent::ResponseUpdate { new, .. } => {
-                let _ = emit_responder(&app_for_cb, "mdns:update", &new);
-            } // Fixed: Remove unreachable pattern since all enum variants are covered above
-        });
-
-    *state.discovery.lock().unwrap() = Some(discovery);
-    Ok(())
-}
-
-#[tauri::command]
-fn stop_discovery(state: State<MdnsState>) -> Result<(), String> {
-    println!("Stopping discovery...");
-
-    match state.discovery.lock() {
-        Ok(mut discovery_guard) => {
-            if let Some(handle) = discovery_guard.take() {
-                println!("Shutting down discovery service...");
-                handle
-                    .shutdown()
-                    .map_err(|e| format!("discovery shutdown failed: {e}"))?;
-                println!("Discovery stopped successfully");
-            } else {
-                println!("No discovery was running");
-            }
-        }
-        Err(e) => {
-            return Err(format!("Failed to acquire discovery lock: {e}"));
-        }
+    #[cfg(not(feature = "headless"))]
+    {
+        false
     }
-
-    Ok(())
 }
 
-#[tauri::command]
-fn get_service_status(state: State<MdnsState>) -> Result<serde_json::Value, String> {
-    let broadcaster_active = state
-        .broadcaster
-        .lock()
-        .map(|guard| guard.is_some())
-        .unwrap_or(false);
-
-    let discovery_active = state
-        .discovery
-        .lock()
-        .map(|guard| guard.is_some())
-        .unwrap_or(false);
-
-    Ok(serde_json::json!({
-        "broadcaster_active": broadcaster_active,
-        "discovery_active": discovery_active
-    }))
+// ---- State ----
+#[derive(Default)]
+struct MdnsState {
+    discovery: Mutex<Option<DiscoveryHandle>>,
+    // Required TXT key/value pairs for the current discovery (empty = everything)
+    discovery_filter: Mutex<Vec<(String, String)>>,
+    // Bumped by every start/stop so a stale supervisor knows to exit
+    discovery_generation: AtomicU64,
+    broadcaster: Mutex<Option<BroadcasterHandle>>,
+    last_service_info: Mutex<Option<ServiceInfo>>,
+    // Why the last registration/discovery start failed, cleared when one succeeds;
+    // reported by `get_service_status`
+    broadcaster_error: Mutex<Option<String>>,
+    discovery_error: Mutex<Option<String>>,
+    // Addresses the broadcaster currently advertises; see "Interface watcher"
+    advertised_ips: Mutex<Vec<IpAddr>>,
+    interface_watcher: Mutex<Option<tauri::async_runtime::JoinHandle<()>>>,
+    socket_server_port: Mutex<Option<u16>>,
+    socket_bind_addr: Mutex<Option<IpAddr>>,
+    socket_server_handle: Mutex<Option<tokio::task::JoinHandle<()>>>,
+    // Signals the accept loop to stop and drain; see `run_socket_server`
+    socket_server_shutdown: Mutex<Option<std::sync::Arc<tokio::sync::Notify>>>,
+    socket_server_started_at: Mutex<Option<std::time::Instant>>,
+    // Heartbeat task and the total it reports; see "Status heartbeat"
+    socket_status_task: Mutex<Option<tokio::task::JoinHandle<()>>>,
+    commands_processed: AtomicU64,
+    settings: Mutex<AppSettings>,
+    text_protocol: Mutex<bool>,
+    json_rpc: Mutex<bool>,
+    screenshot_enabled: Mutex<bool>,
+    file_transfer_enabled: Mutex<bool>,
+    system_shortcuts_enabled: Mutex<bool>,
+    // Power actions (lock/sleep/display_off) through OS commands; off by default
+    allow_system_commands: Mutex<bool>,
+    input_overlay_enabled: Mutex<bool>,
+    // Display layout for "move_to", refreshed by get_displays; see `cached_displays`
+    displays: Mutex<Option<Vec<DisplayInfo>>>,
+    // Read the cursor back after absolute moves; see `verify_cursor_position`
+    input_verification: Mutex<bool>,
+    // Set once an input command has worked or "input:permission-denied" has been sent
+    input_permission_checked: AtomicBool,
+    registering: AtomicBool,
+    services_torn_down_at: Mutex<Option<std::time::Instant>>,
+    // Set while the socket server runs with TLS; see "TLS"
+    tls_fingerprint: Mutex<Option<String>>,
+    laser_pointer: Mutex<bool>,
+    // Applied to relative moves; see "Cursor sensitivity"
+    cursor_sensitivity: Mutex<f32>,
+    cursor_acceleration: Mutex<f32>,
+    // Fractions of a pixel left over from scaled moves, carried into the next one
+    cursor_remainder: Mutex<(f32, f32)>,
+    move_activity: Mutex<MoveActivity>,
+    input_tx: Mutex<Option<std::sync::mpsc::Sender<InputJob>>>,
+    // Created on first use by the input worker and reused for every command after
+    enigo: Mutex<Option<Enigo>>,
+    // Kept alive for the app's lifetime: on Linux clipboard contents are only served
+    // while the instance that set them exists
+    clipboard: Mutex<Option<arboard::Clipboard>>,
+    // Connection currently dragging with each mouse button
+    drag_owners: Mutex<HashMap<&'static str, std::net::SocketAddr>>,
+    peer_churn: Mutex<HashMap<IpAddr, PeerChurn>>,
+    allowed_ips: Mutex<HashSet<IpAddr>>,
+    sessions: Mutex<HashMap<String, Session>>,
+    // Live connections; see "Connected clients"
+    clients: Mutex<HashMap<std::net::SocketAddr, ClientInfo>>,
+    // PIN clients must send before any other command; see "Pairing"
+    pairing_pin: Mutex<String>,
+    // Wrong PINs per peer IP, so reconnecting doesn't reset the count
+    auth_failures: Mutex<HashMap<IpAddr, AuthFailures>>,
+    peer_churn_token: AtomicU64,
+    move_path_generation: AtomicU64,
+    strict_message_types: Mutex<bool>,
+    unknown_message_count: AtomicU64,
+    discovery_stats: DiscoveryStats,
+    held_keys: Mutex<HashMap<Key, std::time::Instant>>,
+    devices: Mutex<HashMap<String, CachedDevice>>,
+    responder_cache: Mutex<HashMap<String, std::sync::Arc<ParsedResponder>>>,
+    discovery_http: Mutex<Option<DiscoveryHttp>>,
+    selected_device: Mutex<Option<String>>,
 }
 
-#[tauri::command]
-fn force_cleanup(state: State<MdnsState>) -> Result<(), String> {
-    println!("Force cleanup requested");
-    cleanup(&state);
-    Ok(())
+// ---- Lock poisoning ----
+// A panic while a lock is held (say, inside an input backend) poisons it, and unwrapping
+// would turn every later command touching that state into another panic until restart.
+// Nothing here leaves shared state half-updated across a panic point, so the guard is
+// taken back and the poison cleared.
+trait LockExt<T> {
+    fn lock_or_recover(&self) -> std::sync::MutexGuard<'_, T>;
 }
 
-#[tauri::command]
-fn send_goodbye_message(state: State<MdnsState>) -> Result<(), String> {
-    println!("Sending goodbye message...");
-
-    // Get the last service info
-    let service_info = {
-        let guard = state.last_service_info.lock().unwrap();
-        guard.clone()
-    };
-
-    if let Some(info) = service_info {
-        println!(
-            "Sending goodbye for service: {} ({})",
-            info.instance_name, info.service_type
-        );
-
-        // Create a temporary broadcaster just to send goodbye messages
-        // We'll create the service and immediately shut it down, which should send goodbye messages
-        let ips = local_ips();
-        if ips.is_empty() {
-            return Err("No non-loopback IPs found for goodbye message".into());
-        }
-
-        let mut svc = ServiceBuilder::new(&info.service_type, &info.instance_name, info.port)
-            .map_err(|e| format!("invalid service params for goodbye: {e}"))?;
-
-        for ip in ips {
-            svc = svc.add_ip_address(ip);
-        }
-        for rec in &info.txt {
-            svc = svc.add_txt_truncated(rec.clone());
-        }
-
-        let svc = svc
-            .build()
-            .map_err(|e| format!("service build failed for goodbye: {e}"))?;
-
-        // Create broadcaster and immediately shut it down to send goodbye
-        let goodbye_broadcaster = BroadcasterBuilder::new()
-            .add_service(svc)
-            .build(IpVersion::Both)
-            .map_err(|e| format!("goodbye broadcaster build failed: {e}"))?
-            .run_in_background();
-
-        // Give it a moment to start, then shut down to send goodbye
-        std::thread::sleep(std::time::Duration::from_millis(100));
-
-        goodbye_broadcaster
-            .shutdown()
-            .map_err(|e| format!("goodbye broadcast shutdown failed: {e}"))?;
-
-        println!("Goodbye message sent successfully");
-
-        // Send multiple goodbye messages to ensure they reach all devices
-        println!("Sending additional goodbye messages...");
-        for i in 1..=3 {
-            std::thread::sleep(std::time::Duration::from_millis(200));
-
-            // Create another temporary broadcaster for additional goodbye
-            let mut svc2 = ServiceBuilder::new(&info.service_type, &info.instance_name, info.port)
-                .map_err(|e| format!("invalid service params for goodbye {}: {e}", i))?;
-
-            for ip in local_ips() {
-                svc2 = svc2.add_ip_address(ip);
-            }
-            for rec in &info.txt {
-                svc2 = svc2.add_txt_truncated(rec.clone());
-            }
-
-            let svc2 = svc2
-                .build()
-                .map_err(|e| format!("service build failed for goodbye {}: {e}", i))?;
-
-            let goodbye_broadcaster2 = BroadcasterBuilder::new()
-                .add_service(svc2)
-                .build(IpVersion::Both)
-                .map_err(|e| format!("goodbye broadcaster {} build failed: {e}", i))?
-                .run_in_background();
-
-            std::thread::sleep(std::time::Duration::from_millis(50));
-            let _ = goodbye_broadcaster2.shutdown();
-            println!("Additional goodbye message {} sent", i);
-        }
-
-        // Extra delay for goodbye propagation
-        std::thread::sleep(std::time::Duration::from_millis(300));
-        println!("All goodbye messages propagation completed");
-    } else {
-        println!("No service info available for goodbye message");
+impl<T> LockExt<T> for Mutex<T> {
+    fn lock_or_recover(&self) -> std::sync::MutexGuard<'_, T> {
+        self.lock().unwrap_or_else(|poisoned| {
+            warn!(
+                "Recovering from a poisoned lock on {}",
+                std::any::type_name::<T>()
+            );
+            self.clear_poison();
+            poisoned.into_inner()
+        })
     }
-
-    Ok(())
 }
 
-// Cursor control functions
-fn handle_cursor_command(action: &str, json_data: &serde_json::Value) {
-    println!("Handling cursor command: {}", action);
-
-    let mut enigo = match Enigo::new(&Settings::default()) {
-        Ok(enigo) => enigo,
-        Err(e) => {
-            eprintln!("Failed to create Enigo instance for cursor: {}", e);
-            return;
-        }
-    };
+impl Drop for MdnsState {
+    fn drop(&mut self) {
+        info!("MdnsState being dropped - performing final cleanup");
 
-    match action {
-        "left_click" => {
-            println!("Simulating left mouse click");
-            if let Err(e) = enigo.button(Button::Left, Direction::Click) {
-                eprintln!("Failed to simulate left click: {}", e);
-            }
-        }
-        "right_click" => {
-            println!("Simulating right mouse click");
-            if let Err(e) = enigo.button(Button::Right, Direction::Click) {
-                eprintln!("Failed to simulate right click: {}", e);
-            }
-        }
-        "move" => {
-            if let (Some(delta_x), Some(delta_y)) = (
-                json_data.get("deltaX").and_then(|v| v.as_i64()),
-                json_data.get("deltaY").and_then(|v| v.as_i64()),
-            ) {
-                println!("Moving cursor by deltaX: {}, deltaY: {}", delta_x, delta_y);
-                if let Err(e) = enigo.move_mouse(delta_x as i32, delta_y as i32, Coordinate::Rel) {
-                    eprintln!("Failed to move cursor: {}", e);
-                }
-            } else {
-                println!("Invalid cursor move command - missing deltaX or deltaY");
-            }
-        }
-        "scroll" => {
-            if let (Some(direction), Some(delta)) = (
-                json_data.get("direction").and_then(|v| v.as_str()),
-                json_data.get("delta").and_then(|v| v.as_i64()),
-            ) {
-                let scroll_amount = if direction == "up" {
-                    delta as i32
-                } else {
-                    -(delta as i32)
-                };
-                println!("Scrolling {} by delta: {}", direction, scroll_amount);
-                if let Err(e) = enigo.scroll(scroll_amount, Axis::Vertical) {
-                    eprintln!("Failed to scroll: {}", e);
-                }
-            } else {
-                println!("Invalid scroll command - missing direction or delta");
+        // Cleanup broadcaster
+        if let Some(handle) = self.broadcaster.lock_or_recover().take() {
+            debug!("Dropping broadcaster handle...");
+            if let Err(e) = handle.shutdown() {
+                error!("Error during broadcaster drop cleanup: {}", e);
             }
         }
-        _ => {
-            println!("Unknown cursor action: {}", action);
-        }
-    }
-}
-
-// Presentation control functions
-fn handle_presentation_command(action: &str) {
-    println!("Handling presentation command: {}", action);
-
-    let mut enigo = match Enigo::new(&Settings::default()) {
-        Ok(enigo) => enigo,
-        Err(e) => {
-            eprintln!("Failed to create Enigo instance: {}", e);
-            return;
-        }
-    };
 
-    match action {
-        "left" => {
-            println!("Simulating Left Arrow key press");
-            if let Err(e) = enigo.key(Key::LeftArrow, enigo::Direction::Click) {
-                eprintln!("Failed to simulate Left Arrow key: {}", e);
-            }
-        }
-        "right" => {
-            println!("Simulating Right Arrow key press");
-            if let Err(e) = enigo.key(Key::RightArrow, enigo::Direction::Click) {
-                eprintln!("Failed to simulate Right Arrow key: {}", e);
-            }
-        }
-        _ => {
-            println!("Unknown presentation action: {}", action);
+        // Cleanup socket server
+        if let Some(handle) = self.socket_server_handle.lock_or_recover().take() {
+            debug!("Dropping socket server handle...");
+            handle.abort();
         }
-    }
-}
 
-// Socket server implementation
-async fn handle_socket_connection(mut stream: TcpStream, addr: std::net::SocketAddr) {
-    println!("New socket connection from: {}", addr);
-
-    let mut buffer = [0; 1024];
+        // Clear service info
+        *self.last_service_info.lock_or_recover() = None;
 
-    loop {
-        match stream.read(&mut buffer).await {
-            Ok(0) => {
-                println!("Connection closed by client: {}", addr);
-                break;
-            }
-            Ok(n) => {
-                let message = String::from_utf8_lossy(&buffer[..n]);
-                println!("Received from {}: {}", addr, message.trim());
-
-                // Try to parse as JSON and handle presentation commands
-                if let Ok(json_value) = serde_json::from_str::<serde_json::Value>(message.trim()) {
-                    // Check if it's a direct presentation command
-                    if let (Some(msg_type), Some(action)) = (
-                        json_value.get("type").and_then(|v| v.as_str()),
-                        json_value.get("action").and_then(|v| v.as_str()),
-                    ) {
-                        match msg_type {
-                            "presentation" => handle_presentation_command(action),
-                            "cursor" => handle_cursor_command(action, &json_value),
-                            _ => println!("Unknown message type: {}", msg_type),
-                        }
-                    }
-                    // Check if it's nested in a "data" field (mobile app format)
-                    else if let Some(data_str) = json_value.get("data").and_then(|v| v.as_str()) {
-                        if let Ok(inner_json) = serde_json::from_str::<serde_json::Value>(data_str)
-                        {
-                            if let (Some(msg_type), Some(action)) = (
-                                inner_json.get("type").and_then(|v| v.as_str()),
-                                inner_json.get("action").and_then(|v| v.as_str()),
-                            ) {
-                                match msg_type {
-                                    "presentation" => handle_presentation_command(action),
-                                    "cursor" => handle_cursor_command(action, &inner_json),
-                                    _ => println!("Unknown inner message type: {}", msg_type),
-                                }
-                            } else {
-                                println!("Invalid inner JSON format - missing type or action");
-                            }
-                        } else {
-                            println!("Failed to parse inner JSON data");
-                        }
-                    } else {
-                        println!("Invalid JSON format - missing type/action or data field");
-                    }
-                } else {
-                    println!(
-                        "Failed to parse JSON, treating as plain text: {}",
-                        message.trim()
-                    );
-                }
-            }
-            Err(e) => {
-                eprintln!("Failed to read from socket: {}", e);
-                break;
+        // Cleanup discovery
+        if let Some(handle) = self.discovery.lock_or_recover().take() {
+            debug!("Dropping discovery handle...");
+            if let Err(e) = handle.shutdown() {
+                error!("Error during discovery drop cleanup: {}", e);
             }
         }
-    }
-}
-
-async fn run_socket_server(port: u16) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let addr = format!("0.0.0.0:{}", port);
-    let listener = TcpListener::bind(&addr).await?;
-    println!("Socket server listening on: {}", addr);
 
-    loop {
-        match listener.accept().await {
-            Ok((stream, addr)) => {
-                tokio::spawn(handle_socket_connection(stream, addr));
-            }
-            Err(e) => {
-                eprintln!("Failed to accept connection: {}", e);
-            }
-        }
+        info!("MdnsState drop cleanup completed");
     }
 }
 
 #[tauri::command]
-async fn start_socket_server(state: State<'_, MdnsState>) -> Result<u16, String> {
-    println!("Starting socket server...");
-
-    // Check if server is already running
-    if state.socket_server_port.lock().unwrap().is_some() {
-        let port = state.socket_server_port.lock().unwrap().unwrap();
-        println!("Socket server already running on port: {}", port);
-        return Ok(port);
-    }
-
-    // Get a random free port
-    let port = portpicker::pick_unused_port().ok_or("Failed to find an unused port")?;
-
-    println!("Selected port: {}", port);
-
-    // Start the server in a background task
-    let server_handle = tokio::spawn(async move {
-        if let Err(e) = run_socket_server(port).await {
-            eprintln!("Socket server error: {}", e);
-        }
-    });
-
-    // Store the port and handle
-    *state.socket_server_port.lock().unwrap() = Some(port);
-    *state.socket_server_handle.lock().unwrap() = Some(server_handle);
+fn force_cleanup(state: State<MdnsState>) -> Result<(), String> {
+    info!("Force cleanup requested");
+    cleanup(&state);
+    Ok(())
+}
 
-    println!("Socket server started successfully on port: {}", port);
-    Ok(port)
+// Wall-clock time for event payloads the UI shows in a log
+fn unix_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
 }
 
+// One-shot overview of every subsystem for status dashboards
 #[tauri::command]
-fn stop_socket_server(state: State<MdnsState>) -> Result<(), String> {
-    println!("Stopping socket server...");
+fn get_health_summary(state: State<MdnsState>) -> Result<serde_json::Value, String> {
+    let port = *state.socket_server_port.lock_or_recover();
+    let broadcaster_active = state.broadcaster.lock_or_recover().is_some();
+    let discovery_active = state.discovery.lock_or_recover().is_some();
 
-    // Stop the server task
-    if let Some(handle) = state.socket_server_handle.lock().unwrap().take() {
-        handle.abort();
-        println!("Socket server task stopped");
-    }
-
-    // Clear the port
-    *state.socket_server_port.lock().unwrap() = None;
-
-    println!("Socket server stopped successfully");
-    Ok(())
+    Ok(serde_json::json!({
+        "socket": {
+            "running": port.is_some(),
+            "port": port,
+            "uptime_secs": socket_uptime_secs(&state)
+        },
+        "mdns": {
+            "broadcaster_active": broadcaster_active,
+            "discovery_active": discovery_active,
+            "discovery_stats": state.discovery_stats.snapshot()
+        },
+        "runtime_worker_threads": RUNTIME_WORKER_THREADS.get().copied()
+    }))
 }
 
 #[tauri::command]
-fn get_socket_server_status(state: State<MdnsState>) -> Result<serde_json::Value, String> {
-    let port = *state.socket_server_port.lock().unwrap();
-    let is_running = port.is_some();
+fn get_runtime_worker_threads(state: State<MdnsState>) -> Result<serde_json::Value, String> {
+    let configured = state.settings.lock_or_recover().worker_threads;
 
     Ok(serde_json::json!({
-        "running": is_running,
-        "port": port
+        "effective": RUNTIME_WORKER_THREADS.get().copied(),
+        "configured": configured,
+        "env_override": std::env::var(WORKER_THREADS_ENV).ok(),
+        "available_cores": available_cores(),
+        "max": MAX_WORKER_THREADS
     }))
 }
 
-fn emit_responder(
-    app: &tauri::AppHandle,
-    topic: &str,
-    r: &std::sync::Arc<Responder>,
-) -> Result<(), tauri::Error> {
-    use searchlight::dns::{op::DnsResponse, rr::RData};
-
-    let packet: &DnsResponse = &r.last_response; // last response we got
-
-    let mut name = String::new();
-    let mut port: u16 = 0;
-    let mut hostname = String::new();
-    let mut txt: Vec<String> = Vec::new();
-
-    // Walk additionals to pull SRV/TXT
-    for rec in packet.additionals() {
-        match rec.data() {
-            Some(RData::SRV(srv)) => {
-                hostname = srv.target().to_utf8().trim_end_matches('.').to_string();
-                port = srv.port();
-                name = rec.name().to_utf8().trim_end_matches('.').to_string();
-            }
-            Some(RData::TXT(t)) => {
-                for d in t.txt_data() {
-                    if let Ok(s) = std::str::from_utf8(d) {
-                        txt.push(s.to_string());
-                    }
-                }
-            }
-            _ => {}
+#[tauri::command]
+fn set_runtime_worker_threads(
+    state: State<MdnsState>,
+    threads: Option<usize>, // None restores the default of one per core
+) -> Result<(), String> {
+    if let Some(n) = threads {
+        if n == 0 || n > MAX_WORKER_THREADS {
+            return Err(format!(
+                "Worker threads must be between 1 and {}",
+                MAX_WORKER_THREADS
+            ));
         }
     }
 
-    let payload = FoundDevice {
-        name,
-        hostname,
-        addr: r.addr.ip().to_string(),
-        port,
-        txt,
-    };
+    let mut settings = state.settings.lock_or_recover();
+    settings.worker_threads = threads;
+    save_settings(&settings)?;
 
-    app.emit(topic, payload)
+    info!(
+        "Runtime worker threads set to {:?} (takes effect on next launch)",
+        threads
+    );
+    Ok(())
 }
 
 fn cleanup(state: &MdnsState) {
-    println!("Cleaning up mDNS services...");
+    info!("Cleaning up mDNS services...");
+    stop_interface_watcher(state);
 
     // Use a timeout to ensure cleanup doesn't hang
     let cleanup_timeout = std::time::Duration::from_secs(3);
@@ -679,79 +372,152 @@ fn cleanup(state: &MdnsState) {
 
     let mut services_cleaned = 0;
 
-    // Shutdown socket server
-    if let Ok(mut socket_handle_guard) = state.socket_server_handle.lock() {
-        if let Some(handle) = socket_handle_guard.take() {
-            println!("Shutting down socket server...");
-            handle.abort();
-            println!("Socket server shut down successfully");
-            services_cleaned += 1;
-        } else {
-            println!("No socket server to shut down");
-        }
+    // Shutdown discovery HTTP endpoint
+    if let Some(http) = state.discovery_http.lock_or_recover().take() {
+        info!("Shutting down discovery HTTP endpoint...");
+        http.task.abort();
+    }
+
+    // Shutdown socket server. Connections get the usual grace period if the app
+    // keeps running; on exit the process ends first.
+    if signal_socket_server_shutdown(state) {
+        info!("Socket server shutdown signalled");
+        services_cleaned += 1;
+    } else {
+        info!("No socket server to shut down");
     }
 
     // Clear socket port
-    *state.socket_server_port.lock().unwrap() = None;
+    *state.socket_server_port.lock_or_recover() = None;
+    *state.socket_bind_addr.lock_or_recover() = None;
+    *state.socket_server_started_at.lock_or_recover() = None;
+    *state.tls_fingerprint.lock_or_recover() = None;
 
     // Shutdown broadcaster
-    if let Ok(mut broadcaster_guard) = state.broadcaster.lock() {
-        if let Some(h) = broadcaster_guard.take() {
-            println!("Shutting down broadcaster...");
-            match h.shutdown() {
-                Ok(_) => {
-                    println!("Broadcaster shut down successfully");
-                    services_cleaned += 1;
-                }
-                Err(e) => eprintln!("Error shutting down broadcaster: {}", e),
+    if let Some(h) = state.broadcaster.lock_or_recover().take() {
+        info!("Shutting down broadcaster...");
+        match h.shutdown() {
+            Ok(_) => {
+                info!("Broadcaster shut down successfully");
+                services_cleaned += 1;
             }
-        } else {
-            println!("No broadcaster to shut down");
+            Err(e) => error!("Error shutting down broadcaster: {}", e),
         }
     } else {
-        eprintln!("Failed to acquire broadcaster lock for cleanup");
+        info!("No broadcaster to shut down");
     }
 
     // Shutdown discovery
-    if let Ok(mut discovery_guard) = state.discovery.lock() {
-        if let Some(h) = discovery_guard.take() {
-            println!("Shutting down discovery...");
-            match h.shutdown() {
-                Ok(_) => {
-                    println!("Discovery shut down successfully");
-                    services_cleaned += 1;
-                }
-                Err(e) => eprintln!("Error shutting down discovery: {}", e),
+    state.discovery_generation.fetch_add(1, Ordering::SeqCst);
+    if let Some(h) = state.discovery.lock_or_recover().take() {
+        info!("Shutting down discovery...");
+        match h.shutdown() {
+            Ok(_) => {
+                info!("Discovery shut down successfully");
+                services_cleaned += 1;
             }
-        } else {
-            println!("No discovery to shut down");
+            Err(e) => error!("Error shutting down discovery: {}", e),
         }
     } else {
-        eprintln!("Failed to acquire discovery lock for cleanup");
+        info!("No discovery to shut down");
     }
 
     let elapsed = start_time.elapsed();
-    println!(
+    info!(
         "mDNS cleanup completed in {:?} ({} services cleaned)",
         elapsed, services_cleaned
     );
 
     if elapsed > cleanup_timeout {
-        eprintln!("Warning: Cleanup took longer than expected ({:?})", elapsed);
+        warn!("Cleanup took longer than expected ({:?})", elapsed);
     }
 
-    // Give extra time for goodbye messages to propagate across the network
     if services_cleaned > 0 {
-        println!("Waiting for goodbye messages to propagate across network...");
-        std::thread::sleep(std::time::Duration::from_millis(750));
-        println!("Network cleanup delay completed");
+        *state.services_torn_down_at.lock_or_recover() = Some(std::time::Instant::now());
+    }
+}
+
+// Goodbyes from the last cleanup need a moment on the wire before the process goes away.
+// cleanup itself doesn't wait, since it also runs from window events on the UI thread
+// and from force_cleanup while the app keeps going; the exit paths call this instead,
+// which only blocks for whatever is left of the delay.
+const GOODBYE_PROPAGATION_DELAY: std::time::Duration = std::time::Duration::from_millis(750);
+
+fn wait_for_goodbyes(state: &MdnsState) {
+    let Some(torn_down_at) = state.services_torn_down_at.lock_or_recover().take() else {
+        return;
+    };
+    let remaining = GOODBYE_PROPAGATION_DELAY.saturating_sub(torn_down_at.elapsed());
+    if !remaining.is_zero() {
+        info!("Waiting for goodbye messages to propagate across network...");
+        std::thread::sleep(remaining);
+        info!("Network cleanup delay completed");
     }
 }
 
 fn main() {
+    let from_env = init_logging();
+    let context = tauri::generate_context!();
+
+    #[cfg(feature = "headless")]
+    let context = match headless_options() {
+        Some(options) => {
+            info!(
+                "Starting headless (mdns: {}, real input: {})",
+                options.mdns, options.real_input
+            );
+            let _ = HEADLESS.set(options);
+            without_windows(context)
+        }
+        None => context,
+    };
+
+    let settings = load_settings(&context.config().identifier);
+    if !from_env {
+        let level = settings.log_level.as_deref().unwrap_or(DEFAULT_LOG_LEVEL);
+        log::set_max_level(level.parse().unwrap_or(log::LevelFilter::Info));
+    }
+
+    // Build the runtime before anything touches tauri::async_runtime
+    let worker_threads = resolve_worker_threads(settings.worker_threads);
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(worker_threads)
+        .enable_all()
+        .build()
+        .expect("error while building async runtime");
+    tauri::async_runtime::set(runtime.handle().clone());
+    let _ = RUNTIME_WORKER_THREADS.set(worker_threads);
+    info!(
+        "Async runtime started with {} worker threads",
+        worker_threads
+    );
+
+    let mdns_state = MdnsState::default();
+    *mdns_state.cursor_sensitivity.lock_or_recover() = DEFAULT_CURSOR_SENSITIVITY;
+    *mdns_state.pairing_pin.lock_or_recover() = generate_pin();
+    if settings.persist_sessions {
+        *mdns_state.sessions.lock_or_recover() = settings
+            .sessions
+            .iter()
+            .map(|(id, saved)| {
+                let session = Session {
+                    paired: saved.paired,
+                    last_seen_ms: saved.last_seen_ms,
+                    addr: None,
+                    close: None,
+                };
+                (id.clone(), session)
+            })
+            .collect();
+    }
+    *mdns_state.allowed_ips.lock_or_recover() = settings.allowed_ips.clone();
+    *mdns_state.settings.lock_or_recover() = settings;
+
     let app = tauri::Builder::default()
-        .manage(MdnsState::default())
+        .manage(mdns_state)
         .setup(|app| {
+            let _ = LOG_APP.set(app.handle().clone());
+
             // Start socket server automatically when app starts
             let app_handle = app.handle().clone();
             tauri::async_runtime::spawn(async move {
@@ -759,22 +525,38 @@ fn main() {
                 tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
 
                 let state: State<MdnsState> = app_handle.state();
-                match start_socket_server(state).await {
-                    Ok(port) => println!("Socket server auto-started on port: {}", port),
-                    Err(e) => eprintln!("Failed to auto-start socket server: {}", e),
+                match start_socket_server(app_handle.clone(), state, None, None, None).await {
+                    Ok(port) => {
+                        info!("Socket server auto-started on port: {}", port);
+                        #[cfg(feature = "headless")]
+                        headless_ready(&app_handle, port);
+                    }
+                    Err(e) => error!("Failed to auto-start socket server: {}", e),
                 }
             });
+
+            spawn_held_key_watchdog(app.handle().clone());
+
+            let state: State<MdnsState> = app.state();
+            emit_pairing_pin(app.handle(), &state.pairing_pin.lock_or_recover());
             Ok(())
         })
         .on_window_event(|window, event| match event {
+            // The laser overlay coming and going must not tear down networking
+            tauri::WindowEvent::Destroyed if window.label() == LASER_WINDOW_LABEL => {
+                let app_handle = window.app_handle();
+                let state: State<MdnsState> = app_handle.state();
+                *state.laser_pointer.lock_or_recover() = false;
+            }
+            _ if window.label() == LASER_WINDOW_LABEL => {}
             tauri::WindowEvent::CloseRequested { .. } => {
-                println!("Window close requested - cleaning up mDNS services");
+                info!("Window close requested - cleaning up mDNS services");
                 let app_handle = window.app_handle();
                 let state: State<MdnsState> = app_handle.state();
                 cleanup(&state);
             }
             tauri::WindowEvent::Destroyed => {
-                println!("Window destroyed - final cleanup");
+                info!("Window destroyed - final cleanup");
                 let app_handle = window.app_handle();
                 let state: State<MdnsState> = app_handle.state();
                 cleanup(&state);
@@ -782,26 +564,89 @@ fn main() {
             _ => {}
         })
         .invoke_handler(tauri::generate_handler![
-            register_service,
-            unregister_service,
-            start_discovery,
-            stop_discovery,
-            get_service_status,
+            discovery::register_service,
+            discovery::unregister_service,
+            discovery::unregister_service_matching,
+            discovery::check_socket_port,
+            discovery::set_room,
+            discovery::start_discovery,
+            discovery::stop_discovery,
+            discovery::get_discovery_stats,
+            discovery::get_discovered_devices,
+            discovery::measure_discovery_latency,
+            discovery_http::start_discovery_http,
+            discovery_http::stop_discovery_http,
+            discovery::get_service_status,
             force_cleanup,
-            send_goodbye_message,
-            start_socket_server,
-            stop_socket_server,
-            get_socket_server_status
+            discovery::send_goodbye_message,
+            server::start_socket_server,
+            server::stop_socket_server,
+            server::get_socket_server_status,
+            server::get_socket_uptime,
+            get_health_summary,
+            protocol::set_text_protocol,
+            discovery::set_restore_last_session,
+            discovery::restore_last_session,
+            protocol::set_json_rpc,
+            protocol::set_strict_message_types,
+            screenshot::set_screenshot_enabled,
+            file_transfer::set_file_transfer_enabled,
+            file_transfer::set_max_file_size,
+            input::set_smooth_move,
+            input::set_cursor_sensitivity,
+            input::get_displays,
+            discovery::set_update_debounce,
+            input::set_held_key_check_interval,
+            input::push_clipboard_to_clients,
+            input::set_scroll_inertia,
+            input::test_input,
+            tls::get_tls_fingerprint,
+            server::set_status_interval,
+            input::set_system_shortcuts_enabled,
+            input::set_allow_system_commands,
+            input::set_input_overlay,
+            input::set_input_verification,
+            input::start_laser_pointer,
+            input::stop_laser_pointer,
+            input::set_activity_event_rate,
+            input::set_drag_conflict_policy,
+            server::set_reconnect_debounce,
+            server::set_idle_timeout,
+            server::list_sessions,
+            server::get_connected_clients,
+            server::disconnect_client,
+            server::set_command_rate_limit,
+            logging::set_log_level,
+            discovery::get_local_ips,
+            server::set_socket_buffer_size,
+            server::clear_session,
+            server::clear_all_sessions,
+            server::set_persist_sessions,
+            server::get_pairing_pin,
+            server::allow_ip,
+            server::revoke_ip,
+            server::list_allowed_ips,
+            server::regenerate_pin,
+            input::list_supported_keys,
+            input::set_input_retry,
+            discovery::set_discovery_retry,
+            discovery::set_max_cached_devices,
+            discovery::select_device,
+            get_runtime_worker_threads,
+            discovery::set_device_alias,
+            discovery::clear_device_alias,
+            set_runtime_worker_threads
         ])
-        .build(tauri::generate_context!())
+        .build(context)
         .expect("error while building tauri application");
 
     // Set up cleanup on app exit
     let app_handle = app.handle().clone();
     std::panic::set_hook(Box::new(move |_| {
-        println!("Panic detected - cleaning up mDNS services");
+        info!("Panic detected - cleaning up mDNS services");
         let state: State<MdnsState> = app_handle.state();
         cleanup(&state);
+        wait_for_goodbyes(&state);
     }));
 
     // Register signal handlers for graceful shutdown
@@ -813,9 +658,10 @@ fn main() {
 
         let app_handle_sigint = app_handle_arc.clone();
         ctrlc::set_handler(move || {
-            println!("Received SIGINT - cleaning up mDNS services");
+            info!("Received SIGINT - cleaning up mDNS services");
             let state: State<MdnsState> = app_handle_sigint.state();
             cleanup(&state);
+            wait_for_goodbyes(&state);
             std::process::exit(0);
         })
         .expect("Error setting Ctrl-C handler");
@@ -823,10 +669,260 @@ fn main() {
 
     app.run(|_app_handle, event| match event {
         tauri::RunEvent::ExitRequested { .. } => {
-            println!("Exit requested - cleaning up mDNS services");
+            info!("Exit requested - cleaning up mDNS services");
             let state: State<MdnsState> = _app_handle.state();
             cleanup(&state);
+            wait_for_goodbyes(&state);
         }
         _ => {}
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discovery::{cached_parse, parse_records, start_once, InFlight};
+    use crate::input::{list_supported_keys, parse_key, primary_modifier};
+    use crate::protocol::{send_reply, ReplyFraming, ReplySender, RPC_AUTH_REQUIRED};
+    use crate::server::bind_reusable_listener;
+    use searchlight::dns::rr::rdata::{SRV, TXT};
+    use searchlight::dns::rr::{Name, RData, Record};
+    use std::str::FromStr;
+    use tokio::net::TcpStream;
+
+    // The additionals a desktop announces, with `seq` standing in for a TXT value that
+    // changes every so often (a rename, a new room)
+    fn synthetic_additionals(instance: usize, seq: usize) -> Vec<Record> {
+        let owner = Name::from_str(&format!("Desk-{instance}._bruteconnect._tcp.local.")).unwrap();
+        let host = Name::from_str(&format!("desk-{instance}.local.")).unwrap();
+        vec![
+            Record::from_rdata(
+                owner.clone(),
+                120,
+                RData::SRV(SRV::new(0, 0, 7878, host.clone())),
+            ),
+            Record::from_rdata(
+                owner,
+                120,
+                RData::TXT(TXT::new(vec![
+                    "socketPort=7879".into(),
+                    format!("room=Room {seq}"),
+                    "version=0.1.0".into(),
+                    "proto=1".into(),
+                ])),
+            ),
+            Record::from_rdata(
+                host.clone(),
+                120,
+                RData::A(std::net::Ipv4Addr::new(192, 168, 1, instance as u8)),
+            ),
+            Record::from_rdata(
+                host,
+                120,
+                RData::AAAA(std::net::Ipv6Addr::new(
+                    0xfe80,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    instance as u16,
+                )),
+            ),
+        ]
+    }
+
+    #[test]
+    fn parse_cache_reparses_changed_records() {
+        let state = MdnsState::default();
+        let key = "Desk-1._bruteconnect._tcp.local".to_string();
+
+        let first = cached_parse(&state, key.clone(), &synthetic_additionals(1, 0));
+        let again = cached_parse(&state, key.clone(), &synthetic_additionals(1, 0));
+        assert!(std::sync::Arc::ptr_eq(&first, &again));
+
+        let changed = cached_parse(&state, key, &synthetic_additionals(1, 1));
+        assert!(changed.txt.contains(&"room=Room 1".to_string()));
+        assert_eq!(changed.addresses, ["192.168.1.1", "fe80::1"]);
+        assert_eq!(
+            state
+                .discovery_stats
+                .parse_cache_hits
+                .load(Ordering::Relaxed),
+            1
+        );
+    }
+
+    // A request's first reply is its result or error; anything after is a notification
+    #[test]
+    fn json_rpc_replies_answer_the_request_once() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let reply = ReplySender {
+            tx,
+            framing: ReplyFraming::RpcRequest {
+                id: Some(7.into()),
+                answered: std::sync::Arc::new(AtomicBool::new(false)),
+            },
+        };
+        let mut next =
+            || serde_json::from_str::<serde_json::Value>(&rx.try_recv().unwrap()).unwrap();
+
+        send_reply(
+            &reply,
+            serde_json::json!({ "type": "auth", "status": "error", "reason": "wrong pin" }),
+        );
+        let answer = next();
+        assert_eq!(answer["id"], 7);
+        assert_eq!(answer["error"]["code"], RPC_AUTH_REQUIRED);
+        assert_eq!(answer["error"]["message"], "wrong pin");
+
+        send_reply(
+            &reply,
+            serde_json::json!({ "type": "screenshot", "data": "" }),
+        );
+        let later = next();
+        assert_eq!(later["method"], "screenshot");
+        assert!(later.get("id").is_none());
+    }
+
+    #[test]
+    fn supported_keys_are_listed_and_parse() {
+        let keys = list_supported_keys();
+        assert!(!keys.is_empty());
+        for name in &keys {
+            assert!(parse_key(name).is_some(), "{name} doesn't parse");
+        }
+    }
+
+    // Many simultaneous starts: exactly one gets past the in-flight flag, and exactly one
+    // builds a handle into the slot
+    #[test]
+    fn concurrent_starts_build_one_handle() {
+        const STARTS: usize = 32;
+        let flag = AtomicBool::new(false);
+        let slot: Mutex<Option<usize>> = Mutex::new(None);
+        let claimed = AtomicU64::new(0);
+        let builds = AtomicU64::new(0);
+        let started = AtomicU64::new(0);
+        let barrier = std::sync::Barrier::new(STARTS);
+
+        std::thread::scope(|scope| {
+            for id in 0..STARTS {
+                let (flag, slot, barrier) = (&flag, &slot, &barrier);
+                let (claimed, builds, started) = (&claimed, &builds, &started);
+                scope.spawn(move || {
+                    barrier.wait();
+                    let claim = InFlight::claim(flag);
+                    if claim.is_some() {
+                        claimed.fetch_add(1, Ordering::SeqCst);
+                    }
+                    // Hold the claim until everyone else has tried theirs
+                    barrier.wait();
+                    drop(claim);
+
+                    let result = start_once(slot, || {
+                        builds.fetch_add(1, Ordering::SeqCst);
+                        std::thread::sleep(std::time::Duration::from_millis(5));
+                        Ok(id)
+                    });
+                    if result.unwrap() {
+                        started.fetch_add(1, Ordering::SeqCst);
+                    }
+                });
+            }
+        });
+
+        assert_eq!(claimed.load(Ordering::SeqCst), 1);
+        assert!(!flag.load(Ordering::SeqCst), "the claim outlived its guard");
+        assert_eq!(builds.load(Ordering::SeqCst), 1);
+        assert_eq!(started.load(Ordering::SeqCst), 1);
+        assert!(slot.lock().unwrap().is_some());
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn primary_modifier_is_command_on_macos() {
+        assert_eq!(primary_modifier(), Key::Meta);
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn primary_modifier_is_control_elsewhere() {
+        assert_eq!(primary_modifier(), Key::Control);
+    }
+
+    // A restart binds the same port again while the connection the old listener
+    // accepted is still in TIME_WAIT
+    #[test]
+    fn reusable_listener_rebinds_after_connection() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let listener = bind_reusable_listener("127.0.0.1:0".parse().unwrap()).unwrap();
+            let addr = listener.local_addr().unwrap();
+
+            let client = TcpStream::connect(addr).await.unwrap();
+            let (accepted, _) = listener.accept().await.unwrap();
+            // Closing from the server side leaves its end of the connection in TIME_WAIT
+            drop(accepted);
+            drop(listener);
+            drop(client);
+
+            let rebound = bind_reusable_listener(addr).unwrap();
+            assert_eq!(rebound.local_addr().unwrap(), addr);
+        });
+    }
+
+    // Replays a stream of re-announcements from a few desktops, with a record change
+    // every 50 responses, through the cache and through a plain parse, and checks the
+    // cache wins. cargo test --release parse_cache_benchmark -- --nocapture prints timings.
+    #[test]
+    fn parse_cache_benchmark() {
+        const INSTANCES: usize = 20;
+        const RESPONSES: usize = 50_000;
+
+        let stream: Vec<(String, Vec<Record>)> = (0..RESPONSES)
+            .map(|i| {
+                let instance = i % INSTANCES;
+                (
+                    format!("Desk-{instance}._bruteconnect._tcp.local"),
+                    synthetic_additionals(instance, i / (INSTANCES * 50)),
+                )
+            })
+            .collect();
+
+        let started = std::time::Instant::now();
+        for (_, records) in &stream {
+            // Without the cache there's nothing to compare a hash against
+            std::hint::black_box(parse_records(records, 0));
+        }
+        let uncached = started.elapsed();
+
+        let state = MdnsState::default();
+        let started = std::time::Instant::now();
+        for (key, records) in &stream {
+            std::hint::black_box(cached_parse(&state, key.clone(), records));
+        }
+        let cached = started.elapsed();
+
+        let hits = state
+            .discovery_stats
+            .parse_cache_hits
+            .load(Ordering::Relaxed);
+        println!(
+            "{RESPONSES} responses: uncached {:?} ({:?}/response), cached {:?} ({:?}/response), {hits} hits",
+            uncached,
+            uncached / RESPONSES as u32,
+            cached,
+            cached / RESPONSES as u32,
+        );
+        assert!(hits as usize > RESPONSES * 9 / 10);
+        assert!(
+            cached < uncached,
+            "the cache made parsing slower ({cached:?} vs {uncached:?})"
+        );
+    }
+}
@@ -0,0 +1,896 @@
+//! Parsing, framing and dispatch of client messages, and the replies sent back.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use log::{debug, error, info, warn};
+use serde::Deserialize;
+use tauri::{Emitter, Manager, State};
+
+use crate::file_transfer::handle_file_command;
+use crate::input::{
+    handle_clipboard_command, handle_cursor_command, handle_drag_action, handle_key_hold_action,
+    handle_keyboard_command, handle_media_command, handle_power_action,
+    handle_presentation_command, handle_scroll_action, handle_system_command, is_power_action,
+    queue_clipboard_get, queue_input, queue_move_path,
+};
+use crate::screenshot::handle_screenshot_command;
+use crate::server::{
+    handle_auth_command, handle_session_command, record_client_command, reject_unauthenticated,
+    ClientConnection,
+};
+use crate::{noop_input, unix_millis, LockExt, MdnsState};
+
+// Where messages for a client go. On a JSON-RPC connection each request gets its own
+// sender (see "JSON-RPC"), which turns the first message a handler sends into that
+// request's result or error; anything else the desktop sends becomes a notification.
+#[derive(Clone)]
+pub(crate) struct ReplySender {
+    pub(crate) tx: tokio::sync::mpsc::UnboundedSender<String>,
+    pub(crate) framing: ReplyFraming,
+}
+
+#[derive(Clone)]
+pub(crate) enum ReplyFraming {
+    Native,
+    RpcNotification,
+    // `id` is None for a client notification, whose answer is dropped
+    RpcRequest {
+        id: Option<serde_json::Value>,
+        answered: std::sync::Arc<AtomicBool>,
+    },
+}
+
+impl ReplySender {
+    fn send_line(&self, line: String) {
+        if self.tx.send(line).is_err() {
+            warn!("Client connection closed before reply could be sent");
+        }
+    }
+}
+
+// Queue a newline-terminated JSON message for the client
+pub(crate) fn send_reply(reply: &ReplySender, message: serde_json::Value) {
+    let message = match &reply.framing {
+        ReplyFraming::Native => message,
+        // Already a JSON-RPC response, e.g. a protocol error
+        _ if message.get("jsonrpc").is_some() => message,
+        ReplyFraming::RpcRequest { id, answered } if !answered.swap(true, Ordering::SeqCst) => {
+            match id {
+                Some(id) => rpc_response(id.clone(), message),
+                None => return,
+            }
+        }
+        _ => rpc_notification(message),
+    };
+    reply.send_line(format!("{}\n", message));
+}
+
+// ---- Incoming commands ----
+// Every JSON command is parsed into an IncomingCommand before it is dispatched, so a
+// missing or mistyped "type"/"action" gets one error ack instead of being dropped.
+// Both envelopes parse the same way: the direct `{"type":...}` form and the mobile
+// app's `{"data":"<stringified json>"}`. A new command type needs a variant here, an
+// arm in `route` and an arm in `dispatch_command`.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum IncomingCommand {
+    Presentation(CommandArgs),
+    Cursor(CommandArgs),
+    Keyboard(CommandArgs),
+    Media(CommandArgs),
+    Clipboard(CommandArgs),
+    System(CommandArgs),
+    #[serde(alias = "screen")]
+    Screenshot(CommandArgs),
+    File(CommandArgs),
+    Session(CommandArgs),
+    Auth(serde_json::Map<String, serde_json::Value>),
+    Ping(serde_json::Map<String, serde_json::Value>),
+}
+
+// The action plus whatever fields the handler reads itself
+#[derive(Deserialize, Debug)]
+struct CommandArgs {
+    action: String,
+    #[serde(flatten)]
+    fields: serde_json::Map<String, serde_json::Value>,
+}
+
+impl IncomingCommand {
+    // The type and action `dispatch_command` routes by. Auth and ping have no action.
+    fn route(&self) -> (&'static str, &str) {
+        match self {
+            IncomingCommand::Presentation(args) => ("presentation", &args.action),
+            IncomingCommand::Cursor(args) => ("cursor", &args.action),
+            IncomingCommand::Keyboard(args) => ("keyboard", &args.action),
+            IncomingCommand::Media(args) => ("media", &args.action),
+            IncomingCommand::Clipboard(args) => ("clipboard", &args.action),
+            IncomingCommand::System(args) => ("system", &args.action),
+            IncomingCommand::Screenshot(args) => ("screenshot", &args.action),
+            IncomingCommand::File(args) => ("file", &args.action),
+            IncomingCommand::Session(args) => ("session", &args.action),
+            IncomingCommand::Auth(_) => ("auth", ""),
+            IncomingCommand::Ping(_) => ("ping", ""),
+        }
+    }
+
+    // The command as the flat JSON object handlers read their fields from
+    fn to_json(&self) -> serde_json::Value {
+        let (msg_type, action) = self.route();
+        let fields = match self {
+            IncomingCommand::Auth(fields) | IncomingCommand::Ping(fields) => fields,
+            IncomingCommand::Presentation(args)
+            | IncomingCommand::Cursor(args)
+            | IncomingCommand::Keyboard(args)
+            | IncomingCommand::Media(args)
+            | IncomingCommand::Clipboard(args)
+            | IncomingCommand::System(args)
+            | IncomingCommand::Screenshot(args)
+            | IncomingCommand::File(args)
+            | IncomingCommand::Session(args) => &args.fields,
+        };
+        let mut json = fields.clone();
+        json.insert("type".into(), msg_type.into());
+        if !action.is_empty() {
+            json.insert("action".into(), action.into());
+        }
+        serde_json::Value::Object(json)
+    }
+}
+
+// Unwrap `{"data":"<stringified json>"}` envelopes, however deeply nested. A message
+// with its own "type" is never an envelope, so file chunks keep their "data".
+fn unwrap_envelope(value: serde_json::Value) -> Result<serde_json::Value, String> {
+    if value.get("type").is_none() {
+        if let Some(data) = value.get("data").and_then(|v| v.as_str()) {
+            let inner = serde_json::from_str(data)
+                .map_err(|e| format!("\"data\" is not valid JSON: {e}"))?;
+            return unwrap_envelope(inner);
+        }
+    }
+    Ok(value)
+}
+
+fn parse_command(raw: &str) -> Result<IncomingCommand, String> {
+    let value = serde_json::from_str(raw).map_err(|e| format!("not valid JSON: {e}"))?;
+    IncomingCommand::deserialize(unwrap_envelope(value)?).map_err(|e| e.to_string())
+}
+
+fn reject_invalid_command(app: &tauri::AppHandle, conn: &ClientConnection, raw: &str, error: &str) {
+    // Best effort: the type and action the client meant, for the ack
+    let json_value = serde_json::from_str(raw)
+        .ok()
+        .and_then(|value| unwrap_envelope(value).ok())
+        .unwrap_or_default();
+    let msg_type = json_value.get("type").and_then(|v| v.as_str());
+    // An unpaired client learns nothing about what it sent until it has paired
+    if msg_type != Some("auth") && !conn.authenticated.load(Ordering::Relaxed) {
+        reject_unauthenticated(conn, msg_type.unwrap_or("invalid"));
+        return;
+    }
+    // Unknown types keep their lenient/strict handling
+    if let Some(msg_type) = msg_type.filter(|t| !is_known_message_type(t)) {
+        handle_unknown_message_type(app, conn, msg_type);
+        return;
+    }
+
+    warn!("Invalid command from {}: {}", conn.addr, error);
+    let action = json_value
+        .get("action")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default();
+    send_ack(
+        conn,
+        msg_type.unwrap_or("unknown"),
+        action,
+        &Err(format!("invalid command: {error}")),
+    );
+}
+
+// Route a parsed command to the matching handler
+fn dispatch_command(
+    app: &tauri::AppHandle,
+    conn: &ClientConnection,
+    msg_type: &str,
+    action: &str,
+    json_data: &serde_json::Value,
+) {
+    record_client_command(app, conn.addr);
+    if msg_type != "auth" && !conn.authenticated.load(Ordering::Relaxed) {
+        reject_unauthenticated(conn, msg_type);
+        return;
+    }
+    // Latency probes skip the input backend so they measure the link, not the backlog
+    if msg_type == "ping" {
+        send_reply(&conn.reply(), pong_message(json_data));
+        return;
+    }
+    if matches!(msg_type, "cursor" | "presentation" | "keyboard") {
+        emit_input_activity(app, conn, msg_type, action);
+    }
+    app.state::<MdnsState>()
+        .commands_processed
+        .fetch_add(1, Ordering::Relaxed);
+
+    match msg_type {
+        "auth" => handle_auth_command(app, conn, json_data),
+        "cursor" if action == "move_path" => queue_move_path(app, conn, json_data),
+        "cursor" if matches!(action, "drag_start" | "drag_end" | "release_all") => {
+            handle_drag_action(app, conn, action, json_data)
+        }
+        "clipboard" if action == "get" => queue_clipboard_get(app, conn),
+        "cursor" if action == "scroll" => handle_scroll_action(app, conn, json_data),
+        "keyboard" if matches!(action, "key_down" | "key_up" | "release_all_keys") => {
+            handle_key_hold_action(app, conn, action, json_data)
+        }
+        "system" if is_power_action(action) => handle_power_action(app, conn, action),
+        "system"
+            if !*app
+                .state::<MdnsState>()
+                .system_shortcuts_enabled
+                .lock_or_recover() =>
+        {
+            send_ack(
+                conn,
+                msg_type,
+                action,
+                &Err("system shortcuts are disabled".into()),
+            )
+        }
+        "presentation" | "cursor" | "keyboard" | "media" | "clipboard" | "system" => {
+            queue_input_command(app, ack_sender(conn), msg_type, action, json_data)
+        }
+        // "screen" is an alias; replies are tagged "screenshot" either way
+        "screenshot" | "screen" => handle_screenshot_command(app, conn, action, json_data),
+        "file" => handle_file_command(app, conn, action, json_data),
+        "session" => handle_session_command(app, conn, action, json_data),
+        _ => handle_unknown_message_type(app, conn, msg_type),
+    }
+}
+
+// "input:activity" tells the UI a phone is sending input. Coalesced per connection to
+// one event every INPUT_ACTIVITY_INTERVAL so a drag doesn't flood the frontend.
+const INPUT_ACTIVITY_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+fn emit_input_activity(
+    app: &tauri::AppHandle,
+    conn: &ClientConnection,
+    msg_type: &str,
+    action: &str,
+) {
+    {
+        let mut last = conn.last_activity_event.lock_or_recover();
+        let now = std::time::Instant::now();
+        if last.is_some_and(|at| now.duration_since(at) < INPUT_ACTIVITY_INTERVAL) {
+            return;
+        }
+        *last = Some(now);
+    }
+
+    let _ = app.emit(
+        "input:activity",
+        serde_json::json!({
+            "addr": conn.addr.to_string(),
+            "type": msg_type,
+            "action": action,
+            "timestamp_ms": unix_millis()
+        }),
+    );
+}
+
+// {"type":"ping","id":123,"t":<client timestamp>} is answered right away with the same
+// id and t, so the client can work out the round trip from its own clock
+fn pong_message(ping: &serde_json::Value) -> serde_json::Value {
+    serde_json::json!({
+        "type": "pong",
+        "id": ping.get("id").cloned().unwrap_or(serde_json::Value::Null),
+        "t": ping.get("t").cloned().unwrap_or(serde_json::Value::Null)
+    })
+}
+
+// Queue an input command; `ack` receives the outcome once the worker has run it
+pub(crate) fn queue_input_command(
+    app: &tauri::AppHandle,
+    ack: Option<ReplySender>,
+    msg_type: &str,
+    action: &str,
+    json_data: &serde_json::Value,
+) {
+    let app_for_job = app.clone();
+    let msg_type = msg_type.to_string();
+    let action = action.to_string();
+    let json_data = json_data.clone();
+    queue_input(
+        app,
+        Box::new(move || {
+            let result = run_input_command(&app_for_job, &msg_type, &action, &json_data);
+            finish_input_command(&app_for_job, ack, &msg_type, &action, &result);
+        }),
+    );
+}
+
+// ---- Acknowledgements ----
+// Every input command gets one newline-terminated reply once it has run (or failed to):
+//   {"ok":true,"type":"cursor","action":"left_click"}
+//   {"ok":false,"type":"cursor","action":"left_click","error":"..."}
+pub(crate) fn ack_message(
+    msg_type: &str,
+    action: &str,
+    result: &Result<(), String>,
+) -> serde_json::Value {
+    match result {
+        Ok(()) => serde_json::json!({ "ok": true, "type": msg_type, "action": action }),
+        Err(e) => serde_json::json!({
+            "ok": false,
+            "type": msg_type,
+            "action": action,
+            "error": e
+        }),
+    }
+}
+
+// Runs on the input worker once a command has been executed. Failures also go to the
+// UI as "input:error", since stderr is invisible to most users and a missing
+// permission (Wayland, macOS accessibility) otherwise looks like the app doing nothing.
+pub(crate) fn finish_input_command(
+    app: &tauri::AppHandle,
+    ack: Option<ReplySender>,
+    msg_type: &str,
+    action: &str,
+    result: &Result<(), String>,
+) {
+    let state: State<MdnsState> = app.state();
+    match result {
+        Ok(()) => state
+            .input_permission_checked
+            .store(true, Ordering::Relaxed),
+        Err(e) => {
+            error!("{}", e);
+            let _ = app.emit(
+                "input:error",
+                serde_json::json!({ "type": msg_type, "action": action, "error": e }),
+            );
+            // Only a failure before anything has worked points at missing OS access
+            if is_permission_error(e)
+                && !state.input_permission_checked.swap(true, Ordering::Relaxed)
+            {
+                warn!("Input appears to be blocked by the OS: {}", e);
+                let _ = app.emit(
+                    "input:permission-denied",
+                    serde_json::json!({ "error": e, "platform": std::env::consts::OS }),
+                );
+            }
+        }
+    }
+    if let Some(reply) = ack {
+        send_reply(&reply, ack_message(msg_type, action, result));
+    }
+}
+
+// Enigo has no dedicated error for missing access, so go by its messages: macOS can't
+// create events without Accessibility permission, and Windows blocks input to elevated
+// windows (UIPI)
+pub(crate) fn is_permission_error(error: &str) -> bool {
+    let error = error.to_lowercase();
+    [
+        "failed creating event",
+        "uipi",
+        "permission",
+        "not permitted",
+        "access denied",
+    ]
+    .iter()
+    .any(|marker| error.contains(marker))
+}
+
+pub(crate) fn ack_sender(conn: &ClientConnection) -> Option<ReplySender> {
+    Some(conn.reply())
+}
+
+pub(crate) fn send_ack(
+    conn: &ClientConnection,
+    msg_type: &str,
+    action: &str,
+    result: &Result<(), String>,
+) {
+    send_reply(&conn.reply(), ack_message(msg_type, action, result));
+}
+
+// Message types `dispatch_command` routes; keep in sync with IncomingCommand
+fn is_known_message_type(msg_type: &str) -> bool {
+    matches!(
+        msg_type,
+        "presentation"
+            | "cursor"
+            | "keyboard"
+            | "media"
+            | "clipboard"
+            | "system"
+            | "screenshot"
+            | "screen"
+            | "file"
+            | "session"
+            | "auth"
+            | "ping"
+    )
+}
+
+// Lenient mode (the default) only logs unknown types so older desktops keep working
+// with newer clients; strict mode tells the client so typos surface during development.
+fn handle_unknown_message_type(app: &tauri::AppHandle, conn: &ClientConnection, msg_type: &str) {
+    warn!("Unknown message type: {}", msg_type);
+
+    let state: State<MdnsState> = app.state();
+    conn.unknown_messages.fetch_add(1, Ordering::Relaxed);
+    state.unknown_message_count.fetch_add(1, Ordering::Relaxed);
+
+    let strict = *state.strict_message_types.lock_or_recover();
+    if strict {
+        send_reply(
+            &conn.reply(),
+            serde_json::json!({
+                "status": "error",
+                "reason": format!("unknown type {}", msg_type)
+            }),
+        );
+    }
+}
+
+// Runs on the input worker thread
+fn run_input_command(
+    app: &tauri::AppHandle,
+    msg_type: &str,
+    action: &str,
+    json_data: &serde_json::Value,
+) -> Result<(), String> {
+    if noop_input() {
+        debug!("No-op input: {} {} {}", msg_type, action, json_data);
+        return Ok(());
+    }
+
+    match msg_type {
+        "presentation" => handle_presentation_command(app, action, json_data),
+        "cursor" => handle_cursor_command(app, action, json_data),
+        "keyboard" => handle_keyboard_command(app, action, json_data),
+        "media" => handle_media_command(app, action),
+        "clipboard" => handle_clipboard_command(app, action, json_data),
+        "system" => handle_system_command(app, action),
+        _ => Err(format!("Unknown input message type: {}", msg_type)),
+    }
+}
+
+// Plain-text line protocol (opt-in via `set_text_protocol`), handy for netcat testing.
+// One command per line, whitespace separated:
+//
+//   cursor move <dx> <dy>          e.g. "cursor move 10 -5"
+//   cursor scroll <up|down> <n>    e.g. "cursor scroll down 3"
+//   cursor left_click | right_click
+//   presentation left | right
+//
+// Lines are converted into the same JSON shape the mobile app sends and dispatched
+// through `dispatch_command`. Malformed lines are answered with "error: <reason>".
+fn parse_text_command(line: &str) -> Result<serde_json::Value, String> {
+    let mut parts = line.split_whitespace();
+    let msg_type = parts.next().ok_or("empty command")?;
+    if msg_type == "auth" {
+        let pin = parts.next().ok_or("usage: auth <pin>")?;
+        return Ok(serde_json::json!({ "type": "auth", "pin": pin }));
+    }
+    let action = parts
+        .next()
+        .ok_or_else(|| format!("missing action after '{}'", msg_type))?;
+    let args: Vec<&str> = parts.collect();
+
+    let mut command = serde_json::json!({ "type": msg_type, "action": action });
+
+    match (msg_type, action) {
+        ("cursor", "move") => {
+            let [dx, dy] = args[..] else {
+                return Err("usage: cursor move <dx> <dy>".into());
+            };
+            let dx: i64 = dx.parse().map_err(|_| format!("invalid deltaX '{}'", dx))?;
+            let dy: i64 = dy.parse().map_err(|_| format!("invalid deltaY '{}'", dy))?;
+            command["deltaX"] = dx.into();
+            command["deltaY"] = dy.into();
+        }
+        ("cursor", "scroll") => {
+            let [direction, delta] = args[..] else {
+                return Err("usage: cursor scroll <up|down> <delta>".into());
+            };
+            if direction != "up" && direction != "down" {
+                return Err(format!("invalid scroll direction '{}'", direction));
+            }
+            let delta: i64 = delta
+                .parse()
+                .map_err(|_| format!("invalid scroll delta '{}'", delta))?;
+            command["direction"] = direction.into();
+            command["delta"] = delta.into();
+        }
+        _ if !args.is_empty() => {
+            return Err(format!("'{} {}' does not take arguments", msg_type, action));
+        }
+        _ => {}
+    }
+
+    Ok(command)
+}
+
+// ---- JSON-RPC ----
+// Optional JSON-RPC 2.0 dialect (enabled with `set_json_rpc`, applies to new
+// connections): one request per line, `method` is the message type and `params` holds
+// the remaining fields, e.g.
+//   {"jsonrpc":"2.0","method":"cursor","params":{"action":"move","deltaX":5,"deltaY":0},"id":1}
+// The first message the handlers send for a request (an ack once the input ran, a
+// screenshot, a clipboard read) becomes its result, or an error object when it reports
+// "status":"error" or "ok":false. A request whose handler sends nothing gets
+// {"dispatched":true}. Requests without an id are notifications and get no reply.
+// Anything else the desktop sends (stream frames, clipboard pushes) goes out as a
+// notification whose method is the message's type.
+const RPC_PARSE_ERROR: i64 = -32700;
+const RPC_INVALID_REQUEST: i64 = -32600;
+const RPC_METHOD_NOT_FOUND: i64 = -32601;
+const RPC_INVALID_PARAMS: i64 = -32602;
+// Implementation-defined server error: the connection hasn't sent the pairing PIN yet
+pub(crate) const RPC_AUTH_REQUIRED: i64 = -32001;
+// Dropped by the per-connection rate limiter
+pub(crate) const RPC_RATE_LIMITED: i64 = -32002;
+// The handler ran and reported a failure; `data` carries its full reply
+const RPC_COMMAND_FAILED: i64 = -32003;
+
+pub(crate) fn rpc_error(id: serde_json::Value, code: i64, message: &str) -> serde_json::Value {
+    serde_json::json!({
+        "jsonrpc": "2.0",
+        "error": { "code": code, "message": message },
+        "id": id
+    })
+}
+
+// Wrap a handler's reply as the response to request `id`
+fn rpc_response(id: serde_json::Value, message: serde_json::Value) -> serde_json::Value {
+    let failed = message.get("status").and_then(|v| v.as_str()) == Some("error")
+        || message.get("ok").and_then(|v| v.as_bool()) == Some(false);
+    if !failed {
+        return serde_json::json!({ "jsonrpc": "2.0", "result": message, "id": id });
+    }
+
+    let reason = message
+        .get("reason")
+        .or_else(|| message.get("error"))
+        .and_then(|v| v.as_str())
+        .unwrap_or("command failed");
+    let code = match (message.get("type").and_then(|v| v.as_str()), reason) {
+        (Some("auth"), _) => RPC_AUTH_REQUIRED,
+        (_, "rate limited") => RPC_RATE_LIMITED,
+        _ => RPC_COMMAND_FAILED,
+    };
+    serde_json::json!({
+        "jsonrpc": "2.0",
+        "error": { "code": code, "message": reason, "data": message },
+        "id": id
+    })
+}
+
+fn rpc_notification(message: serde_json::Value) -> serde_json::Value {
+    let method = message
+        .get("type")
+        .and_then(|v| v.as_str())
+        .unwrap_or("message")
+        .to_string();
+    serde_json::json!({ "jsonrpc": "2.0", "method": method, "params": message })
+}
+
+pub(crate) fn handle_json_rpc_line(app: &tauri::AppHandle, conn: &ClientConnection, line: &str) {
+    let request = match serde_json::from_str::<serde_json::Value>(line) {
+        Ok(request) => request,
+        Err(e) => {
+            warn!("Rejected JSON-RPC line from {}: {}", conn.addr, e);
+            send_reply(
+                &conn.reply(),
+                rpc_error(serde_json::Value::Null, RPC_PARSE_ERROR, "Parse error"),
+            );
+            return;
+        }
+    };
+
+    let id = request.get("id").cloned();
+    let reply = |message: serde_json::Value| {
+        if id.is_some() {
+            send_reply(&conn.reply(), message);
+        }
+    };
+    let reply_id = id.clone().unwrap_or(serde_json::Value::Null);
+
+    if request.get("jsonrpc").and_then(|v| v.as_str()) != Some("2.0") {
+        // Invalid requests are answered even without an id, as the spec requires
+        send_reply(
+            &conn.reply(),
+            rpc_error(reply_id, RPC_INVALID_REQUEST, "jsonrpc must be \"2.0\""),
+        );
+        return;
+    }
+    let Some(method) = request.get("method").and_then(|v| v.as_str()) else {
+        send_reply(
+            &conn.reply(),
+            rpc_error(reply_id, RPC_INVALID_REQUEST, "missing method"),
+        );
+        return;
+    };
+
+    if !is_known_message_type(method) {
+        reply(rpc_error(
+            reply_id,
+            RPC_METHOD_NOT_FOUND,
+            "Method not found",
+        ));
+        return;
+    }
+
+    let mut params = match request.get("params") {
+        Some(serde_json::Value::Object(params)) => params.clone(),
+        None => serde_json::Map::new(),
+        Some(_) => {
+            reply(rpc_error(
+                reply_id,
+                RPC_INVALID_PARAMS,
+                "params must be an object",
+            ));
+            return;
+        }
+    };
+    let Some(action) = params
+        .get("action")
+        .and_then(|v| v.as_str())
+        .map(str::to_string)
+        .or_else(|| matches!(method, "auth" | "ping").then(String::new))
+    else {
+        reply(rpc_error(
+            reply_id,
+            RPC_INVALID_PARAMS,
+            "missing params.action",
+        ));
+        return;
+    };
+
+    if method != "auth" && !conn.authenticated.load(Ordering::Relaxed) {
+        reply(rpc_error(reply_id, RPC_AUTH_REQUIRED, "auth required"));
+        return;
+    }
+    if method == "ping" {
+        reply(serde_json::json!({
+            "jsonrpc": "2.0",
+            "result": pong_message(&serde_json::Value::Object(params)),
+            "id": reply_id
+        }));
+        return;
+    }
+
+    params.insert("type".into(), method.into());
+    let command = serde_json::Value::Object(params);
+    let answered = std::sync::Arc::new(AtomicBool::new(false));
+    *conn.request_reply.lock_or_recover() = Some(ReplySender {
+        tx: conn.push_reply.tx.clone(),
+        framing: ReplyFraming::RpcRequest {
+            id: id.clone(),
+            answered: answered.clone(),
+        },
+    });
+    dispatch_command(app, conn, method, &action, &command);
+    conn.request_reply.lock_or_recover().take();
+
+    // Nothing answered, and no queued job kept the sender to answer later
+    if std::sync::Arc::strong_count(&answered) == 1 && !answered.load(Ordering::SeqCst) {
+        reply(serde_json::json!({
+            "jsonrpc": "2.0",
+            "result": { "dispatched": true },
+            "id": reply_id
+        }));
+    }
+}
+
+#[tauri::command]
+pub(crate) fn set_json_rpc(state: State<MdnsState>, enabled: bool) -> Result<(), String> {
+    *state.json_rpc.lock_or_recover() = enabled;
+    info!(
+        "JSON-RPC protocol {} for new connections",
+        if enabled { "enabled" } else { "disabled" }
+    );
+    Ok(())
+}
+
+// ---- Framing ----
+// Every protocol on the socket is newline-delimited: each command is one line of UTF-8
+// terminated by "\n" (a trailing "\r" is ignored). Reads are buffered until a full
+// line arrives, so a command may span several TCP segments and several commands may
+// share one. A line longer than MAX_MESSAGE_LEN is dropped up to its "\n" and answered
+// with an error.
+// For backward compatibility, complete JSON values at the front of an unterminated
+// remainder are accepted too, so clients that write objects back to back without
+// newlines keep working.
+pub(crate) const MAX_MESSAGE_LEN: usize = 64 * 1024;
+
+// Advertised as the "proto" TXT record next to "version" (the app version) so clients
+// can spot an incompatible desktop before connecting. Bump it whenever the command
+// envelope changes.
+pub(crate) const PROTOCOL_VERSION: u32 = 1;
+
+// Auth messages carry the pairing PIN, which must stay out of the log (and so out of
+// the UI's log view, which "log:line" feeds). Covers the JSON, nested-envelope,
+// JSON-RPC and text forms.
+pub(crate) fn loggable_message(message: &str) -> &str {
+    let lower = message.trim_start().to_ascii_lowercase();
+    if lower.starts_with("auth") || lower.contains("\"pin\"") || lower.contains("\\\"pin\\\"") {
+        "<auth message redacted>"
+    } else {
+        message
+    }
+}
+
+// One native-protocol message: JSON (direct or nested in "data") or, when enabled, a
+// plain-text command line
+pub(crate) fn handle_message(app: &tauri::AppHandle, conn: &ClientConnection, message: &str) {
+    debug!("Received from {}: {}", conn.addr, loggable_message(message));
+
+    // Text commands never start with "{", so anything else is left to the JSON parser
+    let text_protocol = *app.state::<MdnsState>().text_protocol.lock_or_recover();
+    if !text_protocol || message.trim_start().starts_with('{') {
+        match parse_command(message) {
+            Ok(command) => {
+                let (msg_type, action) = command.route();
+                dispatch_command(app, conn, msg_type, action, &command.to_json());
+            }
+            Err(e) => reject_invalid_command(app, conn, message, &e),
+        }
+        return;
+    }
+
+    match parse_text_command(message) {
+        Ok(command) => {
+            let msg_type = command["type"].as_str().unwrap_or_default();
+            let action = command["action"].as_str().unwrap_or_default();
+            dispatch_command(app, conn, msg_type, action, &command);
+        }
+        Err(_) if !conn.authenticated.load(Ordering::Relaxed) => {
+            reject_unauthenticated(conn, "text")
+        }
+        Err(e) => {
+            warn!("Rejected text command from {}: {}", conn.addr, e);
+            conn.reply().send_line(format!("error: {}\n", e));
+        }
+    }
+}
+
+// ---- MessagePack ----
+// Clients that send many small commands (cursor drags) can skip JSON by opening the
+// connection with {"type":"hello","encoding":"msgpack"}. Only the very first message
+// can do this; the desktop answers with a JSON hello line and from then on reads
+// frames of a 4-byte big-endian length followed by one MessagePack-encoded command,
+// with the same fields as the JSON form. Replies stay newline-delimited JSON. A frame
+// longer than MAX_MESSAGE_LEN can't be skipped reliably, so it closes the connection.
+// Services advertise "msgpack=1".
+pub(crate) enum Hello {
+    Json,
+    MsgPack,
+}
+
+// Some(..) when `line` is a hello; an unsupported encoding is answered here
+pub(crate) fn parse_hello(conn: &ClientConnection, line: &str) -> Option<Hello> {
+    let value: serde_json::Value = serde_json::from_str(line).ok()?;
+    if value.get("type").and_then(|v| v.as_str()) != Some("hello") {
+        return None;
+    }
+    let hello = match value.get("encoding").and_then(|v| v.as_str()) {
+        None | Some("json") => Hello::Json,
+        Some("msgpack") => Hello::MsgPack,
+        Some(other) => {
+            send_reply(
+                &conn.reply(),
+                serde_json::json!({
+                    "type": "hello",
+                    "status": "error",
+                    "reason": format!("unsupported encoding {}", other)
+                }),
+            );
+            return Some(Hello::Json);
+        }
+    };
+    let encoding = match hello {
+        Hello::Json => "json",
+        Hello::MsgPack => "msgpack",
+    };
+    info!("{} negotiated {} encoding", conn.addr, encoding);
+    send_reply(
+        &conn.reply(),
+        serde_json::json!({ "type": "hello", "encoding": encoding, "proto": PROTOCOL_VERSION }),
+    );
+    Some(hello)
+}
+
+// Complete frames off the front of `pending`; Err(length) for an oversized frame
+pub(crate) fn take_msgpack_frames(pending: &mut Vec<u8>) -> Result<Vec<Vec<u8>>, usize> {
+    let mut frames = Vec::new();
+    while let Some(header) = pending.first_chunk::<4>() {
+        let len = u32::from_be_bytes(*header) as usize;
+        if len > MAX_MESSAGE_LEN {
+            return Err(len);
+        }
+        if pending.len() < 4 + len {
+            break;
+        }
+        frames.push(pending[4..4 + len].to_vec());
+        pending.drain(..4 + len);
+    }
+    Ok(frames)
+}
+
+pub(crate) fn handle_msgpack_message(
+    app: &tauri::AppHandle,
+    conn: &ClientConnection,
+    frame: &[u8],
+) {
+    match rmp_serde::from_slice::<IncomingCommand>(frame) {
+        Ok(command) => {
+            let (msg_type, action) = command.route();
+            dispatch_command(app, conn, msg_type, action, &command.to_json());
+        }
+        Err(e) => {
+            // Decoded loosely as well, so the error ack can name the type and action
+            let raw = rmp_serde::from_slice::<serde_json::Value>(frame)
+                .map(|value| value.to_string())
+                .unwrap_or_default();
+            reject_invalid_command(app, conn, &raw, &e.to_string());
+        }
+    }
+}
+
+// Pull every complete JSON value off the front of an unterminated buffer, leaving an
+// incomplete tail (or anything that isn't JSON, like a partial text command) in place
+pub(crate) fn take_unterminated_json(pending: &mut Vec<u8>) -> Vec<serde_json::Value> {
+    let mut stream = serde_json::Deserializer::from_slice(pending).into_iter::<serde_json::Value>();
+    let mut values = Vec::new();
+    let mut consumed = 0;
+    while let Some(Ok(value)) = stream.next() {
+        values.push(value);
+        consumed = stream.byte_offset();
+    }
+    pending.drain(..consumed);
+    values
+}
+
+pub(crate) fn message_too_long_reply(json_rpc: bool) -> serde_json::Value {
+    if json_rpc {
+        rpc_error(
+            serde_json::Value::Null,
+            RPC_INVALID_REQUEST,
+            "line too long",
+        )
+    } else {
+        serde_json::json!({ "status": "error", "reason": "message too long" })
+    }
+}
+
+#[tauri::command]
+pub(crate) fn set_strict_message_types(
+    state: State<MdnsState>,
+    strict: bool,
+) -> Result<(), String> {
+    *state.strict_message_types.lock_or_recover() = strict;
+    warn!(
+        "Unknown message types are now {}",
+        if strict {
+            "reported as errors"
+        } else {
+            "ignored"
+        }
+    );
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn set_text_protocol(state: State<MdnsState>, enabled: bool) -> Result<(), String> {
+    *state.text_protocol.lock_or_recover() = enabled;
+    info!(
+        "Plain-text command protocol {}",
+        if enabled { "enabled" } else { "disabled" }
+    );
+    Ok(())
+}
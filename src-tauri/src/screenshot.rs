@@ -0,0 +1,399 @@
+//! Screen capture and low frame rate streaming for clients.
+
+use log::{debug, error, info, warn};
+use tauri::{Emitter, Manager, State};
+
+use crate::protocol::{is_permission_error, send_reply};
+use crate::server::ClientConnection;
+use crate::{LockExt, MdnsState};
+
+// ---- Screenshots ----
+// Screen capture is privacy-sensitive, so it stays disabled until the user turns it
+// on with `set_screenshot_enabled`, and each connection's captures are rate limited.
+const SCREENSHOT_MIN_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+const DEFAULT_SCREENSHOT_QUALITY: u8 = 70;
+const MIN_SCREENSHOT_SCALE: f64 = 0.05;
+
+pub(crate) fn handle_screenshot_command(
+    app: &tauri::AppHandle,
+    conn: &ClientConnection,
+    action: &str,
+    json_data: &serde_json::Value,
+) {
+    debug!("Handling screenshot command: {}", action);
+
+    match action {
+        "capture" => capture_screenshot_for(app, conn, json_data),
+        "stream" => start_screen_stream(app, conn, json_data),
+        "stop" | "screenshot_stop" => stop_screen_stream(conn),
+        _ => {
+            warn!("Unknown screenshot action: {}", action);
+            send_reply(
+                &conn.reply(),
+                screenshot_error(&format!("unknown screenshot action '{}'", action)),
+            );
+        }
+    }
+}
+
+fn screenshots_enabled(app: &tauri::AppHandle) -> bool {
+    let state: State<MdnsState> = app.state();
+    let enabled = *state.screenshot_enabled.lock_or_recover();
+    enabled
+}
+
+fn requested_quality(json_data: &serde_json::Value) -> u8 {
+    json_data
+        .get("quality")
+        .and_then(|v| v.as_u64())
+        .map(|q| q.clamp(1, 100) as u8)
+        .unwrap_or(DEFAULT_SCREENSHOT_QUALITY)
+}
+
+fn requested_format(json_data: &serde_json::Value) -> String {
+    json_data
+        .get("format")
+        .and_then(|v| v.as_str())
+        .unwrap_or("jpeg")
+        .to_string()
+}
+
+// Optional "scale" to shrink the image before encoding, e.g. 0.5 for half size
+fn requested_scale(json_data: &serde_json::Value) -> Result<f32, String> {
+    match json_data.get("scale") {
+        None | Some(serde_json::Value::Null) => Ok(1.0),
+        Some(value) => value
+            .as_f64()
+            .filter(|scale| (MIN_SCREENSHOT_SCALE..=1.0).contains(scale))
+            .map(|scale| scale as f32)
+            .ok_or_else(|| {
+                format!(
+                    "invalid scale {} (must be between {} and 1)",
+                    value, MIN_SCREENSHOT_SCALE
+                )
+            }),
+    }
+}
+
+// Optional "monitor" index into the screen enumeration; None means the primary monitor
+fn requested_monitor(json_data: &serde_json::Value) -> Result<Option<usize>, String> {
+    match json_data.get("monitor") {
+        None | Some(serde_json::Value::Null) => Ok(None),
+        Some(value) => value
+            .as_u64()
+            .map(|index| Some(index as usize))
+            .ok_or_else(|| format!("invalid monitor index {}", value)),
+    }
+}
+
+// Resolve the requested monitor, returning its index alongside it
+fn select_monitor(
+    monitors: &[xcap::Monitor],
+    index: Option<usize>,
+) -> Result<(usize, &xcap::Monitor), String> {
+    let index = match index {
+        Some(index) if index >= monitors.len() => {
+            return Err(format!(
+                "invalid monitor index {} ({} monitor(s) available)",
+                index,
+                monitors.len()
+            ));
+        }
+        Some(index) => index,
+        None => monitors
+            .iter()
+            .position(|m| m.is_primary().unwrap_or(false))
+            .unwrap_or(0),
+    };
+
+    monitors
+        .get(index)
+        .map(|monitor| (index, monitor))
+        .ok_or_else(|| "no monitors found".to_string())
+}
+
+fn capture_screenshot_for(
+    app: &tauri::AppHandle,
+    conn: &ClientConnection,
+    json_data: &serde_json::Value,
+) {
+    if !screenshots_enabled(app) {
+        warn!("Screenshot request rejected - screen sharing is disabled");
+        send_reply(
+            &conn.reply(),
+            screenshot_error("screen sharing is disabled on this desktop"),
+        );
+        return;
+    }
+
+    // Validate before charging the interval so a malformed request doesn't cost a capture
+    let (monitor, scale) = match requested_monitor(json_data)
+        .and_then(|monitor| Ok((monitor, requested_scale(json_data)?)))
+    {
+        Ok(requested) => requested,
+        Err(e) => {
+            send_reply(&conn.reply(), screenshot_error(&e));
+            return;
+        }
+    };
+
+    {
+        let mut last_capture = conn.last_screenshot_at.lock_or_recover();
+        let now = std::time::Instant::now();
+        if let Some(last) = *last_capture {
+            if now.duration_since(last) < SCREENSHOT_MIN_INTERVAL {
+                warn!(
+                    "Screenshot request from {} rejected - rate limited",
+                    conn.addr
+                );
+                send_reply(
+                    &conn.reply(),
+                    screenshot_error("too many screenshot requests"),
+                );
+                return;
+            }
+        }
+        *last_capture = Some(now);
+    }
+    let format = requested_format(json_data);
+    let quality = requested_quality(json_data);
+
+    // Capturing and encoding is slow, keep it off the connection's read loop
+    let app = app.clone();
+    let reply = conn.reply();
+    tokio::spawn(async move {
+        let message =
+            capture_screenshot_message(&app, format, quality, scale, monitor, false).await;
+        send_reply(&reply, message);
+    });
+}
+
+// Capture on a blocking thread and wrap the result as a reply message
+async fn capture_screenshot_message(
+    app: &tauri::AppHandle,
+    format: String,
+    quality: u8,
+    scale: f32,
+    monitor: Option<usize>,
+    streaming: bool,
+) -> serde_json::Value {
+    let result =
+        tokio::task::spawn_blocking(move || capture_screenshot(&format, quality, scale, monitor))
+            .await;
+
+    match result {
+        Ok(Ok(shot)) => serde_json::json!({
+            "type": "screenshot",
+            "status": "ok",
+            "stream": streaming,
+            "monitor": shot.monitor,
+            "format": shot.format,
+            "width": shot.width,
+            "height": shot.height,
+            "data": shot.data
+        }),
+        Ok(Err(e)) => {
+            error!("Screenshot capture failed: {}", e);
+            let _ = app.emit("screen:error", serde_json::json!({ "error": e }));
+            // macOS returns blank or failed captures until Screen Recording is granted
+            if is_permission_error(&e) || e.to_lowercase().contains("screen recording") {
+                let _ = app.emit(
+                    "screen:permission-denied",
+                    serde_json::json!({ "error": e, "platform": std::env::consts::OS }),
+                );
+            }
+            screenshot_error(&e)
+        }
+        Err(e) => {
+            error!("Screenshot task failed: {}", e);
+            screenshot_error("screenshot task failed")
+        }
+    }
+}
+
+// Low frame rate screen streaming. Frames are sent as regular screenshot replies
+// tagged with "stream": true until the client sends a stop, screen sharing is
+// disabled, or the connection closes.
+const MAX_STREAM_FPS: u64 = 5;
+const DEFAULT_STREAM_FPS: u64 = 2;
+const DEFAULT_STREAM_QUALITY: u8 = 50;
+
+fn start_screen_stream(
+    app: &tauri::AppHandle,
+    conn: &ClientConnection,
+    json_data: &serde_json::Value,
+) {
+    if !screenshots_enabled(app) {
+        warn!("Screen stream rejected - screen sharing is disabled");
+        send_reply(
+            &conn.reply(),
+            screenshot_error("screen sharing is disabled on this desktop"),
+        );
+        return;
+    }
+
+    let fps = json_data
+        .get("fps")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(DEFAULT_STREAM_FPS)
+        .clamp(1, MAX_STREAM_FPS);
+    let quality = json_data
+        .get("quality")
+        .and_then(|v| v.as_u64())
+        .map(|q| q.clamp(1, 100) as u8)
+        .unwrap_or(DEFAULT_STREAM_QUALITY);
+    let format = requested_format(json_data);
+    let scale = match requested_scale(json_data) {
+        Ok(scale) => scale,
+        Err(e) => {
+            send_reply(&conn.reply(), screenshot_error(&e));
+            return;
+        }
+    };
+
+    // Validate the monitor up front so a bad index never starts a stream
+    let monitor = match requested_monitor(json_data).and_then(|index| {
+        let monitors = xcap::Monitor::all().map_err(|e| format!("failed to list monitors: {e}"))?;
+        select_monitor(&monitors, index)?;
+        Ok(index)
+    }) {
+        Ok(monitor) => monitor,
+        Err(e) => {
+            warn!("Screen stream rejected - {}", e);
+            send_reply(&conn.reply(), screenshot_error(&e));
+            return;
+        }
+    };
+
+    info!(
+        "Starting screen stream for {} at {} fps (quality {}, monitor {:?})",
+        conn.addr, fps, quality, monitor
+    );
+
+    let app = app.clone();
+    let reply = conn.reply();
+    let addr = conn.addr;
+    let task = tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(std::time::Duration::from_millis(1000 / fps));
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        loop {
+            ticker.tick().await;
+
+            if reply.tx.is_closed() {
+                break;
+            }
+            if !screenshots_enabled(&app) {
+                info!("Screen sharing disabled - ending stream for {}", addr);
+                send_reply(&reply, screenshot_error("screen sharing was disabled"));
+                break;
+            }
+
+            let message =
+                capture_screenshot_message(&app, format.clone(), quality, scale, monitor, true)
+                    .await;
+            send_reply(&reply, message);
+        }
+        info!("Screen stream for {} ended", addr);
+    });
+
+    // Only one stream per connection; a new request replaces the old one
+    if let Some(previous) = conn.screen_stream.lock_or_recover().replace(task) {
+        previous.abort();
+    }
+}
+
+fn stop_screen_stream(conn: &ClientConnection) {
+    if let Some(task) = conn.screen_stream.lock_or_recover().take() {
+        task.abort();
+        info!("Screen stream for {} stopped", conn.addr);
+    } else {
+        info!("No screen stream running for {}", conn.addr);
+    }
+}
+
+fn screenshot_error(reason: &str) -> serde_json::Value {
+    serde_json::json!({
+        "type": "screenshot",
+        "status": "error",
+        "reason": reason
+    })
+}
+
+pub(crate) struct Screenshot {
+    monitor: usize,
+    format: &'static str,
+    width: u32,
+    height: u32,
+    data: String, // base64 encoded image bytes
+}
+
+// Capture a single monitor (the primary one by default) and encode it as PNG or JPEG.
+// Capturing per monitor keeps other screens out of the image on multi-monitor setups.
+fn capture_screenshot(
+    format: &str,
+    quality: u8,
+    scale: f32,
+    monitor_index: Option<usize>,
+) -> Result<Screenshot, String> {
+    use base64::Engine;
+    use image::codecs::jpeg::JpegEncoder;
+    use image::ImageFormat;
+
+    let monitors = xcap::Monitor::all().map_err(|e| format!("failed to list monitors: {e}"))?;
+    let (index, monitor) = select_monitor(&monitors, monitor_index)?;
+
+    let image = monitor
+        .capture_image()
+        .map_err(|e| format!("screen capture failed: {e}"))?;
+    let image = if scale < 1.0 {
+        let (width, height) = image.dimensions();
+        let scaled = |side: u32| ((side as f32 * scale).round() as u32).max(1);
+        image::imageops::resize(
+            &image,
+            scaled(width),
+            scaled(height),
+            image::imageops::FilterType::Triangle,
+        )
+    } else {
+        image
+    };
+    let (width, height) = image.dimensions();
+
+    let mut bytes: Vec<u8> = Vec::new();
+    let format = match format {
+        "png" => {
+            image
+                .write_to(&mut std::io::Cursor::new(&mut bytes), ImageFormat::Png)
+                .map_err(|e| format!("PNG encoding failed: {e}"))?;
+            "png"
+        }
+        "jpeg" | "jpg" => {
+            // JPEG has no alpha channel
+            let rgb = image::DynamicImage::ImageRgba8(image).to_rgb8();
+            JpegEncoder::new_with_quality(&mut bytes, quality)
+                .encode_image(&rgb)
+                .map_err(|e| format!("JPEG encoding failed: {e}"))?;
+            "jpeg"
+        }
+        other => return Err(format!("unsupported image format '{}'", other)),
+    };
+
+    Ok(Screenshot {
+        monitor: index,
+        format,
+        width,
+        height,
+        data: base64::engine::general_purpose::STANDARD.encode(&bytes),
+    })
+}
+
+#[tauri::command]
+pub(crate) fn set_screenshot_enabled(state: State<MdnsState>, enabled: bool) -> Result<(), String> {
+    *state.screenshot_enabled.lock_or_recover() = enabled;
+    info!(
+        "Screen sharing {}",
+        if enabled { "enabled" } else { "disabled" }
+    );
+    Ok(())
+}
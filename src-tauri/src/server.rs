@@ -0,0 +1,1525 @@
+//! The socket server: connections, pairing, sessions and their limits.
+
+use enigo::Key;
+use std::{
+    collections::{HashMap, HashSet},
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex,
+    },
+};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use log::{debug, error, info, warn};
+use serde::Serialize;
+use tauri::{Emitter, Manager, State};
+
+use crate::file_transfer::FileTransfer;
+use crate::input::{release_drags_owned_by, release_keys_held_by};
+use crate::protocol::{
+    handle_json_rpc_line, handle_message, handle_msgpack_message, loggable_message,
+    message_too_long_reply, parse_hello, rpc_error, send_reply, take_msgpack_frames,
+    take_unterminated_json, Hello, ReplyFraming, ReplySender, MAX_MESSAGE_LEN, RPC_RATE_LIMITED,
+};
+use crate::settings::{save_settings, SavedSession};
+use crate::tls::{handle_tls_connection, load_or_create_tls_identity};
+use crate::{unix_millis, LockExt, MdnsState};
+
+// Per-connection state shared by the read loop and the handlers it dispatches to
+pub(crate) struct ClientConnection {
+    pub(crate) addr: std::net::SocketAddr,
+    // For messages the desktop sends on its own; replies go through `reply()`
+    pub(crate) push_reply: ReplySender,
+    // Set while a JSON-RPC request is dispatched, so its handlers answer that request
+    pub(crate) request_reply: Mutex<Option<ReplySender>>,
+    json_rpc: bool,
+    // Session this connection belongs to; see "Sessions"
+    session_id: Mutex<String>,
+    // Signalled to make the read loop close the connection from the desktop side
+    close: std::sync::Arc<tokio::sync::Notify>,
+    // Shared with the client list so desktop-initiated pushes skip unpaired clients
+    pub(crate) authenticated: std::sync::Arc<AtomicBool>,
+    pub(crate) screen_stream: Mutex<Option<tokio::task::JoinHandle<()>>>,
+    // When this connection's last one-off capture was taken, for SCREENSHOT_MIN_INTERVAL
+    pub(crate) last_screenshot_at: Mutex<Option<std::time::Instant>>,
+    // In-flight inertia scroll; any new scroll from this connection cancels it
+    pub(crate) scroll_inertia: Mutex<Option<tokio::task::JoinHandle<()>>>,
+    // Incoming file, if any; dropping it deletes the partial file
+    pub(crate) file_transfer: Mutex<Option<FileTransfer>>,
+    rate_limit: Mutex<CommandBucket>,
+    // Keys this connection holds with "key_down", released if it drops
+    pub(crate) held_keys: Mutex<HashSet<Key>>,
+    // When "input:activity" was last emitted for this connection
+    pub(crate) last_activity_event: Mutex<Option<std::time::Instant>>,
+    // Session stats
+    pub(crate) unknown_messages: AtomicU64,
+    rate_limited: AtomicU64,
+}
+
+impl ClientConnection {
+    // The sender for answering the command being dispatched. Handlers that answer later
+    // clone it up front.
+    pub(crate) fn reply(&self) -> ReplySender {
+        self.request_reply
+            .lock_or_recover()
+            .clone()
+            .unwrap_or_else(|| self.push_reply.clone())
+    }
+
+    // Stop any background work tied to this connection
+    fn shutdown(&self) {
+        if let Some(task) = self.screen_stream.lock_or_recover().take() {
+            task.abort();
+        }
+        if let Some(task) = self.scroll_inertia.lock_or_recover().take() {
+            task.abort();
+        }
+        if let Some(transfer) = self.file_transfer.lock_or_recover().take() {
+            info!(
+                "Discarding unfinished transfer of {} from {}",
+                transfer.name, self.addr
+            );
+        }
+    }
+}
+
+const DEFAULT_IDLE_TIMEOUT_SECS: u64 = 30;
+const MAX_IDLE_TIMEOUT_SECS: u64 = 3_600;
+
+#[tauri::command]
+pub(crate) fn set_idle_timeout(state: State<MdnsState>, timeout_secs: u64) -> Result<(), String> {
+    if timeout_secs > MAX_IDLE_TIMEOUT_SECS {
+        return Err(format!(
+            "Idle timeout must be between 0 (off) and {} seconds",
+            MAX_IDLE_TIMEOUT_SECS
+        ));
+    }
+
+    let mut settings = state.settings.lock_or_recover();
+    settings.idle_timeout_secs = Some(timeout_secs);
+    save_settings(&settings)?;
+
+    info!("Idle timeout for new connections set to {}s", timeout_secs);
+    Ok(())
+}
+
+// ---- Rate limiting ----
+// Each connection gets a token bucket holding one second's worth of commands, refilled
+// continuously, so short bursts (a fast drag) pass while a sustained flood is cut down
+// to the configured rate. Dropped commands are counted and answered with a
+// "rate limited" error; the UI hears about it through "socket:rate-limited", at most
+// once per RATE_LIMIT_EVENT_INTERVAL per connection.
+const DEFAULT_COMMANDS_PER_SEC: u32 = 500;
+const MAX_COMMANDS_PER_SEC: u32 = 10_000;
+const RATE_LIMIT_EVENT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+struct CommandBucket {
+    // 0 = unlimited
+    per_sec: u32,
+    tokens: f64,
+    refilled_at: std::time::Instant,
+    last_event: Option<std::time::Instant>,
+}
+
+impl CommandBucket {
+    fn new(per_sec: u32) -> Self {
+        CommandBucket {
+            per_sec,
+            tokens: per_sec as f64,
+            refilled_at: std::time::Instant::now(),
+            last_event: None,
+        }
+    }
+
+    fn take(&mut self) -> bool {
+        if self.per_sec == 0 {
+            return true;
+        }
+        let now = std::time::Instant::now();
+        let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+        let capacity = self.per_sec as f64;
+        self.tokens = (self.tokens + elapsed * capacity).min(capacity);
+        self.refilled_at = now;
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+pub(crate) fn max_commands_per_sec(app: &tauri::AppHandle) -> u32 {
+    app.state::<MdnsState>()
+        .settings
+        .lock_or_recover()
+        .max_commands_per_sec
+        .unwrap_or(DEFAULT_COMMANDS_PER_SEC)
+}
+
+fn take_command_token(app: &tauri::AppHandle, conn: &ClientConnection) -> bool {
+    let notify = {
+        let mut bucket = conn.rate_limit.lock_or_recover();
+        if bucket.take() {
+            return true;
+        }
+        let now = std::time::Instant::now();
+        let due = bucket
+            .last_event
+            .is_none_or(|at| now.duration_since(at) >= RATE_LIMIT_EVENT_INTERVAL);
+        if due {
+            bucket.last_event = Some(now);
+        }
+        due.then_some(bucket.per_sec)
+    };
+
+    let dropped = conn.rate_limited.fetch_add(1, Ordering::Relaxed) + 1;
+    if let Some(per_sec) = notify {
+        warn!(
+            "Rate limiting {} ({} command(s) dropped so far, limit {}/sec)",
+            conn.addr, dropped, per_sec
+        );
+        let _ = app.emit(
+            "socket:rate-limited",
+            serde_json::json!({
+                "addr": conn.addr.to_string(),
+                "dropped": dropped,
+                "limit_per_sec": per_sec,
+                "timestamp_ms": unix_millis()
+            }),
+        );
+    }
+    false
+}
+
+// Every framed message costs a token before it is parsed or checked for auth, so floods
+// of malformed or unauthenticated messages (and the warnings they log) are capped too
+fn admit_message(app: &tauri::AppHandle, conn: &ClientConnection) -> bool {
+    if take_command_token(app, conn) {
+        return true;
+    }
+    let reply = if conn.json_rpc {
+        rpc_error(serde_json::Value::Null, RPC_RATE_LIMITED, "rate limited")
+    } else {
+        serde_json::json!({ "status": "error", "reason": "rate limited" })
+    };
+    send_reply(&conn.push_reply, reply);
+    false
+}
+
+#[tauri::command]
+pub(crate) fn set_command_rate_limit(state: State<MdnsState>, per_sec: u32) -> Result<(), String> {
+    if per_sec > MAX_COMMANDS_PER_SEC {
+        return Err(format!(
+            "Command rate limit must be between 0 (off) and {} per second",
+            MAX_COMMANDS_PER_SEC
+        ));
+    }
+
+    let mut settings = state.settings.lock_or_recover();
+    settings.max_commands_per_sec = Some(per_sec);
+    save_settings(&settings)?;
+
+    info!(
+        "Command rate limit for new connections set to {}/sec",
+        per_sec
+    );
+    Ok(())
+}
+
+const DEFAULT_READ_BUFFER_SIZE: usize = 4096;
+const MIN_READ_BUFFER_SIZE: usize = 256;
+const MAX_READ_BUFFER_SIZE: usize = 64 * 1024;
+
+#[tauri::command]
+pub(crate) fn set_socket_buffer_size(state: State<MdnsState>, size: usize) -> Result<(), String> {
+    if !(MIN_READ_BUFFER_SIZE..=MAX_READ_BUFFER_SIZE).contains(&size) {
+        return Err(format!(
+            "Read buffer size must be between {} and {} bytes",
+            MIN_READ_BUFFER_SIZE, MAX_READ_BUFFER_SIZE
+        ));
+    }
+
+    let mut settings = state.settings.lock_or_recover();
+    settings.read_buffer_size = Some(size);
+    save_settings(&settings)?;
+
+    info!("Read buffer size for new connections set to {} bytes", size);
+    Ok(())
+}
+
+// `close` is owned by the server that accepted the connection, so stopping that server
+// reaches exactly its own connections
+pub(crate) async fn handle_socket_connection<S>(
+    app: tauri::AppHandle,
+    stream: S,
+    addr: std::net::SocketAddr,
+    close: std::sync::Arc<tokio::sync::Notify>,
+) where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Send + 'static,
+{
+    info!("New socket connection from: {}", addr);
+    // Here rather than on accept, so a TLS handshake that fails never announces a
+    // client that then can't disconnect
+    client_connected(&app, addr);
+
+    let (mut reader, mut writer) = tokio::io::split(stream);
+
+    // Replies go through a channel so handlers (and their background tasks)
+    // never need to own the socket
+    let (reply_tx, mut reply_rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+    tokio::spawn(async move {
+        while let Some(message) = reply_rx.recv().await {
+            if let Err(e) = writer.write_all(message.as_bytes()).await {
+                error!("Failed to write to socket: {}", e);
+                break;
+            }
+        }
+    });
+
+    let json_rpc = *app.state::<MdnsState>().json_rpc.lock_or_recover();
+    let conn = ClientConnection {
+        addr,
+        push_reply: ReplySender {
+            tx: reply_tx,
+            framing: if json_rpc {
+                ReplyFraming::RpcNotification
+            } else {
+                ReplyFraming::Native
+            },
+        },
+        request_reply: Mutex::new(None),
+        json_rpc,
+        session_id: Mutex::new(addr.ip().to_string()),
+        close,
+        authenticated: std::sync::Arc::new(AtomicBool::new(false)),
+        screen_stream: Mutex::new(None),
+        last_screenshot_at: Mutex::new(None),
+        scroll_inertia: Mutex::new(None),
+        file_transfer: Mutex::new(None),
+        rate_limit: Mutex::new(CommandBucket::new(max_commands_per_sec(&app))),
+        held_keys: Mutex::new(HashSet::new()),
+        last_activity_event: Mutex::new(None),
+        unknown_messages: AtomicU64::new(0),
+        rate_limited: AtomicU64::new(0),
+    };
+    attach_session(&app, &conn);
+    track_client(&app, &conn);
+
+    let idle_timeout = std::time::Duration::from_secs(
+        app.state::<MdnsState>()
+            .settings
+            .lock_or_recover()
+            .idle_timeout_secs
+            .unwrap_or(DEFAULT_IDLE_TIMEOUT_SECS),
+    );
+    let mut pending: Vec<u8> = Vec::new();
+    // Set after an oversized line is dropped unterminated: the rest of it is still on its
+    // way and must not be taken for the start of the next command
+    let mut discarding = false;
+
+    let read_buffer_size = app
+        .state::<MdnsState>()
+        .settings
+        .lock_or_recover()
+        .read_buffer_size
+        .unwrap_or(DEFAULT_READ_BUFFER_SIZE);
+    let mut buffer = vec![0; read_buffer_size];
+    // Only the first message may be a hello; see "MessagePack"
+    let mut awaiting_hello = !json_rpc;
+    let mut msgpack = false;
+
+    let disconnect_reason = loop {
+        // A phone that drops off Wi-Fi never closes its socket, so without a deadline
+        // the read would wait forever. The timeout restarts with every read.
+        let next = async {
+            tokio::select! {
+                read = reader.read(&mut buffer) => Some(read),
+                _ = conn.close.notified() => None,
+            }
+        };
+        let read = if idle_timeout.is_zero() {
+            next.await
+        } else {
+            match tokio::time::timeout(idle_timeout, next).await {
+                Ok(read) => read,
+                Err(_) => {
+                    warn!(
+                        "Closing idle connection from {} (nothing received for {:?})",
+                        addr, idle_timeout
+                    );
+                    break "idle_timeout";
+                }
+            }
+        };
+        let Some(read) = read else {
+            info!("Closing connection from {} at the desktop's request", addr);
+            break "closed_by_desktop";
+        };
+
+        match read {
+            Ok(0) => {
+                info!("Connection closed by client: {}", addr);
+                break "closed";
+            }
+            Ok(n) => {
+                touch_session(&app, &conn);
+                record_client_bytes(&app, addr, n);
+                pending.extend_from_slice(&buffer[..n]);
+
+                if discarding {
+                    match pending.iter().position(|&b| b == b'\n') {
+                        Some(end) => {
+                            pending.drain(..=end);
+                            discarding = false;
+                        }
+                        None => {
+                            pending.clear();
+                            continue;
+                        }
+                    }
+                }
+
+                while let Some(end) = pending
+                    .iter()
+                    .position(|&b| b == b'\n')
+                    .filter(|_| !msgpack)
+                {
+                    let line: Vec<u8> = pending.drain(..=end).collect();
+                    // A whole oversized line can arrive in one read, before the
+                    // unterminated check below ever sees it
+                    if line.len() > MAX_MESSAGE_LEN {
+                        warn!(
+                            "Dropping {} byte message from {} (max {})",
+                            line.len(),
+                            addr,
+                            MAX_MESSAGE_LEN
+                        );
+                        send_reply(&conn.reply(), message_too_long_reply(json_rpc));
+                        continue;
+                    }
+                    let line = String::from_utf8_lossy(&line);
+                    let line = line.trim();
+                    if line.is_empty() || !admit_message(&app, &conn) {
+                        continue;
+                    }
+                    if std::mem::take(&mut awaiting_hello) {
+                        match parse_hello(&conn, line) {
+                            Some(Hello::MsgPack) => msgpack = true,
+                            Some(Hello::Json) => {}
+                            None => handle_message(&app, &conn, line),
+                        }
+                        continue;
+                    }
+                    if json_rpc {
+                        debug!(
+                            "Received JSON-RPC from {}: {}",
+                            addr,
+                            loggable_message(line)
+                        );
+                        handle_json_rpc_line(&app, &conn, line);
+                    } else {
+                        handle_message(&app, &conn, line);
+                    }
+                }
+
+                if msgpack {
+                    match take_msgpack_frames(&mut pending) {
+                        Ok(frames) => {
+                            for frame in frames {
+                                if admit_message(&app, &conn) {
+                                    handle_msgpack_message(&app, &conn, &frame);
+                                }
+                            }
+                        }
+                        Err(len) => {
+                            warn!(
+                                "Closing connection from {}: {} byte frame exceeds the {} byte limit",
+                                addr, len, MAX_MESSAGE_LEN
+                            );
+                            send_reply(
+                                &conn.reply(),
+                                serde_json::json!({ "status": "error", "reason": "message too long" }),
+                            );
+                            break "frame_too_long";
+                        }
+                    }
+                }
+
+                // Legacy clients write JSON objects with no newline, sometimes several
+                // back to back in one segment
+                if !json_rpc && !msgpack {
+                    for value in take_unterminated_json(&mut pending) {
+                        awaiting_hello = false;
+                        if admit_message(&app, &conn) {
+                            handle_message(&app, &conn, &value.to_string());
+                        }
+                    }
+                }
+
+                // Frames carry their own limit, and a partial one may legitimately be
+                // this long
+                if !msgpack && pending.len() > MAX_MESSAGE_LEN {
+                    warn!(
+                        "Dropping {} byte unterminated message from {} (max {})",
+                        pending.len(),
+                        addr,
+                        MAX_MESSAGE_LEN
+                    );
+                    pending.clear();
+                    discarding = true;
+                    send_reply(&conn.reply(), message_too_long_reply(json_rpc));
+                }
+            }
+            Err(e) => {
+                error!("Failed to read from socket: {}", e);
+                break "error";
+            }
+        }
+    };
+
+    conn.shutdown();
+    let kicked = !untrack_client(&app, addr);
+    detach_session(&app, &conn);
+    release_drags_owned_by(&app, addr);
+    release_keys_held_by(&app, &conn);
+    if !kicked {
+        client_disconnected(&app, addr, disconnect_reason);
+    }
+    info!(
+        "Session stats for {}: {} unknown message(s), {} rate-limited",
+        addr,
+        conn.unknown_messages.load(Ordering::Relaxed),
+        conn.rate_limited.load(Ordering::Relaxed)
+    );
+}
+
+// ---- Connected clients ----
+// One entry per open socket, unlike sessions which outlive their connections.
+#[derive(Serialize, Clone)]
+pub(crate) struct ClientInfo {
+    addr: String,
+    session_id: String,
+    connected_at_ms: u64,
+    // Any bytes received, including partial lines
+    last_activity_ms: u64,
+    // Last complete command dispatched, authenticated or not
+    last_command_ms: Option<u64>,
+    bytes_received: u64,
+    #[serde(skip)]
+    close: std::sync::Arc<tokio::sync::Notify>,
+    #[serde(skip)]
+    pub(crate) reply: ReplySender,
+    #[serde(skip)]
+    pub(crate) authenticated: std::sync::Arc<AtomicBool>,
+}
+
+fn track_client(app: &tauri::AppHandle, conn: &ClientConnection) {
+    let state: State<MdnsState> = app.state();
+    let now = unix_millis();
+    let info = ClientInfo {
+        addr: conn.addr.to_string(),
+        session_id: conn.session_id.lock_or_recover().clone(),
+        connected_at_ms: now,
+        last_activity_ms: now,
+        last_command_ms: None,
+        bytes_received: 0,
+        close: conn.close.clone(),
+        reply: conn.push_reply.clone(),
+        authenticated: conn.authenticated.clone(),
+    };
+    state.clients.lock_or_recover().insert(conn.addr, info);
+}
+
+// False if the client was already dropped by `disconnect_client`
+fn untrack_client(app: &tauri::AppHandle, addr: std::net::SocketAddr) -> bool {
+    let state: State<MdnsState> = app.state();
+    let removed = state.clients.lock_or_recover().remove(&addr);
+    removed.is_some()
+}
+
+fn record_client_bytes(app: &tauri::AppHandle, addr: std::net::SocketAddr, bytes: usize) {
+    let state: State<MdnsState> = app.state();
+    let mut clients = state.clients.lock_or_recover();
+    if let Some(client) = clients.get_mut(&addr) {
+        client.last_activity_ms = unix_millis();
+        client.bytes_received += bytes as u64;
+    }
+}
+
+pub(crate) fn record_client_command(app: &tauri::AppHandle, addr: std::net::SocketAddr) {
+    let state: State<MdnsState> = app.state();
+    let mut clients = state.clients.lock_or_recover();
+    if let Some(client) = clients.get_mut(&addr) {
+        client.last_command_ms = Some(unix_millis());
+    }
+}
+
+#[tauri::command]
+pub(crate) fn get_connected_clients(state: State<MdnsState>) -> Vec<ClientInfo> {
+    let mut clients: Vec<ClientInfo> = state.clients.lock_or_recover().values().cloned().collect();
+    clients.sort_by_key(|c| c.connected_at_ms);
+    clients
+}
+
+// Kick one connection. Its read loop closes the socket on the next wakeup; the event
+// goes out right away rather than through the reconnect debounce, since a phone
+// reconnecting straight after being kicked is not flapping.
+#[tauri::command]
+pub(crate) fn disconnect_client(
+    app: tauri::AppHandle,
+    state: State<MdnsState>,
+    addr: String,
+) -> Result<(), String> {
+    let addr: std::net::SocketAddr = addr
+        .trim()
+        .parse()
+        .map_err(|e| format!("Invalid client address '{}': {e}", addr))?;
+    let client = state
+        .clients
+        .lock_or_recover()
+        .remove(&addr)
+        .ok_or_else(|| format!("No client connected from {}", addr))?;
+    client.close.notify_one();
+
+    info!("Disconnecting client {}", addr);
+    let _ = app.emit(
+        "socket:client-disconnected",
+        serde_json::json!({
+            "addr": addr.to_string(),
+            "reason": "kicked",
+            "timestamp_ms": unix_millis()
+        }),
+    );
+    Ok(())
+}
+
+// ---- Sessions ----
+// A session is a client identity that outlives individual connections. Clients name
+// themselves with {"type":"session","action":"hello","id":"<stable id>"}; until then a
+// connection belongs to a session named after its IP address. Sessions carry pairing
+// state and can be revoked from the UI, which also drops the session's connection.
+pub(crate) struct Session {
+    pub(crate) paired: bool,
+    pub(crate) last_seen_ms: u64,
+    // Live connection, if any
+    pub(crate) addr: Option<std::net::SocketAddr>,
+    pub(crate) close: Option<std::sync::Arc<tokio::sync::Notify>>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct SessionInfo {
+    id: String,
+    paired: bool,
+    last_seen_ms: u64,
+    addr: Option<String>,
+}
+
+const MAX_SESSION_ID_LEN: usize = 128;
+
+fn attach_session(app: &tauri::AppHandle, conn: &ClientConnection) {
+    let state: State<MdnsState> = app.state();
+    let id = conn.session_id.lock_or_recover().clone();
+    let mut sessions = state.sessions.lock_or_recover();
+    let session = sessions.entry(id).or_insert(Session {
+        paired: false,
+        last_seen_ms: 0,
+        addr: None,
+        close: None,
+    });
+    session.last_seen_ms = unix_millis();
+    session.addr = Some(conn.addr);
+    session.close = Some(conn.close.clone());
+}
+
+fn touch_session(app: &tauri::AppHandle, conn: &ClientConnection) {
+    let state: State<MdnsState> = app.state();
+    let id = conn.session_id.lock_or_recover().clone();
+    let mut sessions = state.sessions.lock_or_recover();
+    if let Some(session) = sessions.get_mut(&id) {
+        session.last_seen_ms = unix_millis();
+    }
+}
+
+fn detach_session(app: &tauri::AppHandle, conn: &ClientConnection) {
+    let state: State<MdnsState> = app.state();
+    let id = conn.session_id.lock_or_recover().clone();
+    {
+        let mut sessions = state.sessions.lock_or_recover();
+        // A newer connection may already have taken the session over
+        if let Some(session) = sessions.get_mut(&id).filter(|s| s.addr == Some(conn.addr)) {
+            session.last_seen_ms = unix_millis();
+            session.addr = None;
+            session.close = None;
+        }
+    }
+    persist_sessions(&state);
+}
+
+pub(crate) fn handle_session_command(
+    app: &tauri::AppHandle,
+    conn: &ClientConnection,
+    action: &str,
+    json_data: &serde_json::Value,
+) {
+    match action {
+        "hello" => {
+            let id = json_data
+                .get("id")
+                .and_then(|v| v.as_str())
+                .map(str::trim)
+                .filter(|id| !id.is_empty() && id.len() <= MAX_SESSION_ID_LEN);
+            let Some(id) = id else {
+                send_reply(
+                    &conn.reply(),
+                    serde_json::json!({
+                        "type": "session",
+                        "status": "error",
+                        "reason": format!("id must be 1-{} characters", MAX_SESSION_ID_LEN)
+                    }),
+                );
+                return;
+            };
+
+            detach_session(app, conn);
+            *conn.session_id.lock_or_recover() = id.to_string();
+            attach_session(app, conn);
+            {
+                let state: State<MdnsState> = app.state();
+                let mut clients = state.clients.lock_or_recover();
+                if let Some(client) = clients.get_mut(&conn.addr) {
+                    client.session_id = id.to_string();
+                }
+            }
+            if conn.authenticated.load(Ordering::Relaxed) {
+                mark_session_paired(app, conn);
+            }
+            persist_sessions(&app.state());
+
+            info!("{} identified as session {}", conn.addr, id);
+            send_reply(
+                &conn.reply(),
+                serde_json::json!({ "type": "session", "status": "ok", "id": id }),
+            );
+        }
+        _ => warn!("Unknown session action: {}", action),
+    }
+}
+
+pub(crate) fn persist_sessions(state: &MdnsState) {
+    let mut settings = state.settings.lock_or_recover();
+    if !settings.persist_sessions {
+        return;
+    }
+
+    settings.sessions = state
+        .sessions
+        .lock_or_recover()
+        .iter()
+        .map(|(id, session)| {
+            let saved = SavedSession {
+                paired: session.paired,
+                last_seen_ms: session.last_seen_ms,
+            };
+            (id.clone(), saved)
+        })
+        .collect();
+    if let Err(e) = save_settings(&settings) {
+        error!("Failed to save sessions: {}", e);
+    }
+}
+
+// Drop a session, closing its connection if it has one. The connection's normal
+// teardown releases any drag it holds.
+fn remove_session(state: &MdnsState, id: &str) -> bool {
+    let Some(session) = state.sessions.lock_or_recover().remove(id) else {
+        return false;
+    };
+    if let Some(close) = session.close {
+        info!("Closing connection for cleared session {}", id);
+        close.notify_one();
+    }
+    true
+}
+
+#[tauri::command]
+pub(crate) fn list_sessions(state: State<MdnsState>) -> Vec<SessionInfo> {
+    let mut sessions: Vec<SessionInfo> = state
+        .sessions
+        .lock_or_recover()
+        .iter()
+        .map(|(id, session)| SessionInfo {
+            id: id.clone(),
+            paired: session.paired,
+            last_seen_ms: session.last_seen_ms,
+            addr: session.addr.map(|addr| addr.to_string()),
+        })
+        .collect();
+    sessions.sort_by_key(|s| std::cmp::Reverse(s.last_seen_ms));
+    sessions
+}
+
+#[tauri::command]
+pub(crate) fn clear_session(state: State<MdnsState>, id: String) -> Result<(), String> {
+    if !remove_session(&state, &id) {
+        return Err(format!("No session with id {}", id));
+    }
+    persist_sessions(&state);
+    info!("Session {} cleared", id);
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn clear_all_sessions(state: State<MdnsState>) -> Result<usize, String> {
+    let ids: Vec<String> = state.sessions.lock_or_recover().keys().cloned().collect();
+    for id in &ids {
+        remove_session(&state, id);
+    }
+    persist_sessions(&state);
+    info!("Cleared {} session(s)", ids.len());
+    Ok(ids.len())
+}
+
+#[tauri::command]
+pub(crate) fn set_persist_sessions(state: State<MdnsState>, enabled: bool) -> Result<(), String> {
+    {
+        let mut settings = state.settings.lock_or_recover();
+        settings.persist_sessions = enabled;
+        if !enabled {
+            settings.sessions.clear();
+        }
+        save_settings(&settings)?;
+    }
+    persist_sessions(&state);
+
+    info!(
+        "Session persistence {}",
+        if enabled { "enabled" } else { "disabled" }
+    );
+    Ok(())
+}
+
+// ---- Pairing ----
+// The desktop shows a 6-digit PIN and every connection has to send it before anything
+// else: {"type":"auth","pin":"123456"}. Other messages on an unauthenticated
+// connection are answered with an "auth required" error. Wrong PINs are counted per
+// peer IP rather than per connection, since reconnecting is free: MAX_AUTH_FAILURES of
+// them close the connection and lock the IP out, for AUTH_LOCKOUT_BASE doubling with
+// every further lockout (up to MAX_AUTH_LOCKOUT). A correct PIN clears the record.
+// Regenerating the PIN doesn't affect connections that already authenticated.
+const MAX_AUTH_FAILURES: u64 = 3;
+const AUTH_LOCKOUT_BASE: std::time::Duration = std::time::Duration::from_secs(30);
+const MAX_AUTH_LOCKOUT: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+#[derive(Default)]
+pub(crate) struct AuthFailures {
+    failures: u64,
+    lockouts: u32,
+    locked_until: Option<std::time::Instant>,
+}
+
+fn auth_lockout(lockouts: u32) -> std::time::Duration {
+    AUTH_LOCKOUT_BASE
+        .saturating_mul(1 << lockouts.saturating_sub(1).min(16))
+        .min(MAX_AUTH_LOCKOUT)
+}
+
+// Time left on `ip`'s lockout, if it has one
+fn auth_locked_out(state: &MdnsState, ip: IpAddr) -> Option<std::time::Duration> {
+    let failures = state.auth_failures.lock_or_recover();
+    let locked_until = failures.get(&ip)?.locked_until?;
+    let left = locked_until.saturating_duration_since(std::time::Instant::now());
+    (!left.is_zero()).then_some(left)
+}
+
+// Count a wrong PIN from `ip`; returns the failures so far and the lockout it
+// triggered, if any
+fn record_auth_failure(state: &MdnsState, ip: IpAddr) -> (u64, Option<std::time::Duration>) {
+    let mut failures = state.auth_failures.lock_or_recover();
+    let record = failures.entry(ip).or_default();
+    record.failures += 1;
+    if record.failures < MAX_AUTH_FAILURES {
+        return (record.failures, None);
+    }
+    let count = record.failures;
+    record.failures = 0;
+    record.lockouts += 1;
+    let lockout = auth_lockout(record.lockouts);
+    record.locked_until = Some(std::time::Instant::now() + lockout);
+    (count, Some(lockout))
+}
+
+pub(crate) fn generate_pin() -> String {
+    use rand::Rng;
+    format!("{:06}", rand::thread_rng().gen_range(0..1_000_000))
+}
+
+pub(crate) fn emit_pairing_pin(app: &tauri::AppHandle, pin: &str) {
+    let _ = app.emit("pairing:pin", serde_json::json!({ "pin": pin }));
+}
+
+pub(crate) fn reject_unauthenticated(conn: &ClientConnection, msg_type: &str) {
+    warn!(
+        "Rejected {} message from unauthenticated client {}",
+        msg_type, conn.addr
+    );
+    send_reply(
+        &conn.reply(),
+        serde_json::json!({
+            "type": "auth",
+            "status": "error",
+            "reason": "auth required"
+        }),
+    );
+}
+
+pub(crate) fn handle_auth_command(
+    app: &tauri::AppHandle,
+    conn: &ClientConnection,
+    json_data: &serde_json::Value,
+) {
+    if conn.authenticated.load(Ordering::Relaxed) {
+        send_reply(
+            &conn.reply(),
+            serde_json::json!({ "type": "auth", "status": "ok" }),
+        );
+        return;
+    }
+
+    let state: State<MdnsState> = app.state();
+    let ip = conn.addr.ip().to_canonical();
+    // Checked before the PIN so a locked-out peer learns nothing from its guesses
+    if let Some(left) = auth_locked_out(&state, ip) {
+        warn!(
+            "Rejected PIN from {} (locked out for {:?})",
+            conn.addr, left
+        );
+        send_reply(
+            &conn.reply(),
+            serde_json::json!({
+                "type": "auth",
+                "status": "error",
+                "reason": "locked out",
+                "retry_after_secs": left.as_secs() + 1
+            }),
+        );
+        conn.close.notify_one();
+        return;
+    }
+    // Accept the PIN as a number too; leading zeros are restored by the format
+    let pin = match json_data.get("pin") {
+        Some(serde_json::Value::String(pin)) => Some(pin.trim().to_string()),
+        Some(serde_json::Value::Number(pin)) => pin.as_u64().map(|pin| format!("{:06}", pin)),
+        _ => None,
+    };
+    let matches = pin.is_some_and(|pin| pin == *state.pairing_pin.lock_or_recover());
+
+    if matches {
+        state.auth_failures.lock_or_recover().remove(&ip);
+        conn.authenticated.store(true, Ordering::Relaxed);
+        mark_session_paired(app, conn);
+        persist_sessions(&state);
+        info!("Client {} paired", conn.addr);
+        send_reply(
+            &conn.reply(),
+            serde_json::json!({ "type": "auth", "status": "ok" }),
+        );
+        return;
+    }
+
+    let (failures, lockout) = record_auth_failure(&state, ip);
+    info!(
+        "Wrong PIN from {} ({}/{})",
+        conn.addr, failures, MAX_AUTH_FAILURES
+    );
+    send_reply(
+        &conn.reply(),
+        serde_json::json!({
+            "type": "auth",
+            "status": "error",
+            "reason": "invalid pin",
+            "attempts_left": MAX_AUTH_FAILURES.saturating_sub(failures)
+        }),
+    );
+    if let Some(lockout) = lockout {
+        warn!(
+            "Too many failed PIN attempts from {} - locked out for {:?}",
+            conn.addr, lockout
+        );
+        conn.close.notify_one();
+    }
+}
+
+fn mark_session_paired(app: &tauri::AppHandle, conn: &ClientConnection) {
+    let state: State<MdnsState> = app.state();
+    let id = conn.session_id.lock_or_recover().clone();
+    let mut sessions = state.sessions.lock_or_recover();
+    if let Some(session) = sessions.get_mut(&id) {
+        session.paired = true;
+    }
+}
+
+#[tauri::command]
+pub(crate) fn get_pairing_pin(state: State<MdnsState>) -> String {
+    state.pairing_pin.lock_or_recover().clone()
+}
+
+#[tauri::command]
+pub(crate) fn regenerate_pin(app: tauri::AppHandle, state: State<MdnsState>) -> String {
+    let pin = generate_pin();
+    *state.pairing_pin.lock_or_recover() = pin.clone();
+    emit_pairing_pin(&app, &pin);
+    info!("Pairing PIN regenerated");
+    pin
+}
+
+// ---- Connection churn ----
+// A client on bad Wi-Fi can drop and reconnect every few seconds. Disconnect events
+// are held back for the debounce window; if the same peer (by IP - its port changes on
+// every reconnect) comes back in time, both events are swallowed and the UI gets a
+// single "socket:flapping" warning per burst instead of a connect/disconnect storm.
+const DEFAULT_RECONNECT_DEBOUNCE_MS: u64 = 2_000;
+const MAX_RECONNECT_DEBOUNCE_MS: u64 = 30_000;
+
+#[derive(Default)]
+pub(crate) struct PeerChurn {
+    // Token of the disconnect event waiting for the window to pass
+    pending_disconnect: Option<u64>,
+    reconnects: u32,
+    warned: bool,
+}
+
+fn reconnect_debounce(state: &MdnsState) -> std::time::Duration {
+    let ms = state
+        .settings
+        .lock_or_recover()
+        .reconnect_debounce_ms
+        .unwrap_or(DEFAULT_RECONNECT_DEBOUNCE_MS);
+    std::time::Duration::from_millis(ms)
+}
+
+fn client_connected(app: &tauri::AppHandle, addr: std::net::SocketAddr) {
+    let state: State<MdnsState> = app.state();
+    let flapping = {
+        let mut churn = state.peer_churn.lock_or_recover();
+        churn
+            .get_mut(&addr.ip())
+            .filter(|peer| peer.pending_disconnect.is_some())
+            .map(|peer| {
+                peer.pending_disconnect = None;
+                peer.reconnects += 1;
+                let first_warning = !peer.warned;
+                peer.warned = true;
+                (peer.reconnects, first_warning)
+            })
+    };
+
+    match flapping {
+        Some((reconnects, first_warning)) => {
+            info!(
+                "{} reconnected within the debounce window ({} reconnects)",
+                addr, reconnects
+            );
+            if first_warning {
+                let _ = app.emit(
+                    "socket:flapping",
+                    serde_json::json!({
+                        "addr": addr.to_string(),
+                        "reconnects": reconnects,
+                        "window_ms": reconnect_debounce(&state).as_millis() as u64,
+                        "timestamp_ms": unix_millis()
+                    }),
+                );
+            }
+        }
+        None => {
+            let _ = app.emit(
+                "socket:client-connected",
+                serde_json::json!({ "addr": addr.to_string(), "timestamp_ms": unix_millis() }),
+            );
+        }
+    }
+}
+
+fn client_disconnected(app: &tauri::AppHandle, addr: std::net::SocketAddr, reason: &str) {
+    let payload = serde_json::json!({
+        "addr": addr.to_string(),
+        "reason": reason,
+        "timestamp_ms": unix_millis()
+    });
+
+    let state: State<MdnsState> = app.state();
+    let window = reconnect_debounce(&state);
+    if window.is_zero() {
+        let _ = app.emit("socket:client-disconnected", payload);
+        return;
+    }
+
+    let token = state.peer_churn_token.fetch_add(1, Ordering::Relaxed);
+    state
+        .peer_churn
+        .lock_or_recover()
+        .entry(addr.ip())
+        .or_default()
+        .pending_disconnect = Some(token);
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(window).await;
+
+        let state: State<MdnsState> = app.state();
+        let expired = {
+            let mut churn = state.peer_churn.lock_or_recover();
+            let still_pending = churn
+                .get(&addr.ip())
+                .is_some_and(|peer| peer.pending_disconnect == Some(token));
+            if still_pending {
+                churn.remove(&addr.ip());
+            }
+            still_pending
+        };
+        if expired {
+            let _ = app.emit("socket:client-disconnected", payload);
+        }
+    });
+}
+
+#[tauri::command]
+pub(crate) fn set_reconnect_debounce(
+    state: State<MdnsState>,
+    window_ms: u64,
+) -> Result<(), String> {
+    if window_ms > MAX_RECONNECT_DEBOUNCE_MS {
+        return Err(format!(
+            "Reconnect debounce must be between 0 and {}ms",
+            MAX_RECONNECT_DEBOUNCE_MS
+        ));
+    }
+
+    let mut settings = state.settings.lock_or_recover();
+    settings.reconnect_debounce_ms = Some(window_ms);
+    save_settings(&settings)?;
+
+    info!("Reconnect debounce window set to {}ms", window_ms);
+    Ok(())
+}
+
+// ---- IP allowlist ----
+// When the allowlist is non-empty only listed peers may connect; everyone else is
+// dropped right after accept, before any data is read. An empty list allows everyone,
+// which is how the app behaved before the allowlist existed.
+fn ip_allowed(app: &tauri::AppHandle, ip: IpAddr) -> bool {
+    let state: State<MdnsState> = app.state();
+    let allowed = state.allowed_ips.lock_or_recover();
+    allowed.is_empty() || allowed.contains(&ip.to_canonical())
+}
+
+fn reject_connection(app: &tauri::AppHandle, stream: TcpStream, addr: std::net::SocketAddr) {
+    drop(stream);
+    warn!("Rejected connection from {} (not in allowlist)", addr);
+    let _ = app.emit(
+        "socket:connection-rejected",
+        serde_json::json!({
+            "addr": addr.to_string(),
+            "reason": "not_allowed",
+            "timestamp_ms": unix_millis()
+        }),
+    );
+}
+
+fn parse_ip(ip: &str) -> Result<IpAddr, String> {
+    ip.trim()
+        .parse::<IpAddr>()
+        .map(|ip| ip.to_canonical())
+        .map_err(|e| format!("Invalid IP address '{}': {e}", ip))
+}
+
+fn save_allowed_ips(state: &MdnsState) -> Result<(), String> {
+    let allowed = state.allowed_ips.lock_or_recover().clone();
+    let mut settings = state.settings.lock_or_recover();
+    settings.allowed_ips = allowed;
+    save_settings(&settings)
+}
+
+#[tauri::command]
+pub(crate) fn allow_ip(state: State<MdnsState>, ip: String) -> Result<(), String> {
+    let ip = parse_ip(&ip)?;
+    if !state.allowed_ips.lock_or_recover().insert(ip) {
+        return Ok(());
+    }
+    save_allowed_ips(&state)?;
+    info!("Allowed {} to connect", ip);
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn revoke_ip(state: State<MdnsState>, ip: String) -> Result<(), String> {
+    let ip = parse_ip(&ip)?;
+    if !state.allowed_ips.lock_or_recover().remove(&ip) {
+        return Err(format!("{} is not in the allowlist", ip));
+    }
+    save_allowed_ips(&state)?;
+    info!("Revoked {} from the allowlist", ip);
+
+    // The allowlist is checked on accept, so close what the peer already has open
+    for session in state.sessions.lock_or_recover().values() {
+        if let (Some(addr), Some(close)) = (session.addr, &session.close) {
+            if addr.ip().to_canonical() == ip {
+                close.notify_one();
+            }
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn list_allowed_ips(state: State<MdnsState>) -> Vec<String> {
+    let mut ips: Vec<IpAddr> = state
+        .allowed_ips
+        .lock_or_recover()
+        .iter()
+        .copied()
+        .collect();
+    ips.sort();
+    ips.iter().map(|ip| ip.to_string()).collect()
+}
+
+// SO_REUSEADDR lets a restart reuse a port whose old socket is still in
+// TIME_WAIT. Not set on Windows, where it would let another process steal
+// the port instead.
+pub(crate) fn bind_reusable_listener(addr: std::net::SocketAddr) -> std::io::Result<TcpListener> {
+    let socket = if addr.is_ipv4() {
+        tokio::net::TcpSocket::new_v4()?
+    } else {
+        tokio::net::TcpSocket::new_v6()?
+    };
+    #[cfg(not(windows))]
+    socket.set_reuseaddr(true)?;
+    socket.bind(addr)?;
+    socket.listen(1024)
+}
+
+// How long open connections get to close after the server is told to stop before
+// their tasks are aborted
+const SOCKET_DRAIN_GRACE: std::time::Duration = std::time::Duration::from_secs(3);
+
+// Bound in `start_socket_server` so bind errors reach the caller. Once `shutdown` is
+// signalled the listener is dropped, every connection is asked to close (each finishes
+// the commands it has already read), and whatever is still open after
+// SOCKET_DRAIN_GRACE is aborted. "socket:server-stopped" follows the drain.
+async fn run_socket_server(
+    app: tauri::AppHandle,
+    listener: TcpListener,
+    tls: Option<tokio_rustls::TlsAcceptor>,
+    shutdown: std::sync::Arc<tokio::sync::Notify>,
+) {
+    let mut connections = tokio::task::JoinSet::new();
+    // Close handles of this server's connections, so the drain below doesn't reach
+    // connections of a server started after this one
+    let mut closers: HashMap<tokio::task::Id, std::sync::Arc<tokio::sync::Notify>> = HashMap::new();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, addr)) => {
+                    if !ip_allowed(&app, addr.ip()) {
+                        reject_connection(&app, stream, addr);
+                        continue;
+                    }
+                    let close = std::sync::Arc::new(tokio::sync::Notify::new());
+                    let task = match tls.clone() {
+                        Some(acceptor) => connections.spawn(handle_tls_connection(
+                            app.clone(),
+                            acceptor,
+                            stream,
+                            addr,
+                            close.clone(),
+                        )),
+                        None => connections.spawn(handle_socket_connection(
+                            app.clone(),
+                            stream,
+                            addr,
+                            close.clone(),
+                        )),
+                    };
+                    closers.insert(task.id(), close);
+                }
+                Err(e) => {
+                    error!("Failed to accept connection: {}", e);
+                }
+            },
+            // Reap finished connections so the set doesn't grow
+            Some(finished) = connections.join_next_with_id(), if !connections.is_empty() => {
+                let id = match finished {
+                    Ok((id, ())) => id,
+                    Err(e) => e.id(),
+                };
+                closers.remove(&id);
+            }
+            _ = shutdown.notified() => break,
+        }
+    }
+    drop(listener);
+
+    let open = connections.len();
+    info!(
+        "Socket server no longer accepting; closing {} connection(s)",
+        open
+    );
+    for close in closers.values() {
+        close.notify_one();
+    }
+    let drained = tokio::time::timeout(SOCKET_DRAIN_GRACE, async {
+        while connections.join_next().await.is_some() {}
+    })
+    .await
+    .is_ok();
+    if !drained {
+        warn!(
+            "Aborting {} connection(s) still open after {:?}",
+            connections.len(),
+            SOCKET_DRAIN_GRACE
+        );
+        connections.shutdown().await;
+    }
+
+    info!("Socket server stopped");
+    let _ = app.emit(
+        "socket:server-stopped",
+        serde_json::json!({
+            "connections_closed": open,
+            "forced": !drained,
+            "timestamp_ms": unix_millis()
+        }),
+    );
+}
+
+// Ask the running server to stop; the drain continues in the background
+pub(crate) fn signal_socket_server_shutdown(state: &MdnsState) -> bool {
+    // Dropping the handle detaches the task rather than aborting it
+    state.socket_server_handle.lock_or_recover().take();
+    if let Some(status_task) = state.socket_status_task.lock_or_recover().take() {
+        status_task.abort();
+    }
+    match state.socket_server_shutdown.lock_or_recover().take() {
+        Some(shutdown) => {
+            shutdown.notify_one();
+            true
+        }
+        None => false,
+    }
+}
+
+#[tauri::command]
+pub(crate) async fn start_socket_server(
+    app: tauri::AppHandle,
+    state: State<'_, MdnsState>,
+    bind_addr: Option<String>, // interface to listen on (default 0.0.0.0, all of them)
+    port: Option<u16>,         // fixed port, e.g. the previous one on restart (default random)
+    tls: Option<bool>,         // encrypt connections; see "TLS" (default false)
+) -> Result<u16, String> {
+    info!("Starting socket server...");
+
+    // Check if server is already running
+    if state.socket_server_port.lock_or_recover().is_some() {
+        let port = state.socket_server_port.lock_or_recover().unwrap();
+        // Handing back a plaintext port to a caller that asked for TLS (or the other way
+        // round) would leave it talking the wrong protocol
+        let running_tls = state.tls_fingerprint.lock_or_recover().is_some();
+        if tls.is_some_and(|wanted| wanted != running_tls) {
+            return Err(format!(
+                "Socket server is already running on port {} with TLS {}; stop it first to switch",
+                port,
+                if running_tls { "on" } else { "off" }
+            ));
+        }
+        info!("Socket server already running on port: {}", port);
+        return Ok(port);
+    }
+
+    let bind_ip: IpAddr = match bind_addr.as_deref().map(str::trim) {
+        None | Some("") => IpAddr::from([0, 0, 0, 0]),
+        Some(addr) => addr
+            .parse()
+            .map_err(|e| format!("Invalid bind address '{}': {e}", addr))?,
+    };
+
+    let port = match port {
+        Some(0) => return Err("Port must be between 1 and 65535".to_string()),
+        Some(port) => port,
+        // Get a random free port
+        None => portpicker::pick_unused_port().ok_or("Failed to find an unused port")?,
+    };
+
+    debug!("Selected port: {}", port);
+
+    let tls = match tls.unwrap_or(false) {
+        true => Some(load_or_create_tls_identity()?),
+        false => None,
+    };
+
+    let addr = std::net::SocketAddr::new(bind_ip, port);
+    let listener = bind_reusable_listener(addr)
+        .map_err(|e| format!("Failed to bind socket server to {}: {e}", addr))?;
+    info!("Socket server listening on: {}", addr);
+
+    // Start the server in a background task
+    let shutdown = std::sync::Arc::new(tokio::sync::Notify::new());
+    let fingerprint = tls.as_ref().map(|identity| identity.fingerprint.clone());
+    if let Some(fingerprint) = &fingerprint {
+        info!("TLS enabled, certificate fingerprint {}", fingerprint);
+    }
+    let acceptor = tls.map(|identity| identity.acceptor);
+    state.commands_processed.store(0, Ordering::Relaxed);
+    let status_task = tokio::spawn(run_status_heartbeat(app.clone()));
+    let server_handle = tokio::spawn(run_socket_server(app, listener, acceptor, shutdown.clone()));
+
+    // Store the port and handle
+    *state.socket_server_port.lock_or_recover() = Some(port);
+    *state.socket_bind_addr.lock_or_recover() = Some(bind_ip);
+    *state.socket_server_handle.lock_or_recover() = Some(server_handle);
+    *state.socket_status_task.lock_or_recover() = Some(status_task);
+    *state.socket_server_shutdown.lock_or_recover() = Some(shutdown);
+    *state.socket_server_started_at.lock_or_recover() = Some(std::time::Instant::now());
+    *state.tls_fingerprint.lock_or_recover() = fingerprint;
+
+    info!("Socket server started successfully on port: {}", port);
+    Ok(port)
+}
+
+// ---- Status heartbeat ----
+// While the socket server runs, "socket:status" goes out every status interval with the
+// connected-client count, the commands processed since the server started and its
+// uptime, so a dashboard can stay live without polling get_socket_server_status. The
+// interval is re-read on every tick, so set_status_interval applies without a restart;
+// at 0 the task idles until it is turned back on.
+const DEFAULT_STATUS_INTERVAL_SECS: u64 = 5;
+const MAX_STATUS_INTERVAL_SECS: u64 = 3_600;
+
+async fn run_status_heartbeat(app: tauri::AppHandle) {
+    loop {
+        let interval_secs = app
+            .state::<MdnsState>()
+            .settings
+            .lock_or_recover()
+            .status_interval_secs
+            .unwrap_or(DEFAULT_STATUS_INTERVAL_SECS);
+        let period = std::time::Duration::from_secs(match interval_secs {
+            0 => DEFAULT_STATUS_INTERVAL_SECS,
+            secs => secs,
+        });
+        tokio::time::sleep(period).await;
+        if interval_secs == 0 {
+            continue;
+        }
+
+        let state: State<MdnsState> = app.state();
+        let clients = state.clients.lock_or_recover().len();
+        let _ = app.emit(
+            "socket:status",
+            serde_json::json!({
+                "port": *state.socket_server_port.lock_or_recover(),
+                "clients": clients,
+                "commands_processed": state.commands_processed.load(Ordering::Relaxed),
+                "uptime_secs": socket_uptime_secs(&state),
+                "timestamp_ms": unix_millis()
+            }),
+        );
+    }
+}
+
+#[tauri::command]
+pub(crate) fn set_status_interval(
+    state: State<MdnsState>,
+    interval_secs: u64,
+) -> Result<(), String> {
+    if interval_secs > MAX_STATUS_INTERVAL_SECS {
+        return Err(format!(
+            "Status interval must be between 0 (off) and {} seconds",
+            MAX_STATUS_INTERVAL_SECS
+        ));
+    }
+
+    let mut settings = state.settings.lock_or_recover();
+    settings.status_interval_secs = Some(interval_secs);
+    save_settings(&settings)?;
+
+    if interval_secs == 0 {
+        info!("Socket status events disabled");
+    } else {
+        info!("Socket status events every {} s", interval_secs);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn stop_socket_server(state: State<MdnsState>) -> Result<(), String> {
+    info!("Stopping socket server...");
+
+    // Stop accepting; open connections drain in the background
+    if signal_socket_server_shutdown(&state) {
+        info!("Socket server shutdown signalled");
+    }
+
+    // Clear the port
+    *state.socket_server_port.lock_or_recover() = None;
+    *state.socket_bind_addr.lock_or_recover() = None;
+    *state.socket_server_started_at.lock_or_recover() = None;
+    *state.tls_fingerprint.lock_or_recover() = None;
+
+    info!("Socket server stopping");
+    Ok(())
+}
+
+#[tauri::command]
+pub(crate) fn get_socket_server_status(
+    state: State<MdnsState>,
+) -> Result<serde_json::Value, String> {
+    let port = *state.socket_server_port.lock_or_recover();
+    let bind_addr = state
+        .socket_bind_addr
+        .lock_or_recover()
+        .map(|ip| ip.to_string());
+    let is_running = port.is_some();
+    let text_protocol = *state.text_protocol.lock_or_recover();
+    let json_rpc = *state.json_rpc.lock_or_recover();
+    let tls_fingerprint = state.tls_fingerprint.lock_or_recover().clone();
+    let screenshot_enabled = *state.screenshot_enabled.lock_or_recover();
+    let file_transfer_enabled = *state.file_transfer_enabled.lock_or_recover();
+    let system_shortcuts_enabled = *state.system_shortcuts_enabled.lock_or_recover();
+    let allow_system_commands = *state.allow_system_commands.lock_or_recover();
+    let strict_message_types = *state.strict_message_types.lock_or_recover();
+    let cursor_sensitivity = *state.cursor_sensitivity.lock_or_recover();
+    let cursor_acceleration = *state.cursor_acceleration.lock_or_recover();
+
+    Ok(serde_json::json!({
+        "running": is_running,
+        "port": port,
+        "bind_addr": bind_addr,
+        "text_protocol": text_protocol,
+        "json_rpc": json_rpc,
+        "tls": tls_fingerprint.is_some(),
+        "tls_fingerprint": tls_fingerprint,
+        "screenshot_enabled": screenshot_enabled,
+        "file_transfer_enabled": file_transfer_enabled,
+        "system_shortcuts_enabled": system_shortcuts_enabled,
+        "allow_system_commands": allow_system_commands,
+        "strict_message_types": strict_message_types,
+        "cursor_sensitivity": cursor_sensitivity,
+        "cursor_acceleration": cursor_acceleration,
+        "commands_processed": state.commands_processed.load(Ordering::Relaxed),
+        "unknown_messages": state.unknown_message_count.load(Ordering::Relaxed)
+    }))
+}
+
+pub(crate) fn socket_uptime_secs(state: &MdnsState) -> Option<u64> {
+    state
+        .socket_server_started_at
+        .lock_or_recover()
+        .map(|started| started.elapsed().as_secs())
+}
+
+#[tauri::command]
+pub(crate) fn get_socket_uptime(state: State<MdnsState>) -> Option<u64> {
+    socket_uptime_secs(&state)
+}
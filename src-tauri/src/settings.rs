@@ -0,0 +1,187 @@
+//! Persisted user preferences and the retry policies stored with them.
+
+use once_cell::sync::OnceCell;
+use std::{
+    collections::{HashMap, HashSet},
+    net::IpAddr,
+    path::PathBuf,
+};
+
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+
+// ---- Settings ----
+// Persisted user preferences, stored as JSON in the app config directory.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub(crate) struct AppSettings {
+    // Tokio worker threads to use on next launch (None = one per logical core)
+    pub(crate) worker_threads: Option<usize>,
+    // Advertised as a "room=<name>" TXT record so clients can browse one room only
+    pub(crate) room: Option<String>,
+    // User-chosen display names, keyed by a device's TXT "id" or its instance name
+    pub(crate) device_aliases: HashMap<String, String>,
+    pub(crate) input_retry: InputRetryPolicy,
+    pub(crate) discovery_retry: DiscoveryRetryPolicy,
+    // Most discovered devices kept in the cache (None = DEFAULT_MAX_CACHED_DEVICES)
+    pub(crate) max_cached_devices: Option<usize>,
+    // Shortest gap between two "mdns:update" events for one device
+    // (None = DEFAULT_UPDATE_DEBOUNCE_MS, 0 disables)
+    pub(crate) update_debounce_ms: Option<u64>,
+    // Cap on batched "input:applied" move events (None = DEFAULT_ACTIVITY_EVENTS_PER_SEC)
+    pub(crate) activity_events_per_sec: Option<u32>,
+    pub(crate) drag_conflict_policy: DragConflictPolicy,
+    // A peer that reconnects within this window is treated as the same session
+    // (None = DEFAULT_RECONNECT_DEBOUNCE_MS, 0 disables)
+    pub(crate) reconnect_debounce_ms: Option<u64>,
+    // Connections that send nothing for this long are closed
+    // (None = DEFAULT_IDLE_TIMEOUT_SECS, 0 disables)
+    pub(crate) idle_timeout_secs: Option<u64>,
+    // Period of the "socket:status" heartbeat (None = DEFAULT_STATUS_INTERVAL_SECS,
+    // 0 disables)
+    pub(crate) status_interval_secs: Option<u64>,
+    // "error", "warn", "info", "debug" or "trace" (None = DEFAULT_LOG_LEVEL).
+    // RUST_LOG takes precedence at startup.
+    pub(crate) log_level: Option<String>,
+    // Commands accepted per connection per second; see "Rate limiting"
+    // (None = DEFAULT_COMMANDS_PER_SEC, 0 disables)
+    pub(crate) max_commands_per_sec: Option<u32>,
+    // Bytes read from a connection at a time (None = DEFAULT_READ_BUFFER_SIZE)
+    pub(crate) read_buffer_size: Option<usize>,
+    // Largest file a client may send (None = DEFAULT_MAX_FILE_SIZE)
+    pub(crate) max_file_size: Option<u64>,
+    // How `{"smooth":true}` moves are split up
+    // (None = DEFAULT_SMOOTH_MOVE_STEPS / DEFAULT_SMOOTH_MOVE_DURATION_MS)
+    pub(crate) smooth_move_steps: Option<u32>,
+    pub(crate) smooth_move_duration_ms: Option<u64>,
+    // How `{"inertia":true}` scrolls decay
+    // (None = DEFAULT_SCROLL_DECAY / DEFAULT_SCROLL_STEP_INTERVAL_MS)
+    pub(crate) scroll_decay: Option<f32>,
+    pub(crate) scroll_step_interval_ms: Option<u64>,
+    // Only these peers may connect to the socket server (empty = everyone)
+    pub(crate) allowed_ips: HashSet<IpAddr>,
+    // Keep the session store across restarts (off by default)
+    pub(crate) persist_sessions: bool,
+    pub(crate) sessions: HashMap<String, SavedSession>,
+    // What was registered/browsed last, for `restore_last_session` (opt-in)
+    pub(crate) restore_last_session: bool,
+    pub(crate) last_session: LastSession,
+    // How often held keys are checked against the OS key state
+    // (None = DEFAULT_HELD_KEY_CHECK_MS, 0 disables)
+    pub(crate) held_key_check_ms: Option<u64>,
+}
+
+// What happens when a second client starts a drag while another client's drag is
+// still holding the button
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum DragConflictPolicy {
+    #[default]
+    FirstWins,
+    LastWins,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct SavedSession {
+    pub(crate) paired: bool,
+    pub(crate) last_seen_ms: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub(crate) struct LastSession {
+    pub(crate) service: Option<SavedService>,
+    pub(crate) discovery_type: Option<String>,
+    pub(crate) discovery_ip_version: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct SavedService {
+    pub(crate) service_type: String,
+    pub(crate) instance_name: String,
+    // None when the service advertised the socket server's own (randomly picked) port,
+    // so a restore advertises whatever port the server has now
+    pub(crate) port: Option<u16>,
+    pub(crate) txt: Vec<String>,
+    pub(crate) ip_version: Option<String>,
+    pub(crate) include_virtual: Option<bool>,
+    pub(crate) ttl_secs: Option<u32>,
+    pub(crate) announce_interval_secs: Option<u64>,
+}
+
+// How failed Enigo calls are retried; max_retries = 0 disables retrying
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(default)]
+pub(crate) struct InputRetryPolicy {
+    pub(crate) max_retries: u32,
+    pub(crate) base_delay_ms: u64,
+}
+
+impl Default for InputRetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 0,
+            base_delay_ms: 20,
+        }
+    }
+}
+
+pub(crate) const MAX_INPUT_RETRIES: u32 = 5;
+pub(crate) const MAX_INPUT_RETRY_DELAY_MS: u64 = 500;
+
+// How a discovery that died in the background is restarted; the delay doubles after
+// each failed attempt. max_retries = 0 reports the failure without retrying.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(default)]
+pub(crate) struct DiscoveryRetryPolicy {
+    pub(crate) max_retries: u32,
+    pub(crate) base_delay_ms: u64,
+}
+
+impl Default for DiscoveryRetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            base_delay_ms: 1_000,
+        }
+    }
+}
+
+pub(crate) const MAX_DISCOVERY_RETRIES: u32 = 20;
+pub(crate) const MAX_DISCOVERY_RETRY_DELAY_MS: u64 = 60_000;
+
+pub(crate) static SETTINGS_PATH: OnceCell<PathBuf> = OnceCell::new();
+
+// Settings are loaded before the Tauri app (and its path resolver) exists, so the
+// config directory is resolved the same way Tauri does: <config dir>/<identifier>.
+pub(crate) fn load_settings(identifier: &str) -> AppSettings {
+    let Some(config_dir) = dirs::config_dir() else {
+        warn!("No config directory available - using default settings");
+        return AppSettings::default();
+    };
+    let path = config_dir.join(identifier).join("settings.json");
+
+    let settings = match std::fs::read_to_string(&path) {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+            error!("Failed to parse settings at {}: {}", path.display(), e);
+            AppSettings::default()
+        }),
+        Err(_) => AppSettings::default(),
+    };
+
+    let _ = SETTINGS_PATH.set(path);
+    settings
+}
+
+pub(crate) fn save_settings(settings: &AppSettings) -> Result<(), String> {
+    let path = SETTINGS_PATH
+        .get()
+        .ok_or("Settings location is not available on this system")?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("failed to create settings directory: {e}"))?;
+    }
+    let contents = serde_json::to_string_pretty(settings)
+        .map_err(|e| format!("failed to serialize settings: {e}"))?;
+    std::fs::write(path, contents).map_err(|e| format!("failed to write settings: {e}"))
+}
@@ -0,0 +1,123 @@
+//! TLS for the socket server with a self-signed, persisted identity.
+
+use tokio::net::TcpStream;
+
+use log::{info, warn};
+use tauri::State;
+
+use crate::server::handle_socket_connection;
+use crate::settings::SETTINGS_PATH;
+use crate::{LockExt, MdnsState};
+
+// ---- TLS ----
+// With `tls`, start_socket_server wraps every connection in TLS, so input (and the PIN
+// handshake) no longer crosses the LAN in plaintext. The certificate is self-signed,
+// generated on first use and kept next to settings.json so its fingerprint survives
+// restarts. Phones can't check it against a CA; instead they pin the SHA-256
+// fingerprint shown in the app on first connect (trust on first use). A registered
+// service advertises "tls=1" while it is on. Plaintext stays the default.
+const TLS_CERT_FILE: &str = "tls_cert.der";
+const TLS_KEY_FILE: &str = "tls_key.der";
+const TLS_HANDSHAKE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+pub(crate) struct TlsIdentity {
+    pub(crate) acceptor: tokio_rustls::TlsAcceptor,
+    pub(crate) fingerprint: String,
+}
+
+pub(crate) fn load_or_create_tls_identity() -> Result<TlsIdentity, String> {
+    use tokio_rustls::rustls::{
+        self,
+        pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer},
+    };
+
+    let dir = SETTINGS_PATH
+        .get()
+        .and_then(|path| path.parent())
+        .ok_or("Settings location is not available on this system")?;
+    let cert_path = dir.join(TLS_CERT_FILE);
+    let key_path = dir.join(TLS_KEY_FILE);
+
+    let (cert, key) = match (std::fs::read(&cert_path), std::fs::read(&key_path)) {
+        (Ok(cert), Ok(key)) => (cert, key),
+        _ => {
+            info!("Generating a self-signed TLS certificate");
+            let generated = rcgen::generate_simple_self_signed(vec!["bruteconnect.local".into()])
+                .map_err(|e| format!("Failed to generate a TLS certificate: {e}"))?;
+            let cert = generated.cert.der().to_vec();
+            let key = generated.key_pair.serialize_der();
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("failed to create settings directory: {e}"))?;
+            std::fs::write(&cert_path, &cert)
+                .map_err(|e| format!("Failed to save the TLS certificate: {e}"))?;
+            write_private_file(&key_path, &key)
+                .map_err(|e| format!("Failed to save the TLS key: {e}"))?;
+            (cert, key)
+        }
+    };
+
+    let fingerprint = tls_fingerprint(&cert);
+    let provider = std::sync::Arc::new(rustls::crypto::ring::default_provider());
+    let config = rustls::ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| format!("Failed to set up TLS: {e}"))?
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![CertificateDer::from(cert)],
+            PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key)),
+        )
+        .map_err(|e| {
+            format!(
+                "Invalid TLS certificate - delete {} and {} to generate a new one: {e}",
+                cert_path.display(),
+                key_path.display()
+            )
+        })?;
+
+    Ok(TlsIdentity {
+        acceptor: tokio_rustls::TlsAcceptor::from(std::sync::Arc::new(config)),
+        fingerprint,
+    })
+}
+
+// Only the owner may read the private key
+fn write_private_file(path: &std::path::Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents)
+}
+
+// "AB:CD:..." SHA-256 of the certificate, the form phones display for comparison
+pub(crate) fn tls_fingerprint(cert_der: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+
+    Sha256::digest(cert_der)
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+// The handshake runs inside the connection's task so a slow client can't hold up accept
+pub(crate) async fn handle_tls_connection(
+    app: tauri::AppHandle,
+    acceptor: tokio_rustls::TlsAcceptor,
+    stream: TcpStream,
+    addr: std::net::SocketAddr,
+    close: std::sync::Arc<tokio::sync::Notify>,
+) {
+    match tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+        Ok(Ok(stream)) => handle_socket_connection(app, stream, addr, close).await,
+        Ok(Err(e)) => warn!("TLS handshake with {} failed: {}", addr, e),
+        Err(_) => warn!("TLS handshake with {} timed out", addr),
+    }
+}
+
+#[tauri::command]
+pub(crate) fn get_tls_fingerprint(state: State<MdnsState>) -> Option<String> {
+    state.tls_fingerprint.lock_or_recover().clone()
+}
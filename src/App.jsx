@@ -4,12 +4,23 @@ import { listen } from "@tauri-apps/api/event";
 
 // const SERVICE = "_bruteconnect._tcp.local.";
 const SERVICE = "_mdnsconnect._udp.local.";
+const MAX_LOG_LINES = 200;
+// Keep in sync with PROTOCOL_VERSION in src-tauri/src/main.rs
+const PROTOCOL_VERSION = 1;
 
 export default function App() {
   const [devices, setDevices] = useState([]);
   const [discovering, setDiscovering] = useState(false);
   const [advertising, setAdvertising] = useState(false);
   const [socketServerStatus, setSocketServerStatus] = useState({ running: false, port: null });
+  const [pairingPin, setPairingPin] = useState(null);
+  const [clients, setClients] = useState([]);
+  const [inputBlocked, setInputBlocked] = useState(null);
+  const [logLines, setLogLines] = useState([]);
+  const [localIps, setLocalIps] = useState([]);
+  const [inputActivity, setInputActivity] = useState(null);
+  const [useTls, setUseTls] = useState(false);
+  const [socketHealth, setSocketHealth] = useState(null);
 
   useEffect(() => {
     const unsubs = [];
@@ -35,11 +46,30 @@ export default function App() {
       setDevices((prev) => prev.filter((x) => key(x) !== key(d)))
     );
 
+    on("mdns:discovery-stopped", () => setDiscovering(false));
+
+    // Pick up devices found before this component mounted
+    invoke("get_discovered_devices")
+      .then((found) => setDevices((prev) => found.reduce(upsert, prev)))
+      .catch(console.error);
+
+    on("pairing:pin", ({ pin }) => setPairingPin(pin));
+    on("input:permission-denied", (p) => setInputBlocked(p));
+    on("input:activity", (a) => setInputActivity(a));
+    on("socket:status", (s) => setSocketHealth(s));
+    on("log:line", (line) => setLogLines((prev) => [...prev.slice(-(MAX_LOG_LINES - 1)), line]));
+    // The startup event fires before this listener exists
+    invoke("get_pairing_pin").then(setPairingPin).catch(console.error);
+    // Warn about missing input access before the user tries it from the phone
+    invoke("test_input").catch((error) => setInputBlocked({ error, platform: null }));
+    invoke("get_local_ips", { excludeVirtual: true }).then(setLocalIps).catch(console.error);
+
     // Check socket server status on startup
     const checkSocketStatus = async () => {
       try {
         const status = await invoke("get_socket_server_status");
         setSocketServerStatus(status);
+        setClients(await invoke("get_connected_clients"));
       } catch (error) {
         console.error("Failed to get socket server status:", error);
       }
@@ -67,6 +97,13 @@ export default function App() {
     };
   }, []);
 
+  // Hide the "phone is controlling" badge once input stops for a second
+  useEffect(() => {
+    if (!inputActivity) return;
+    const t = setTimeout(() => setInputActivity(null), 1000);
+    return () => clearTimeout(t);
+  }, [inputActivity]);
+
   // Note: Removed beforeunload event listener as cleanup is now handled 
   // properly in the Rust backend during window close events
 
@@ -134,7 +171,7 @@ export default function App() {
 
   const startSocketServer = async () => {
     try {
-      const port = await invoke("start_socket_server");
+      const port = await invoke("start_socket_server", { tls: useTls });
       console.log("Socket server started on port:", port);
       setSocketServerStatus({ running: true, port });
     } catch (error) {
@@ -153,6 +190,14 @@ export default function App() {
     }
   };
 
+  const regeneratePin = async () => {
+    try {
+      setPairingPin(await invoke("regenerate_pin"));
+    } catch (error) {
+      console.error("Failed to regenerate pairing PIN:", error);
+    }
+  };
+
   return (
     <div style={{ padding: "2rem", fontFamily: "sans-serif" }}>
       <h1>BruteConnect (mDNS demo)</h1>
@@ -183,6 +228,15 @@ export default function App() {
         </button>
       </div>
 
+      {inputBlocked && (
+        <div style={{ marginBottom: "1rem", padding: "10px", backgroundColor: "#f8d7da", color: "#721c24", borderRadius: "4px" }}>
+          <strong>Input is blocked:</strong>{" "}
+          {inputBlocked.platform === "macos"
+            ? "grant this app Accessibility access in System Settings > Privacy & Security, then restart it."
+            : `the OS refused simulated input (${inputBlocked.error}).`}
+        </div>
+      )}
+
       <div style={{ marginBottom: "1rem", padding: "10px", backgroundColor: "#f8f9fa", borderRadius: "4px" }}>
         <strong>Status:</strong> 
         <span style={{ color: discovering ? "#28a745" : "#6c757d" }}>
@@ -196,6 +250,9 @@ export default function App() {
         <span style={{ color: socketServerStatus.running ? "#28a745" : "#dc3545" }}>
           {socketServerStatus.running ? ` 🔌 Socket Server: ${socketServerStatus.port}` : " 🔌 Socket Server: Stopped"}
         </span>
+        {inputActivity && (
+          <span style={{ marginLeft: "10px", color: "#007bff" }}>📱 Phone is controlling</span>
+        )}
       </div>
 
       {/* Socket Server Controls */}
@@ -206,15 +263,41 @@ export default function App() {
             Stop Server
           </button>
         ) : (
-          <button onClick={startSocketServer} style={{ backgroundColor: "#28a745", color: "white", padding: "5px 10px", border: "none", borderRadius: "4px", marginLeft: "10px" }}>
-            Start Server
-          </button>
+          <>
+            <button onClick={startSocketServer} style={{ backgroundColor: "#28a745", color: "white", padding: "5px 10px", border: "none", borderRadius: "4px", marginLeft: "10px" }}>
+              Start Server
+            </button>
+            <label style={{ marginLeft: "10px", fontSize: "12px" }}>
+              <input type="checkbox" checked={useTls} onChange={(e) => setUseTls(e.target.checked)} /> Encrypt (TLS)
+            </label>
+          </>
         )}
         <span style={{ marginLeft: "10px", fontSize: "12px", color: "#6c757d" }}>
           {socketServerStatus.running 
-            ? `Running on port ${socketServerStatus.port}` 
+            ? `Running on port ${socketServerStatus.port} (${clients.length} connected${socketHealth ? `, ${socketHealth.commands_processed} commands` : ""})`
             : "Required for device advertising"}
         </span>
+        {localIps.length > 0 && (
+          <div style={{ marginTop: "5px", fontSize: "12px", color: "#6c757d" }}>
+            Connect your phone to one of these addresses: {localIps.join(", ")}
+          </div>
+        )}
+        {socketServerStatus.tls_fingerprint && (
+          <div style={{ marginTop: "5px", fontSize: "12px", color: "#6c757d" }}>
+            🔒 Check that your phone shows this certificate fingerprint:{" "}
+            <span style={{ fontFamily: "monospace" }}>{socketServerStatus.tls_fingerprint}</span>
+          </div>
+        )}
+      </div>
+
+      <div style={{ marginBottom: "1rem", padding: "10px", backgroundColor: "#e9ecef", borderRadius: "4px" }}>
+        <strong>Pairing PIN:</strong>
+        <span style={{ marginLeft: "10px", fontFamily: "monospace", fontSize: "18px", letterSpacing: "2px" }}>
+          {pairingPin ?? "------"}
+        </span>
+        <button onClick={regeneratePin} style={{ backgroundColor: "#6c757d", color: "white", padding: "5px 10px", border: "none", borderRadius: "4px", marginLeft: "10px" }}>
+          New PIN
+        </button>
       </div>
 
       <h2>Discovered Devices: ({devices.length})</h2>
@@ -232,6 +315,11 @@ export default function App() {
               <div style={{ color: "#666", margin: "5px 0" }}>
                 <strong>Address:</strong> {d.addr}:{d.port}
               </div>
+              {d.proto != null && d.proto !== PROTOCOL_VERSION && (
+                <div style={{ color: "#856404", margin: "5px 0" }}>
+                  ⚠️ Speaks protocol {d.proto} (this app: {PROTOCOL_VERSION}){d.version ? `, version ${d.version}` : ""} - commands may not work
+                </div>
+              )}
               {d.txt.length > 0 && (
                 <div style={{ marginTop: "8px" }}>
                   <strong>TXT Records:</strong>
@@ -256,6 +344,15 @@ export default function App() {
           ))}
         </ul>
       )}
+
+      <details style={{ marginTop: "1rem" }}>
+        <summary>Backend log ({logLines.length})</summary>
+        <pre style={{ maxHeight: "300px", overflow: "auto", fontSize: "12px", backgroundColor: "#f8f9fa", padding: "10px" }}>
+          {logLines
+            .map((l) => `${new Date(l.timestamp_ms).toLocaleTimeString()} ${l.level.toUpperCase()} ${l.message}`)
+            .join("\n")}
+        </pre>
+      </details>
     </div>
   );
 }
@@ -0,0 +1,40 @@
+import { useEffect, useState } from "react";
+import { listen } from "@tauri-apps/api/event";
+
+// Rendered in the transparent "laser" window opened by start_laser_pointer. The
+// backend reports the cursor in physical screen pixels; the overlay covers the
+// screen from its top-left corner, so dividing by the scale factor lines them up.
+export default function LaserOverlay() {
+  const [pos, setPos] = useState(null);
+
+  useEffect(() => {
+    document.documentElement.style.background = "transparent";
+    document.body.style.background = "transparent";
+
+    let unlisten;
+    listen("laser:move", (e) => {
+      const scale = window.devicePixelRatio || 1;
+      setPos({ x: e.payload.x / scale, y: e.payload.y / scale });
+    }).then((un) => (unlisten = un));
+
+    return () => unlisten && unlisten();
+  }, []);
+
+  if (!pos) return null;
+
+  return (
+    <div
+      style={{
+        position: "fixed",
+        left: pos.x - 12,
+        top: pos.y - 12,
+        width: 24,
+        height: 24,
+        borderRadius: "50%",
+        background: "rgba(255, 0, 0, 0.55)",
+        boxShadow: "0 0 12px 6px rgba(255, 0, 0, 0.45)",
+        pointerEvents: "none",
+      }}
+    />
+  );
+}
@@ -1,9 +1,13 @@
 import React from "react";
 import ReactDOM from "react-dom/client";
 import App from "./App";
+import LaserOverlay from "./LaserOverlay";
+
+// The laser pointer overlay window loads index.html#laser
+const Root = window.location.hash === "#laser" ? LaserOverlay : App;
 
 ReactDOM.createRoot(document.getElementById("root")).render(
   // <React.StrictMode>
-    <App />
+    <Root />
   // </React.StrictMode>,
 );